@@ -5,9 +5,15 @@ pub fn init(ctx: &mut Ctx, state: &mut State) {
   state.assets.reset = Some(load_img(ctx, "examples/pegs/assets/images/reset.png"));
   state.assets.tile = Some(load_img(ctx, "examples/pegs/assets/images/tile.png"));
   state.assets.shadow = Some(load_img(ctx, "examples/pegs/assets/images/shadow.png"));
-  state.assets.peg_beige = Some(load_img(ctx, "examples/pegs/assets/images/peg_beige.png"));
-  state.assets.peg_blue = Some(load_img(ctx, "examples/pegs/assets/images/peg_blue.png"));
-  state.assets.peg_green = Some(load_img(ctx, "examples/pegs/assets/images/peg_green.png"));
-  state.assets.peg_pink = Some(load_img(ctx, "examples/pegs/assets/images/peg_pink.png"));
-  state.assets.peg_yellow = Some(load_img(ctx, "examples/pegs/assets/images/peg_yellow.png"));
+
+  // the sheets share a texture so pegs of all colors draw in one batch
+  let mut pegs = AtlasBuilder::new(AtlasConfig {
+    page_size: 512,
+    ..Default::default()
+  });
+  for name in &["beige", "blue", "green", "pink", "yellow"] {
+    let filename = format!("examples/pegs/assets/images/peg_{}.json", name);
+    pegs.add_texturepacker(&filename, Pivot::Center).unwrap();
+  }
+  state.assets.pegs = Some(pegs.build(ctx).unwrap());
 }
@@ -23,6 +23,7 @@ pub const RESET_URI: &str = "/assets/images/return.png";
 pub const DEATH_DURATION: u32 = 200;
 pub const DEATH_ALPHA: f32 = 0.0;
 // pub const DEATH_SCALE: Vec2 = vec2(0.0, 0.0);
+pub const DEATH_FLASH_DURATION: u32 = 6; // frames
 
 pub const BUZZ_STATE_DURATION: u32 = 20; // frames
 
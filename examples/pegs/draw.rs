@@ -7,10 +7,10 @@ use crate::types::PegType;
 use crate::types::State;
 use kit::*;
 
-pub fn draw(ctx: &mut Ctx, state: &State) {
+pub fn draw(ctx: &mut Ctx, state: &State, alpha: f32) {
   default_projection_2d(ctx);
   tiles::draw(ctx, state);
-  pegs::draw(ctx, state);
+  pegs::draw(ctx, state, alpha);
   gui::draw(ctx, state);
   // debug::draw(ctx, state);
 }
@@ -8,7 +8,7 @@ const LEAN_THRESHOLD: f32 = 0.5;
 const GROUNDED_THRESHOLD: f32 = 1.0;
 const PEG_SHADOW_FADE_DIST: f32 = 50.0;
 
-pub fn draw(ctx: &mut Ctx, state: &State) {
+pub fn draw(ctx: &mut Ctx, state: &State, alpha: f32) {
   // TODO death animation
   // TODO move animation
   // TODO excited animation (indicates a peg is selected)
@@ -19,18 +19,19 @@ pub fn draw(ctx: &mut Ctx, state: &State) {
       None => {}
       Some(i) => {
         let pos = board_to_screen_position(pos);
-        draw_shadow(ctx, state, i, pos);
-        draw_sprite(ctx, state, i, pos);
+        let z = lerpf(state.pegs.prev_z[i], state.pegs.z[i], alpha);
+        draw_shadow(ctx, state, i, pos, z);
+        draw_sprite(ctx, state, i, pos, z);
       }
     }
   }
 }
 
-fn draw_shadow(ctx: &mut Ctx, state: &State, i: usize, pos: Vec2) {
+/// `peg_z` is the peg's height between the last two updates
+fn draw_shadow(ctx: &mut Ctx, state: &State, i: usize, pos: Vec2, peg_z: f32) {
   let peg_type = state.pegs.peg_type[i];
   let peg_state = state.pegs.state[i];
   let peg_animation_frame = state.pegs.animation[i];
-  let peg_z = state.pegs.z[i];
   let peg_z_vel = state.pegs.z_vel[i];
   let lean = state.pegs.lean[i];
 
@@ -41,13 +42,24 @@ fn draw_shadow(ctx: &mut Ctx, state: &State, i: usize, pos: Vec2) {
     ..Default::default()
   };
   let shadow_pivot = Pivot::Center;
-  kit::draw_image(ctx, shadow_img_id, shadow_pivot, shadow_transform);
+  let style = SpriteStyle::with_alpha(death_alpha(state, i));
+  kit::draw_image_styled(ctx, shadow_img_id, shadow_pivot, shadow_transform, style);
 }
 
-fn draw_sprite(ctx: &mut Ctx, state: &State, i: usize, pos: Vec2) {
+/// fades dying pegs out over the course of their death animation
+fn death_alpha(state: &State, i: usize) -> f32 {
+  match state.pegs.state[i] {
+    PegState::Dying(..) => {
+      let progress = state.pegs.animation[i] as f32 / JUMP_DURATION as f32;
+      lerpf(1.0, DEATH_ALPHA, clampf(progress, 0.0, 1.0))
+    }
+    _ => 1.0,
+  }
+}
+
+fn draw_sprite(ctx: &mut Ctx, state: &State, i: usize, pos: Vec2, z: f32) {
   let peg_type = state.pegs.peg_type[i];
   let peg_state = state.pegs.state[i];
-  let z = state.pegs.z[i];
   let lean = state.pegs.lean[i];
   let anim = state.pegs.animation[i];
 
@@ -85,13 +97,22 @@ fn draw_sprite(ctx: &mut Ctx, state: &State, i: usize, pos: Vec2) {
     _ => pos,
   };
   let pos = vec2(pos.x(), pos.y() + z);
-  kit::draw_sprite(
+
+  // flash red as the peg is hit, then fade away
+  let mut style = SpriteStyle::with_alpha(death_alpha(state, i));
+  if let PegState::Dying(..) = peg_state {
+    if anim < DEATH_FLASH_DURATION {
+      style.flash = vec3(0.6, 0.0, 0.0);
+    }
+  }
+  kit::draw_sprite_styled(
     ctx,
     sprite,
     Transform2d {
       pos,
       ..Default::default()
     },
+    style,
   );
 }
 
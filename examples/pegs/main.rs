@@ -14,21 +14,27 @@ use crate::constants::*;
 use crate::types::*;
 use kit::*;
 use rand;
-use std::time::Duration;
+use std::path::PathBuf;
 
 const TITLE: &str = "Peg Game";
 
-const DT_NANOS: u64 = 1_000_000_000 / 60;
-const DT: Duration = Duration::from_nanos(DT_NANOS);
-
 struct App {
   state: State,
+  /// where to save input when run with `--record <file>`
+  record_path: Option<PathBuf>,
+}
+
+/// the file following a command line flag, if the flag was passed
+fn arg_path(flag: &str) -> Option<PathBuf> {
+  let mut args = std::env::args().skip_while(|arg| arg != flag);
+  args.nth(1).map(PathBuf::from)
 }
 
 impl KApp for App {
   fn new() -> Self {
     Self {
       state: Default::default(),
+      record_path: arg_path("--record"),
     }
   }
 
@@ -36,17 +42,41 @@ impl KApp for App {
     let state = &mut self.state;
 
     assets::init(ctx, state);
-    sprites::init(ctx, state);
+    sprites::init(state);
+
+    // record or replay before anything random happens
+    if self.record_path.is_some() {
+      start_recording(ctx);
+    } else if let Some(path) = arg_path("--replay") {
+      match load_recording(&path) {
+        Ok(recording) => start_replay(ctx, recording),
+        Err(err) => println!("{}", err),
+      }
+    }
 
     // set initial pegs on board
-    utils::populate(state);
+    utils::populate(ctx, state);
   }
 
   fn frame(&mut self, ctx: &mut Ctx) {
-    let state = &mut self.state;
-    update::update(ctx, state);
+    update::input(ctx, &mut self.state);
+  }
+
+  fn update(&mut self, ctx: &mut Ctx, dt: f32) {
+    update::update(ctx, &mut self.state);
+  }
+
+  fn draw(&mut self, ctx: &mut Ctx, alpha: f32) {
     // TODO pegs kinda blend together when overlapping; differentiate somehow
-    draw::draw(ctx, state);
+    draw::draw(ctx, &self.state, alpha);
+  }
+
+  fn cleanup(&mut self, ctx: &mut Ctx) {
+    if let (Some(path), Some(recording)) = (&self.record_path, stop_recording(ctx)) {
+      if let Err(err) = save_recording(&recording, path) {
+        println!("{}", err);
+      }
+    }
   }
 }
 
@@ -5,7 +5,7 @@ use kit::*;
 pub fn update(ctx: &Ctx, state: &mut State, from_pos: Coords) {
   let click = ctx.input.mouse.left.down > 0;
   if click {
-    let click_pos = window_to_world(ctx, ctx.input.mouse.pos);
+    let click_pos = window_to_world_2d(ctx, ctx.input.mouse.pos);
     let mut clicked_tile = None;
     for pos in state.board.iterator() {
       let tile_bounds = tile_bounds(ctx, pos);
@@ -1,16 +1,21 @@
 use crate::types::*;
 use kit::*;
 
-mod peg_beige;
-mod peg_blue;
-mod peg_green;
-mod peg_pink;
-mod peg_yellow;
+pub fn init(state: &mut State) {
+  let sheets = &state.assets.pegs.as_ref().unwrap().sheets;
+  state.sprites.peg_beige = peg_sheet(&sheets[PegType::Beige as usize]);
+  state.sprites.peg_blue = peg_sheet(&sheets[PegType::Blue as usize]);
+  state.sprites.peg_green = peg_sheet(&sheets[PegType::Green as usize]);
+  state.sprites.peg_pink = peg_sheet(&sheets[PegType::Pink as usize]);
+  state.sprites.peg_yellow = peg_sheet(&sheets[PegType::Yellow as usize]);
+}
 
-pub fn init(ctx: &mut Ctx, state: &mut State) {
-  peg_beige::init(ctx, state);
-  peg_blue::init(ctx, state);
-  peg_green::init(ctx, state);
-  peg_pink::init(ctx, state);
-  peg_yellow::init(ctx, state);
+fn peg_sheet(sheet: &Spritesheet) -> PegSheet {
+  PegSheet {
+    duck: sheet.sprite("duck"),
+    front: sheet.sprite("front"),
+    hurt: sheet.sprite("hurt"),
+    jump: sheet.sprite("jump"),
+    lean: sheet.sprite("lean"),
+  }
 }
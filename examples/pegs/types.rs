@@ -106,11 +106,8 @@ pub struct Assets {
   pub reset: Option<Texture>,
   pub tile: Option<Texture>,
   pub shadow: Option<Texture>,
-  pub peg_beige: Option<Texture>,
-  pub peg_blue: Option<Texture>,
-  pub peg_green: Option<Texture>,
-  pub peg_pink: Option<Texture>,
-  pub peg_yellow: Option<Texture>,
+  /// every peg's spritesheet, in `PegType` order
+  pub pegs: Option<Atlas>,
 }
 
 #[derive(Default)]
@@ -136,6 +133,8 @@ pub struct Pegs {
   pub state: [PegState; MAX_PEGS],
   pub animation: [u32; MAX_PEGS],
   pub z: [f32; MAX_PEGS],
+  /// `z` as of the previous update, to draw in between updates
+  pub prev_z: [f32; MAX_PEGS],
   pub z_vel: [f32; MAX_PEGS],
   pub lean: [f32; MAX_PEGS],
 }
@@ -147,6 +146,7 @@ impl Default for Pegs {
       state: [PegState::Idle; MAX_PEGS],
       animation: [0; MAX_PEGS],
       z: [0.0; MAX_PEGS],
+      prev_z: [0.0; MAX_PEGS],
       z_vel: [0.0; MAX_PEGS],
       lean: [0.0; MAX_PEGS],
     }
@@ -8,17 +8,24 @@ mod phase_jump;
 mod phase_picking;
 mod phase_reset;
 
-pub fn update(ctx: &Ctx, state: &mut State) {
+/// handles clicks, once per frame since input counts are per frame
+pub fn input(ctx: &mut Ctx, state: &mut State) {
   // TODO gamepad input
   // TODO animate peg hover state
   // TODO highlight valid moves
 
   update_mouse_pos(ctx, state);
   update_over_peg(ctx, state);
+  input_phase(ctx, state);
+  update_reset(ctx, state);
+}
+
+/// advances animations by one fixed step
+pub fn update(ctx: &mut Ctx, state: &mut State) {
+  state.pegs.prev_z = state.pegs.z;
   update_peg_animations(ctx, state);
   update_peg_z(ctx, state);
   update_phase(ctx, state);
-  update_reset(ctx, state);
 }
 
 fn update_reset(ctx: &Ctx, state: &mut State) {
@@ -47,7 +54,7 @@ fn update_reset(ctx: &Ctx, state: &mut State) {
 /// the state so we only have to do this once per frame
 fn update_mouse_pos(ctx: &Ctx, state: &mut State) {
   let mouse_pos = ctx.input.mouse.pos;
-  state.mouse_pos = window_to_world(ctx, mouse_pos);
+  state.mouse_pos = window_to_world_2d(ctx, mouse_pos);
 }
 
 /// calculates whether the current mouse world position is over
@@ -69,12 +76,19 @@ fn update_over_peg(ctx: &Ctx, state: &mut State) {
   }
 }
 
-fn update_phase(ctx: &Ctx, state: &mut State) {
+fn input_phase(ctx: &mut Ctx, state: &mut State) {
   match state.phase {
     Phase::Picking => phase_picking::update(ctx, state),
     Phase::Excited(pos) => phase_excited::update(ctx, state, pos),
+    _ => {}
+  }
+}
+
+fn update_phase(ctx: &mut Ctx, state: &mut State) {
+  match state.phase {
     Phase::Jump => phase_jump::update(ctx, state),
     Phase::Reset => phase_reset::update(ctx, state),
+    _ => {}
   }
 }
 
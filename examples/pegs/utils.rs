@@ -130,34 +130,6 @@ pub fn window_center_px(ctx: &Ctx) -> Vec2 {
   vec2(window_width(ctx) as f32, window_height(ctx) as f32) / 2.0
 }
 
-pub fn window_to_world(ctx: &Ctx, p: Vec2) -> Vec2 {
-  let window_width = window_width(ctx) as f32;
-  let window_height = window_height(ctx) as f32;
-  let world_pos = vec2(p.x() - window_width / 2.0, window_height / 2.0 - p.y());
-
-  // TODO mouse coords are y-down and relative to the upper
-  // left corner of the screen - kit's default 2D rendering
-  // view uses y-up and the origin is at the center of the
-  // window. This makes converting mouse pos to world pos
-  // for hit testing a bit hairy (see below for a general
-  // approach). Could I do GPU-based hit testing? How can
-  // I make hit testing simpler? Should the default coord
-  // system use the window coordinates (y-down, upper-left
-  // origin)?
-  // let world_to_window_vp = ctx.gl.view * ctx.gl.proj;
-  // let window_to_world_vp = world_to_window_vp.inverse();
-  // let window_normalized_pos = vec4(
-  //   lerpf(-1.0, 1.0, p.x() / window_width),
-  //   lerpf(1.0, -1.0, p.y() / window_height),
-  //   0.0,
-  //   1.0,
-  // );
-  // let world_pos = window_to_world_vp * window_normalized_pos;
-  // let world_pos = vec2(world_pos.x(), world_pos.y());
-
-  world_pos
-}
-
 pub fn grounded(state: &State, peg_i: usize) -> bool {
   let z = state.pegs.z[peg_i];
   let z_vel = state.pegs.z_vel[peg_i];
@@ -165,16 +137,18 @@ pub fn grounded(state: &State, peg_i: usize) -> bool {
 }
 
 /// fills the game board with pegs; only call when the board is empty!
-pub fn populate(state: &mut State) {
+pub fn populate(ctx: &mut Ctx, state: &mut State) {
   let empty = Coords { x: 1, y: 2 };
   let mut peg_i = 0;
   for pos in state.board.iterator() {
     if pos == empty {
       continue;
     }
-    let peg_type: PegType = rand::random();
+    let peg_type: PegType = random(ctx);
     state.pegs.peg_type[peg_i] = peg_type;
-    state.pegs.z[peg_i] = rand::random::<f32>() * DROP_HEIGHT_VARIANCE + DROP_HEIGHT_MIN;
+    let z = random::<f32>(ctx) * DROP_HEIGHT_VARIANCE + DROP_HEIGHT_MIN;
+    state.pegs.z[peg_i] = z;
+    state.pegs.prev_z[peg_i] = z;
     state.board.set(pos, Some(peg_i));
     peg_i += 1;
   }
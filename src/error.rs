@@ -0,0 +1,89 @@
+//! Errors that can come out of engine setup and asset loading.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Something that went wrong inside kit. Asset loaders have `try_` variants
+/// that return this so the game can decide how to recover, e.g. by showing
+/// a message or using `placeholder_img`.
+#[derive(Debug)]
+pub enum Error {
+  /// an image file couldn't be opened or decoded
+  Image {
+    path: PathBuf,
+    source: image::ImageError,
+  },
+  /// a file couldn't be read or written
+  Io { path: PathBuf, source: io::Error },
+  /// a line in an input bindings file couldn't be understood
+  Bindings { line: usize, message: String },
+  /// an input recording file is corrupt or from a different version of kit
+  Recording { path: PathBuf },
+  /// a spritesheet's data file couldn't be understood
+  Spritesheet { path: PathBuf, message: String },
+  /// a font file couldn't be understood
+  Font { path: PathBuf, message: String },
+  /// an image is too big to fit on an atlas page
+  AtlasPageTooSmall { name: String, page_size: u32 },
+  /// pixel data added to an atlas isn't `w * h` RGBA8 pixels
+  AtlasPixelsWrongSize {
+    name: String,
+    w: u32,
+    h: u32,
+    len: usize,
+  },
+  /// the graphics backend failed to initialize
+  GraphicsSetup,
+  /// there are no shaders for the named primitive on the current graphics backend
+  UnsupportedBackend { primitive: &'static str },
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Error::Image { path, source } => write!(f, "Error loading image at {:?}: {}", path, source),
+      Error::Io { path, source } => write!(f, "Error accessing file at {:?}: {}", path, source),
+      Error::Bindings { line, message } => {
+        write!(
+          f,
+          "Error reading input bindings on line {}: {}",
+          line, message
+        )
+      }
+      Error::Recording { path } => write!(f, "Error reading input recording at {:?}", path),
+      Error::Spritesheet { path, message } => {
+        write!(f, "Error reading spritesheet at {:?}: {}", path, message)
+      }
+      Error::Font { path, message } => {
+        write!(f, "Error reading font at {:?}: {}", path, message)
+      }
+      Error::AtlasPageTooSmall { name, page_size } => write!(
+        f,
+        "Error packing atlas: {} doesn't fit on a {}x{} page",
+        name, page_size, page_size
+      ),
+      Error::AtlasPixelsWrongSize { name, w, h, len } => write!(
+        f,
+        "Error adding {} to atlas: {} bytes of pixel data isn't a {}x{} RGBA image",
+        name, len, w, h
+      ),
+      Error::GraphicsSetup => write!(f, "Error setting up graphics"),
+      Error::UnsupportedBackend { primitive } => write!(
+        f,
+        "{} shaders not implemented for this graphics backend",
+        primitive
+      ),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Error::Image { source, .. } => Some(source),
+      Error::Io { source, .. } => Some(source),
+      _ => None,
+    }
+  }
+}
@@ -3,7 +3,7 @@
 //! TODO move hittesting to a separate crate?
 
 use crate::math::*;
-use rand;
+use crate::{random, Ctx};
 use std::fmt;
 use std::ops::Add;
 
@@ -136,6 +136,36 @@ impl fmt::Display for Shape {
   }
 }
 
+/// A half-line starting at `origin` and heading along `dir`, which should be
+/// normalized. See `screen_ray`.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct Ray {
+  pub origin: Vec3,
+  pub dir: Vec3,
+}
+
+impl Ray {
+  /// the point `t` units along the ray
+  pub fn at(self, t: f32) -> Vec3 {
+    self.origin + self.dir * t
+  }
+
+  /// Returns how far along the ray it crosses the plane through `point` with
+  /// the given `normal`, or `None` if it runs parallel to or away from it.
+  pub fn intersect_plane(self, point: Vec3, normal: Vec3) -> Option<f32> {
+    let facing = self.dir.dot(normal);
+    if facing.abs() < f32::EPSILON {
+      return None;
+    }
+    let t = (point - self.origin).dot(normal) / facing;
+    if t < 0.0 {
+      None
+    } else {
+      Some(t)
+    }
+  }
+}
+
 pub struct ProjectionResult {
   pub step: Vec2,
   pub step_mag: f32,
@@ -868,18 +898,20 @@ pub fn sweep(step: Vec2, a: Shape, b: Shape) -> SweepResult {
   }
 }
 
-pub fn rand_in_shape(shape: Shape) -> Vec2 {
+/// a random point inside `shape`, drawn from the ctx's seeded generator so
+/// that it repeats along with everything else after `seed_random`
+pub fn rand_in_shape(ctx: &mut Ctx, shape: Shape) -> Vec2 {
   match shape {
     Shape::Point(p) => p,
     Shape::Rect(r) => {
-      let x = lerpf(r.min_x, r.max_x, rand::random());
-      let y = lerpf(r.min_y, r.max_y, rand::random());
+      let x = lerpf(r.min_x, r.max_x, random(ctx));
+      let y = lerpf(r.min_y, r.max_y, random(ctx));
       return vec2(x, y);
     }
     Shape::Circle(c) => {
-      let random_arc: f32 = rand::random();
+      let random_arc: f32 = random(ctx);
       let a: f32 = random_arc * TAU;
-      let r = c.r * f32::sqrt(rand::random());
+      let r = c.r * f32::sqrt(random(ctx));
 
       // Cartesian coordinates
       let x = r * f32::cos(a);
@@ -889,3 +921,174 @@ pub fn rand_in_shape(shape: Shape) -> Vec2 {
     }
   }
 }
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Splits a polygon into triangles, returned as indices into `points`. The
+/// polygon can be convex or concave and wound either way, but its edges
+/// shouldn't cross; if they do, every point is still covered, just not
+/// necessarily correctly.
+pub fn triangulate(points: &[Vec2]) -> Vec<[usize; 3]> {
+  let n = points.len();
+  if n < 3 {
+    return vec![];
+  }
+
+  // ear clipping: repeatedly cut off a corner with no other points inside,
+  // working counter clockwise so corners that bulge out have positive area
+  let area: f32 = (0..n).map(|i| cross(points[i], points[(i + 1) % n])).sum();
+  let mut remaining: Vec<usize> = if area >= 0.0 {
+    (0..n).collect()
+  } else {
+    (0..n).rev().collect()
+  };
+
+  let mut triangles = Vec::with_capacity(n - 2);
+  let mut i = 0;
+  let mut since_ear = 0;
+  while remaining.len() > 3 {
+    let m = remaining.len();
+    i %= m;
+    let prev = remaining[(i + m - 1) % m];
+    let next = remaining[(i + 1) % m];
+    let ear = [prev, remaining[i], next];
+    // with no ear left the polygon crosses itself, so cut one off anyway
+    if since_ear >= m || is_ear(points, &remaining, ear) {
+      triangles.push(ear);
+      remaining.remove(i);
+      since_ear = 0;
+    } else {
+      i += 1;
+      since_ear += 1;
+    }
+  }
+  triangles.push([remaining[0], remaining[1], remaining[2]]);
+  triangles
+}
+
+/// the z of the 3d cross product, positive when `b` is counter clockwise
+/// from `a`
+fn cross(a: Vec2, b: Vec2) -> f32 {
+  a.x() * b.y() - a.y() * b.x()
+}
+
+/// whether a counter clockwise corner bulges out with no other points
+/// inside it
+fn is_ear(points: &[Vec2], remaining: &[usize], [a, b, c]: [usize; 3]) -> bool {
+  let (pa, pb, pc) = (points[a], points[b], points[c]);
+  if cross(pb - pa, pc - pb) <= 0.0 {
+    return false;
+  }
+  remaining.iter().all(|&i| {
+    let p = points[i];
+    // points on the corner itself, including duplicates, don't count
+    if p == pa || p == pb || p == pc {
+      return true;
+    }
+    let inside = cross(pb - pa, p - pa) >= 0.0
+      && cross(pc - pb, p - pb) >= 0.0
+      && cross(pa - pc, p - pc) >= 0.0;
+    !inside
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn polygon_area(points: &[Vec2]) -> f32 {
+    let n = points.len();
+    (0..n)
+      .map(|i| cross(points[i], points[(i + 1) % n]))
+      .sum::<f32>()
+      .abs()
+      * 0.5
+  }
+
+  /// Checks there are n - 2 triangles, all wound counter clockwise, covering
+  /// exactly the polygon's area. Overlapping triangles would add up to more.
+  fn assert_triangulates(points: &[Vec2]) {
+    let triangles = triangulate(points);
+    assert_eq!(triangles.len(), points.len() - 2);
+    let mut total = 0.0;
+    for [a, b, c] in &triangles {
+      let (pa, pb, pc) = (points[*a], points[*b], points[*c]);
+      let area = cross(pb - pa, pc - pa) * 0.5;
+      assert!(area >= 0.0, "triangle {:?} is wound clockwise", [a, b, c]);
+      total += area;
+    }
+    let expected = polygon_area(points);
+    assert!(
+      (total - expected).abs() < 1e-4,
+      "triangles cover {} but the polygon is {}",
+      total,
+      expected
+    );
+  }
+
+  #[test]
+  fn triangulates_convex_polygons() {
+    let hexagon: Vec<Vec2> = (0..6)
+      .map(|i| {
+        let angle = i as f32 * std::f32::consts::PI / 3.0;
+        vec2(angle.cos(), angle.sin())
+      })
+      .collect();
+    assert_triangulates(&hexagon);
+  }
+
+  #[test]
+  fn triangulates_concave_polygons() {
+    let l_shape = [
+      vec2(0.0, 0.0),
+      vec2(2.0, 0.0),
+      vec2(2.0, 1.0),
+      vec2(1.0, 1.0),
+      vec2(1.0, 2.0),
+      vec2(0.0, 2.0),
+    ];
+    assert_triangulates(&l_shape);
+  }
+
+  #[test]
+  fn triangulates_clockwise_polygons() {
+    let l_shape = [
+      vec2(0.0, 2.0),
+      vec2(1.0, 2.0),
+      vec2(1.0, 1.0),
+      vec2(2.0, 1.0),
+      vec2(2.0, 0.0),
+      vec2(0.0, 0.0),
+    ];
+    assert_triangulates(&l_shape);
+  }
+
+  #[test]
+  fn triangulates_collinear_points() {
+    let square = [
+      vec2(0.0, 0.0),
+      vec2(1.0, 0.0),
+      vec2(2.0, 0.0),
+      vec2(2.0, 2.0),
+      vec2(0.0, 2.0),
+    ];
+    assert_triangulates(&square);
+  }
+
+  #[test]
+  fn triangulates_duplicate_points() {
+    let square = [
+      vec2(0.0, 0.0),
+      vec2(2.0, 0.0),
+      vec2(2.0, 0.0),
+      vec2(2.0, 2.0),
+      vec2(0.0, 2.0),
+    ];
+    assert_triangulates(&square);
+  }
+
+  #[test]
+  fn needs_three_points() {
+    assert!(triangulate(&[vec2(0.0, 0.0), vec2(1.0, 0.0)]).is_empty());
+  }
+}
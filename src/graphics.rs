@@ -1,13 +1,25 @@
 //! Graphics commands for drawing primitives.
+mod atlas;
+mod cff;
 mod circle;
+mod draw_list;
+mod glyph_cache;
+mod layer;
 mod line;
 mod mesh;
 mod point;
+mod polygon;
 mod quad;
+mod raster;
 mod rect;
 mod sprite;
+mod spritesheet;
+mod text;
+mod triangle;
+mod truetype;
 
 use crate::geometry::*;
+use crate::graphics::draw_list::push_cmd;
 use crate::math::*;
 use crate::*;
 use core::mem::size_of;
@@ -16,9 +28,6 @@ use image::*;
 use std::f32;
 use std::path::Path;
 
-const BYTES_POINTS: usize = size_of::<DrawPoint>() * MAX_POINTS;
-const BYTES_LINES: usize = size_of::<DrawLine>() * MAX_LINES;
-
 // struct MeshVsParams {
 //     model: Mat4,
 //     view_proj: Mat4,
@@ -30,12 +39,19 @@ const BYTES_LINES: usize = size_of::<DrawLine>() * MAX_LINES;
 // these build on the drawing primitives to draw special game structures like
 // sprite sheets and circles
 
-pub use circle::draw_circ;
-pub use line::draw_line;
-pub use mesh::draw_mesh;
-pub use point::draw_point;
-pub use rect::draw_rect;
+pub use atlas::*;
+pub use circle::*;
+pub use layer::*;
+pub use line::{draw_line, draw_line_on};
+pub use mesh::{draw_mesh, draw_mesh_on};
+pub use point::{draw_point, draw_point_on};
+pub use polygon::*;
+pub use rect::*;
 pub use sprite::*;
+pub use spritesheet::*;
+pub use text::*;
+pub use triangle::{fill_triangle, fill_triangle_on};
+pub(crate) use truetype::TtfFont;
 
 // TODO reimplement
 // pub fn draw_mesh(&mut self, mesh_i: u8, transform: Mat4) {
@@ -47,29 +63,70 @@ pub use sprite::*;
 /// general draw call for drawing a shape primitive
 
 pub fn draw_shape(ctx: &mut Ctx, shape: Shape, color: Vec4) {
+  draw_shape_on(ctx, Layer::World, shape, color);
+}
+
+/// `draw_shape` on a specific render layer
+pub fn draw_shape_on(ctx: &mut Ctx, layer: Layer, shape: Shape, color: Vec4) {
   match shape {
-    Shape::Point(p) => draw_point(ctx, p.extend(0.0), color),
-    Shape::Rect(r) => draw_rect(ctx, r, color),
-    Shape::Circle(c) => draw_circ(ctx, c, color),
+    Shape::Point(p) => draw_point_on(ctx, layer, p.extend(0.0), color),
+    Shape::Rect(r) => draw_rect_on(ctx, layer, r, color),
+    Shape::Circle(c) => draw_circ_on(ctx, layer, c, color),
   }
 }
 
-/// draws text on screen
-pub fn draw_text(ctx: &mut Ctx, text: &str, font: usize, size: f32, pos: Vec2) {}
+/// Fills a shape with a solid color. Points have no area, so they're drawn
+/// as they are by `draw_shape`.
+pub fn fill_shape(ctx: &mut Ctx, shape: Shape, color: Vec4) {
+  fill_shape_on(ctx, Layer::World, 0.0, shape, color);
+}
+
+/// `fill_shape` on a specific render layer and depth
+pub fn fill_shape_on(ctx: &mut Ctx, layer: Layer, z: f32, shape: Shape, color: Vec4) {
+  match shape {
+    Shape::Point(p) => draw_point_on(ctx, layer, p.extend(z), color),
+    Shape::Rect(r) => fill_rect_on(ctx, layer, z, r, color),
+    Shape::Circle(c) => fill_circle_on(ctx, layer, z, c, color),
+  }
+}
 
 // ----------------------------------------------------------------------------
 // GETTERS
 
 /// the current window width
-pub fn window_width(_: &Ctx) -> f32 {
+pub fn window_width(ctx: &Ctx) -> f32 {
+  if ctx.gfx.headless {
+    return HEADLESS_WIDTH;
+  }
   (sapp_width() as f32)
 }
 
 /// the current window height
-pub fn window_height(_: &Ctx) -> f32 {
+pub fn window_height(ctx: &Ctx) -> f32 {
+  if ctx.gfx.headless {
+    return HEADLESS_HEIGHT;
+  }
   (sapp_height() as f32)
 }
 
+/// How many device pixels there are per window point, e.g. 2.0 on most
+/// high dpi displays when the app asks for a full resolution canvas. Text
+/// and ui sized in points can be multiplied by this to look the same on
+/// every display.
+pub fn dpi_scale(ctx: &Ctx) -> f32 {
+  if ctx.gfx.headless {
+    return 1.0;
+  }
+  sapp_dpi_scale()
+}
+
+/// The number of draw calls sent to the gpu for the last frame, or that
+/// would have been in headless runs. Sprites sharing a texture are drawn
+/// together, so this stays low when they're packed into an atlas.
+pub fn draw_calls(ctx: &Ctx) -> usize {
+  ctx.gfx.draw_calls
+}
+
 /// the current aspect ratio of the application window
 pub fn aspect(ctx: &mut Ctx) -> f32 {
   // TODO memoize in state on window size change
@@ -77,30 +134,94 @@ pub fn aspect(ctx: &mut Ctx) -> f32 {
 }
 
 /// half the current window width in device pixels
-pub fn window_width_half(_: &Ctx) -> f32 {
+pub fn window_width_half(ctx: &Ctx) -> f32 {
   // TODO memoize in state on window size change
-  (sapp_width() as f32) / 2.0
+  window_width(ctx) / 2.0
 }
 
 /// half the current window height in device pixels
-pub fn window_height_half(_: &Ctx) -> f32 {
+pub fn window_height_half(ctx: &Ctx) -> f32 {
   // TODO memoize in state on window size change
-  (sapp_height() as f32) / 2.0
+  window_height(ctx) / 2.0
 }
 
-/// returns a world position corresponding to the given window position. This is a bit faster
-/// than the corresponding
-
+/// Returns a world position corresponding to the given window position, on
+/// the z = 0 plane used by 2d games. See `screen_to_world` for other depths.
+///
+/// This follows the current `proj` and `view` like the functions below. It
+/// used to assume the default 2d projection whatever the camera was, so
+/// games that never call `default_projection_2d` (or set their own camera)
+/// now get positions through the identity projection, from -1 to 1 across
+/// the window, rather than pixels from its center.
 pub fn window_to_world_2d(ctx: &Ctx, p: Vec2) -> Vec2 {
-  let window_width = window_width(ctx) as f32;
-  let window_height = window_height(ctx) as f32;
-  let world_pos = vec2(p.x() - window_width / 2.0, window_height / 2.0 - p.y());
-  world_pos
+  let ray = screen_ray(ctx, p);
+  match ray.intersect_plane(Vec3::zero(), Vec3::unit_z()) {
+    Some(t) => ray.at(t).truncate(),
+    // the plane is out of view, so the best we can do is the near plane
+    None => ray.origin.truncate(),
+  }
+}
+
+// ----------------------------------------------------------------------------
+// COORDINATE CONVERSION
+//
+// window positions are in device pixels with the origin at the top left and
+// y pointing down, like `MouseCtx::pos`. These convert through the current
+// `proj` and `view`, so they keep working after panning, zooming or switching
+// to a perspective camera.
+
+/// Returns the world position under a window position. `depth` picks a point
+/// between the near plane (0) and the far plane (1) of the projection.
+pub fn screen_to_world(ctx: &Ctx, p: Vec2, depth: f32) -> Vec3 {
+  let ndc = window_to_ndc(ctx, p).extend(depth * 2.0 - 1.0);
+  let world = view_proj(ctx).inverse() * ndc.extend(1.0);
+  vec3(world.x(), world.y(), world.z()) / world.w()
+}
+
+/// Returns the ray from the camera through a window position, e.g. for
+/// picking objects in a 3d scene. The ray starts on the near plane.
+pub fn screen_ray(ctx: &Ctx, p: Vec2) -> Ray {
+  let near = screen_to_world(ctx, p, 0.0);
+  let far = screen_to_world(ctx, p, 1.0);
+  Ray {
+    origin: near,
+    dir: (far - near).normalize(),
+  }
+}
+
+/// Returns the window position a world position is drawn at, or `None` if
+/// it's behind the camera.
+pub fn world_to_screen(ctx: &Ctx, p: Vec3) -> Option<Vec2> {
+  let clip = view_proj(ctx) * p.extend(1.0);
+  if clip.w() <= 0.0 {
+    return None;
+  }
+  let ndc = vec2(clip.x(), clip.y()) / clip.w();
+  Some(vec2(
+    (ndc.x() + 1.0) / 2.0 * window_width(ctx),
+    (1.0 - ndc.y()) / 2.0 * window_height(ctx),
+  ))
+}
+
+/// converts a window position to normalized device coordinates
+fn window_to_ndc(ctx: &Ctx, p: Vec2) -> Vec2 {
+  vec2(
+    p.x() / window_width(ctx) * 2.0 - 1.0,
+    1.0 - p.y() / window_height(ctx) * 2.0,
+  )
+}
+
+/// the world camera transform
+fn view_proj(ctx: &Ctx) -> Mat4 {
+  ctx.gfx.proj * ctx.gfx.view
 }
 
 // ----------------------------------------------------------------------------
 // GRAPHICS SETUP
 
+/// how far in front of and behind the camera the default 2d projection reaches
+pub(crate) const DEPTH_2D: f32 = 100_000.0;
+
 /// configures kit to use the default 2d projection for rendering.
 /// In this projection, 1 world unit is equal to 1 device pixel.
 /// However, we set the world origin to be the center of the screen and y points up.
@@ -110,7 +231,10 @@ pub fn default_projection_2d(ctx: &mut Ctx) {
   let half_w = window_width_half(ctx);
   let half_h = window_height_half(ctx);
   let camera_pos = vec3(0.0, 0.0, 6.0);
-  ctx.gfx.proj = Mat4::orthographic_rh_gl(-half_w, half_w, -half_h, half_h, f32::MIN, f32::MAX);
+  // the depth range has to be finite for the projection to be invertible,
+  // which `screen_to_world` relies on
+  let (near, far) = (-DEPTH_2D, DEPTH_2D);
+  ctx.gfx.proj = Mat4::orthographic_rh_gl(-half_w, half_w, -half_h, half_h, near, far);
   ctx.gfx.view = Mat4::look_at_rh(camera_pos, Vec3::zero(), Vec3::unit_y());
 }
 
@@ -120,39 +244,64 @@ pub fn default_projection_2d(ctx: &mut Ctx) {
 /// Loads an image into memory. Returns info about the image, including width,
 /// height, and an id for setting the image for use in draw calls.
 ///
+/// If the image can't be loaded, the error is printed and a placeholder
+/// texture is returned instead. Use `try_load_img` to handle failures yourself.
+///
 /// TODO unloading
 pub fn load_img(ctx: &mut Ctx, filename: &str) -> Texture {
-  let id = ctx.gfx.images.count;
-  ctx.gfx.images.count += 1;
+  match try_load_img(ctx, filename) {
+    Ok(texture) => texture,
+    Err(e) => {
+      println!("{}", e);
+      placeholder_img(ctx)
+    }
+  }
+}
 
+/// Loads an image into memory, like `load_img`, but returns an error if the
+/// image can't be read or decoded.
+pub fn try_load_img(ctx: &mut Ctx, filename: &str) -> Result<Texture, Error> {
   // TODO get the true path using the base... is this needed or does the Rust std lib do this for me?
   let path = application_root_dir().join(filename);
 
   // TODO when I switch to OpenGL, I may just want to use a surface to load pixel data
-  let img = image::open(path.clone());
-
-  let (img_ptr, w, h) = match img {
-    Err(e) => {
-      println!("Error loading image at {:?}: {}", path, e);
-      let img_fallback: Vec<u8> = vec![0];
-      (img_fallback.as_ptr(), 1, 1)
-    }
-    Ok(img) => {
-      let img = img.into_rgba();
-      let (w, h) = img.dimensions();
-      let img_data = img.into_raw();
-      let img_ptr: *const u8 = img_data.as_ptr();
-      (img_ptr, w, h)
-    }
+  let img = match image::open(&path) {
+    Ok(img) => img.into_rgba(),
+    Err(source) => return Err(Error::Image { path, source }),
   };
 
+  let (w, h) = img.dimensions();
+  Ok(add_img(ctx, &img.into_raw(), w, h))
+}
+
+/// Creates a small magenta and black checkerboard texture. Useful as a
+/// stand-in for images that failed to load, since it's hard to miss.
+pub fn placeholder_img(ctx: &mut Ctx) -> Texture {
+  const MAGENTA: [u8; 4] = [0xFF, 0x00, 0xFF, 0xFF];
+  const BLACK: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+  let pixels = [MAGENTA, BLACK, BLACK, MAGENTA].concat();
+  add_img(ctx, &pixels, 2, 2)
+}
+
+/// uploads RGBA8 pixel data as a new texture
+pub(crate) fn add_img(ctx: &mut Ctx, pixels: &[u8], w: u32, h: u32) -> Texture {
+  let id = ctx.gfx.images.e.len();
+
+  // there's no gpu to upload to, but dimensions are still useful to the game
+  if ctx.gfx.headless {
+    ctx.gfx.images.e.push(Image {
+      e: Default::default(),
+      w,
+      h,
+    });
+    return Texture { id, w, h };
+  }
+
   let width = w as i32;
   let height = h as i32;
-
-  // let num_channels = num_channels as i32;
-  let size: i32 = width * height * 8 /* bytes per pixel */;
+  let size: i32 = width * height * 4 /* bytes per pixel */;
   let e = sg_make_image(
-    Some(&[(img_ptr, size)]),
+    Some(&[(pixels.as_ptr(), size)]),
     &SgImageDesc {
       width,
       height,
@@ -165,7 +314,7 @@ pub fn load_img(ctx: &mut Ctx, filename: &str) -> Texture {
     },
   );
 
-  ctx.gfx.images.e[id] = Image { e, w, h };
+  ctx.gfx.images.e.push(Image { e, w, h });
   Texture { id, w, h }
 }
 
@@ -184,52 +333,206 @@ pub(crate) fn std_uniform_block<'a>() -> SgShaderUniformBlockDesc<'a> {
   }
 }
 
+/// Makes a stream vertex buffer with room for `capacity` items of type `T`.
+
+pub(crate) fn make_stream_buffer<T>(capacity: usize) -> SgBuffer {
+  sg_make_buffer::<()>(
+    None,
+    &SgBufferDesc {
+      buffer_type: SgBufferType::VertexBuffer,
+      size: max(capacity, 1) * size_of::<T>(),
+      usage: SgUsage::Stream,
+    },
+  )
+}
+
+/// Replaces a stream vertex buffer with a bigger one if its draw buffer has
+/// grown past what it was made to hold. Returns true if it was replaced.
+
+pub(crate) fn fit_stream_buffer<T>(
+  buffer: &mut SgBuffer,
+  gpu_capacity: &mut usize,
+  capacity: usize,
+) -> bool {
+  if capacity <= *gpu_capacity {
+    return false;
+  }
+  sg_destroy_buffer(*buffer);
+  *buffer = make_stream_buffer::<T>(capacity);
+  *gpu_capacity = capacity;
+  true
+}
+
 // ----------------------------------------------------------------------------
 // LIFECYCLE
 
 /// lifecycle function for initial setup and sensible defaults. Needs to be
-/// run *after* window initialization.
-
-pub fn init(ctx: &mut Ctx) {
-  sg_setup(&SgDesc {
-    ..Default::default()
-  });
+/// run *after* window initialization. Fails if the graphics backend can't be
+/// set up or if kit has no shaders for it.
 
+pub fn init(ctx: &mut Ctx) -> Result<(), Error> {
+  ctx.gfx.bg = vec3(0.2, 0.2, 0.2);
   ctx.gfx.proj = Mat4::identity();
   ctx.gfx.view = Mat4::identity();
-  ctx.gfx.pass_action = SgPassAction {
-    colors: vec![SgColorAttachmentAction {
-      action: SgAction::Clear,
-      val: [0.2, 0.2, 0.2, 1.0],
-    }],
+
+  // allocate room for draw calls up front
+  let config = ctx.gfx.config;
+  ctx.gfx.quads.e = DrawBuffer::new(config.quads);
+  ctx.gfx.points.e = DrawBuffer::new(config.points);
+  ctx.gfx.lines.e = DrawBuffer::new(config.lines);
+  ctx.gfx.triangles.e = DrawBuffer::new(config.triangles);
+  ctx.gfx.mesh.e = DrawBuffer::new(config.meshes);
+  ctx.gfx.images.e.reserve(config.images);
+
+  // headless runs have no window or gpu, so there's nothing to set up
+  if ctx.gfx.headless {
+    return Ok(());
+  }
+
+  sg_setup(&SgDesc {
     ..Default::default()
-  };
+  });
+  if !sg_isvalid() {
+    return Err(Error::GraphicsSetup);
+  }
 
   // initialize each primitive shape's memory for draw commands
   // shader, and pipeline
-  mesh::init(ctx);
-  line::init(ctx);
-  point::init(ctx);
-  quad::init(ctx);
+  mesh::init(ctx)?;
+  line::init(ctx)?;
+  triangle::init(ctx)?;
+  point::init(ctx)?;
+  quad::init(ctx)?;
+  Ok(())
+}
+
+/// lifecycle function for releasing all gpu resources. Run once the game
+/// has finished cleaning up.
+
+pub fn shutdown(ctx: &mut Ctx) {
+  if ctx.gfx.headless {
+    return;
+  }
+  sg_shutdown();
 }
 
 /// Lifecycle function for processing all draw calls collected for a single
 /// frame of rendering. Clears all calls when done to prepare for the next frame.
 
 pub fn present(ctx: &mut Ctx) {
-  sg_begin_default_pass(&ctx.gfx.pass_action, sapp_width(), sapp_height());
-
-  mesh::present(ctx);
-  quad::present(ctx);
-  point::present(ctx);
-  line::present(ctx);
+  glyph_cache::upload(ctx);
+  draw_list::present(ctx);
+  if !ctx.gfx.headless {
+    sg_commit();
+  }
 
   // clear all draw calls
-  ctx.gfx.quads.count = 0;
-  ctx.gfx.points.count = 0;
-  ctx.gfx.lines.count = 0;
-  ctx.gfx.mesh.count = 0;
+  ctx.gfx.quads.e.clear();
+  ctx.gfx.points.e.clear();
+  ctx.gfx.lines.e.clear();
+  ctx.gfx.triangles.e.clear();
+  ctx.gfx.mesh.e.clear();
+  ctx.gfx.draw_list.e.clear();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn headless_ctx() -> Ctx {
+    let mut ctx: Ctx = Default::default();
+    ctx.gfx.headless = true;
+    ctx
+  }
+
+  fn assert_near2(a: Vec2, b: Vec2) {
+    assert!((a - b).length() < 1e-2, "{:?} isn't {:?}", a, b);
+  }
+
+  fn assert_near3(a: Vec3, b: Vec3) {
+    assert!((a - b).length() < 1e-2, "{:?} isn't {:?}", a, b);
+  }
 
-  sg_end_pass();
-  sg_commit();
+  /// how far `p` is from the line along `ray`
+  fn distance_to_ray(ray: Ray, p: Vec3) -> f32 {
+    let along = (p - ray.origin).dot(ray.dir);
+    (ray.at(along) - p).length()
+  }
+
+  #[test]
+  fn converts_pixels_under_the_default_2d_projection() {
+    let mut ctx = headless_ctx();
+    default_projection_2d(&mut ctx);
+    let (w, h) = (window_width(&ctx), window_height(&ctx));
+
+    // 1 unit per pixel from the center of the window, with y up
+    assert_near2(
+      window_to_world_2d(&ctx, vec2(w / 2.0, h / 2.0)),
+      Vec2::zero(),
+    );
+    assert_near2(
+      window_to_world_2d(&ctx, Vec2::zero()),
+      vec2(-w / 2.0, h / 2.0),
+    );
+    assert_near2(
+      window_to_world_2d(&ctx, vec2(w, h)),
+      vec2(w / 2.0, -h / 2.0),
+    );
+
+    for p in [vec2(10.0, 20.0), vec2(320.0, 5.0), vec2(639.0, 479.0)].iter() {
+      let world = window_to_world_2d(&ctx, *p);
+      assert_near2(world_to_screen(&ctx, world.extend(0.0)).unwrap(), *p);
+      // every depth lines up with z = 0 in an orthographic projection
+      for depth in [0.0, 0.5, 1.0].iter() {
+        assert_near2(screen_to_world(&ctx, *p, *depth).truncate(), world);
+      }
+      let ray = screen_ray(&ctx, *p);
+      assert_near3(ray.dir, vec3(0.0, 0.0, -1.0));
+      assert_near2(ray.origin.truncate(), world);
+    }
+  }
+
+  #[test]
+  fn converts_through_a_perspective_camera() {
+    let mut ctx = headless_ctx();
+    let aspect = window_width(&ctx) / window_height(&ctx);
+    ctx.gfx.proj = Mat4::perspective_rh_gl(1.0, aspect, 1.0, 1000.0);
+    let eye = vec3(0.0, -200.0, 400.0);
+    ctx.gfx.view = Mat4::look_at_rh(eye, Vec3::zero(), Vec3::unit_z());
+
+    let points = [Vec3::zero(), vec3(50.0, 20.0, 0.0), vec3(-80.0, 40.0, 30.0)];
+    for p in points.iter() {
+      let screen = world_to_screen(&ctx, *p).unwrap();
+      let ray = screen_ray(&ctx, screen);
+      assert!(distance_to_ray(ray, *p) < 1e-2);
+      assert!(ray.dir.dot(*p - eye) > 0.0);
+      if p.z() == 0.0 {
+        assert_near2(window_to_world_2d(&ctx, screen), p.truncate());
+      }
+    }
+
+    // the center of the window looks straight at the target
+    let center = vec2(window_width(&ctx), window_height(&ctx)) / 2.0;
+    let ray = screen_ray(&ctx, center);
+    assert_near3(ray.dir, (Vec3::zero() - eye).normalize());
+    assert_near2(window_to_world_2d(&ctx, center), Vec2::zero());
+
+    // behind the camera
+    assert_eq!(world_to_screen(&ctx, eye * 2.0), None);
+  }
+
+  #[test]
+  fn uses_the_identity_projection_until_one_is_set() {
+    let ctx = headless_ctx();
+    let (w, h) = (window_width(&ctx), window_height(&ctx));
+    assert_near2(window_to_world_2d(&ctx, Vec2::zero()), vec2(-1.0, 1.0));
+    assert_near2(
+      window_to_world_2d(&ctx, vec2(w / 2.0, h / 2.0)),
+      Vec2::zero(),
+    );
+    assert_near2(
+      world_to_screen(&ctx, vec3(1.0, -1.0, 0.0)).unwrap(),
+      vec2(w, h),
+    );
+  }
 }
@@ -0,0 +1,531 @@
+//! Packs many images into a few big textures, called pages, so that sprites
+//! from different images can be drawn together in one draw call.
+//!
+//! ```ignore
+//! let mut builder = AtlasBuilder::new(AtlasConfig::default());
+//! let hero = builder.add_file("assets/hero.png", Pivot::Center)?;
+//! let tree = builder.add_file("assets/tree.png", Pivot::Px(16.0, 0.0))?;
+//! let atlas = builder.build(ctx)?;
+//! draw_sprite(ctx, atlas.sprites[hero], Transform2d::default());
+//! ```
+
+use crate::graphics::sprite::sprite_in_sheet;
+use crate::graphics::spritesheet::{read_aseprite, read_texturepacker, SheetData};
+use crate::graphics::*;
+
+/// Settings for packing an atlas
+#[derive(Copy, Clone, Debug)]
+pub struct AtlasConfig {
+  /// the width and height of each page in pixels
+  pub page_size: u32,
+  /// empty pixels between packed images
+  pub padding: u32,
+  /// How many pixels to repeat each image's edges outward by. Keeps
+  /// neighbouring images from bleeding in when sprites are scaled or sit
+  /// between pixels.
+  pub extrude: u32,
+}
+
+impl Default for AtlasConfig {
+  fn default() -> Self {
+    Self {
+      page_size: 2048,
+      padding: 2,
+      extrude: 1,
+    }
+  }
+}
+
+/// An image waiting to be packed
+struct AtlasImage {
+  name: String,
+  pixels: RgbaImage,
+  pivot: Pivot,
+}
+
+/// Collects images to pack into an atlas with `build`
+pub struct AtlasBuilder {
+  config: AtlasConfig,
+  images: Vec<AtlasImage>,
+  /// spritesheets and the images they're cut from
+  sheets: Vec<(usize, SheetData)>,
+}
+
+/// Images packed into pages, see `AtlasBuilder`
+pub struct Atlas {
+  /// the packed textures
+  pub pages: Vec<Texture>,
+  /// a sprite for each image, in the order they were added to the builder
+  pub sprites: Vec<Sprite>,
+  /// the spritesheets, in the order they were added to the builder
+  pub sheets: Vec<Spritesheet>,
+  /// where each image ended up, to cut smaller sprites out of it
+  placements: Vec<Placement>,
+  page_size: u32,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Placement {
+  page: usize,
+  x: u32,
+  y: u32,
+}
+
+impl AtlasBuilder {
+  pub fn new(config: AtlasConfig) -> Self {
+    Self {
+      config,
+      images: vec![],
+      sheets: vec![],
+    }
+  }
+
+  /// Adds an image file. Returns its index in `Atlas::sprites`.
+  pub fn add_file(&mut self, filename: &str, pivot: Pivot) -> Result<usize, Error> {
+    let path = application_root_dir().join(filename);
+    let pixels = match image::open(&path) {
+      Ok(img) => img.into_rgba(),
+      Err(source) => return Err(Error::Image { path, source }),
+    };
+    Ok(self.add(filename, pixels, pivot))
+  }
+
+  /// Adds RGBA8 pixel data, e.g. generated at runtime. `name` is only used
+  /// in error messages. Returns the image's index in `Atlas::sprites`, or
+  /// an error if there aren't exactly `w * h * 4` bytes.
+  pub fn add_pixels(
+    &mut self,
+    name: &str,
+    w: u32,
+    h: u32,
+    pixels: Vec<u8>,
+    pivot: Pivot,
+  ) -> Result<usize, Error> {
+    let len = pixels.len();
+    if len != (w as usize) * (h as usize) * 4 {
+      return Err(Error::AtlasPixelsWrongSize {
+        name: name.to_string(),
+        w,
+        h,
+        len,
+      });
+    }
+    // can't fail now that the size has been checked
+    let pixels = RgbaImage::from_raw(w, h, pixels).unwrap();
+    Ok(self.add(name, pixels, pivot))
+  }
+
+  /// Adds a spritesheet exported from Aseprite, see `load_aseprite`.
+  /// Returns its index in `Atlas::sheets`.
+  pub fn add_aseprite(&mut self, filename: &str, pivot: Pivot) -> Result<usize, Error> {
+    let data = read_aseprite(filename, pivot)?;
+    self.add_sheet(data)
+  }
+
+  /// Adds a spritesheet exported from TexturePacker, see
+  /// `load_texturepacker`. Returns its index in `Atlas::sheets`.
+  pub fn add_texturepacker(&mut self, filename: &str, pivot: Pivot) -> Result<usize, Error> {
+    let data = read_texturepacker(filename, pivot)?;
+    self.add_sheet(data)
+  }
+
+  fn add_sheet(&mut self, data: SheetData) -> Result<usize, Error> {
+    let image = self.add_file(&data.image, Pivot::Center)?;
+    self.sheets.push((image, data));
+    Ok(self.sheets.len() - 1)
+  }
+
+  fn add(&mut self, name: &str, pixels: RgbaImage, pivot: Pivot) -> usize {
+    self.images.push(AtlasImage {
+      name: name.to_string(),
+      pixels,
+      pivot,
+    });
+    self.images.len() - 1
+  }
+
+  /// Packs the images into as few pages as they fit in and uploads them.
+  /// Fails if an image is too big for a page.
+  pub fn build(self, ctx: &mut Ctx) -> Result<Atlas, Error> {
+    let config = self.config;
+    let (placements, page_count) = pack(&self.images, config)?;
+
+    let size = config.page_size;
+    let pages: Vec<Texture> = draw_pages(&self.images, &placements, page_count, config)
+      .into_iter()
+      .map(|pixels| add_img(ctx, &pixels.into_raw(), size, size))
+      .collect();
+
+    let sprites = self
+      .images
+      .iter()
+      .zip(&placements)
+      .map(|(image, placement)| {
+        let (w, h) = image.pixels.dimensions();
+        let img_id = pages[placement.page].id;
+        sprite_in_sheet(
+          img_id,
+          (size, size),
+          placement.x,
+          placement.y,
+          w,
+          h,
+          image.pivot,
+        )
+      })
+      .collect();
+
+    let sheets = self
+      .sheets
+      .iter()
+      .map(|(image, data)| {
+        let placement = placements[*image];
+        let offset = (placement.x, placement.y);
+        data.build(pages[placement.page], (size, size), offset)
+      })
+      .collect();
+
+    Ok(Atlas {
+      pages,
+      sprites,
+      sheets,
+      placements,
+      page_size: size,
+    })
+  }
+}
+
+impl Atlas {
+  /// Cuts a sprite out of part of a packed image, e.g. a frame from a
+  /// spritesheet. Positions are in pixels from the top left of the original
+  /// image, like `sprite`.
+  pub fn region(&self, image: usize, x: u32, y: u32, w: u32, h: u32, pivot: Pivot) -> Sprite {
+    let placement = self.placements[image];
+    let img_id = self.pages[placement.page].id;
+    let size = (self.page_size, self.page_size);
+    sprite_in_sheet(img_id, size, placement.x + x, placement.y + y, w, h, pivot)
+  }
+}
+
+/// Finds a place for every image, returning them in the same order along
+/// with how many pages they take up. Fails if an image is too big for a page.
+fn pack(images: &[AtlasImage], config: AtlasConfig) -> Result<(Vec<Placement>, usize), Error> {
+  let border = config.extrude * 2 + config.padding;
+  // padding only has to fit between images, not past the last one, so
+  // pages get that much extra room on the right and bottom for packing
+  let room = config.page_size + config.padding;
+
+  // tallest first packs tightest
+  let mut order: Vec<usize> = (0..images.len()).collect();
+  order.sort_by_key(|i| std::cmp::Reverse(images[*i].pixels.height()));
+
+  let mut skylines: Vec<Skyline> = vec![];
+  let mut placements = vec![None; images.len()];
+  for i in order {
+    let image = &images[i];
+    let w = image.pixels.width() + border;
+    let h = image.pixels.height() + border;
+    if w > room || h > room {
+      return Err(Error::AtlasPageTooSmall {
+        name: image.name.clone(),
+        page_size: config.page_size,
+      });
+    }
+
+    let placed = skylines
+      .iter_mut()
+      .enumerate()
+      .find_map(|(page, skyline)| skyline.insert(w, h).map(|(x, y)| (page, x, y)));
+    let (page, x, y) = match placed {
+      Some(placed) => placed,
+      None => {
+        let mut skyline = Skyline::new(room);
+        // an empty page takes anything up to `room`, checked above
+        let (x, y) = skyline.insert(w, h).expect("image checked to fit a page");
+        skylines.push(skyline);
+        (skylines.len() - 1, x, y)
+      }
+    };
+    placements[i] = Some(Placement {
+      page,
+      x: x + config.extrude,
+      y: y + config.extrude,
+    });
+  }
+  let placements = placements.into_iter().map(Option::unwrap).collect();
+  Ok((placements, skylines.len()))
+}
+
+/// draws the packed images onto each page
+fn draw_pages(
+  images: &[AtlasImage],
+  placements: &[Placement],
+  page_count: usize,
+  config: AtlasConfig,
+) -> Vec<RgbaImage> {
+  let size = config.page_size;
+  let mut pages = vec![RgbaImage::new(size, size); page_count];
+  for (image, placement) in images.iter().zip(placements) {
+    let page = &mut pages[placement.page];
+    blit_extruded(page, &image.pixels, *placement, config.extrude);
+  }
+  pages
+}
+
+/// copies an image onto a page, repeating its edge pixels `extrude` pixels out
+fn blit_extruded(page: &mut RgbaImage, image: &RgbaImage, at: Placement, extrude: u32) {
+  let (w, h) = image.dimensions();
+  if w == 0 || h == 0 {
+    return;
+  }
+  let extrude = extrude as i64;
+  for y in -extrude..h as i64 + extrude {
+    for x in -extrude..w as i64 + extrude {
+      let src_x = x.max(0).min(w as i64 - 1) as u32;
+      let src_y = y.max(0).min(h as i64 - 1) as u32;
+      let dst_x = (at.x as i64 + x) as u32;
+      let dst_y = (at.y as i64 + y) as u32;
+      page.put_pixel(dst_x, dst_y, *image.get_pixel(src_x, src_y));
+    }
+  }
+}
+
+// ----------------------------------------------------------------------------
+// packing
+
+/// A skyline packer. Tracks the top edge of everything placed so far as a
+/// list of horizontal segments, and puts each new rect as high up as it fits.
+struct Skyline {
+  size: u32,
+  /// segments from left to right as (x, y, width), with y growing downward
+  nodes: Vec<(u32, u32, u32)>,
+}
+
+impl Skyline {
+  fn new(size: u32) -> Self {
+    Self {
+      size,
+      nodes: vec![(0, 0, size)],
+    }
+  }
+
+  /// places a rect, returning its top left corner, or `None` if it doesn't fit
+  fn insert(&mut self, w: u32, h: u32) -> Option<(u32, u32)> {
+    // the highest spot, then the leftmost
+    let (i, x, y) = (0..self.nodes.len())
+      .filter_map(|i| self.fit(i, w, h).map(|y| (i, self.nodes[i].0, y)))
+      .min_by_key(|(_, x, y)| (*y, *x))?;
+
+    self.nodes.insert(i, (x, y + h, w));
+
+    // shrink or remove the segments the new one covers
+    let right = x + w;
+    while i + 1 < self.nodes.len() {
+      let (next_x, next_y, next_w) = self.nodes[i + 1];
+      if next_x >= right {
+        break;
+      }
+      let covered = right - next_x;
+      if next_w <= covered {
+        self.nodes.remove(i + 1);
+      } else {
+        self.nodes[i + 1] = (right, next_y, next_w - covered);
+        break;
+      }
+    }
+
+    // join neighbours at the same height
+    let mut j = 0;
+    while j + 1 < self.nodes.len() {
+      if self.nodes[j].1 == self.nodes[j + 1].1 {
+        self.nodes[j].2 += self.nodes[j + 1].2;
+        self.nodes.remove(j + 1);
+      } else {
+        j += 1;
+      }
+    }
+
+    Some((x, y))
+  }
+
+  /// how high a rect can go with its left edge on segment `i`
+  fn fit(&self, i: usize, w: u32, h: u32) -> Option<u32> {
+    let x = self.nodes[i].0;
+    if x + w > self.size {
+      return None;
+    }
+    let mut y = 0;
+    let mut remaining = w as i64;
+    let mut j = i;
+    while remaining > 0 {
+      let (_, node_y, node_w) = *self.nodes.get(j)?;
+      y = y.max(node_y);
+      if y + h > self.size {
+        return None;
+      }
+      remaining -= node_w as i64;
+      j += 1;
+    }
+    Some(y)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::Rgba;
+
+  fn image(w: u32, h: u32) -> AtlasImage {
+    AtlasImage {
+      name: format!("{}x{}", w, h),
+      pixels: RgbaImage::new(w, h),
+      pivot: Pivot::Center,
+    }
+  }
+
+  fn config(page_size: u32, padding: u32, extrude: u32) -> AtlasConfig {
+    AtlasConfig {
+      page_size,
+      padding,
+      extrude,
+    }
+  }
+
+  fn spots(placements: &[Placement]) -> Vec<(usize, u32, u32)> {
+    placements.iter().map(|p| (p.page, p.x, p.y)).collect()
+  }
+
+  #[test]
+  fn skyline_puts_rects_as_high_as_they_fit() {
+    let mut skyline = Skyline::new(10);
+    assert_eq!(skyline.insert(4, 4), Some((0, 0)));
+    assert_eq!(skyline.insert(6, 3), Some((4, 0)));
+    // lower on the left, so the right side wins
+    assert_eq!(skyline.insert(6, 2), Some((4, 3)));
+    assert_eq!(skyline.insert(4, 6), Some((0, 4)));
+    assert_eq!(skyline.insert(7, 1), None);
+    // exactly fills what's left
+    assert_eq!(skyline.insert(6, 5), Some((4, 5)));
+    assert_eq!(skyline.insert(1, 1), None);
+  }
+
+  #[test]
+  fn fits_images_exactly_as_big_as_a_page() {
+    let config = config(16, 2, 1);
+    let (placements, pages) = pack(&[image(14, 14)], config).unwrap();
+    assert_eq!(pages, 1);
+    assert_eq!(spots(&placements), [(0, 1, 1)]);
+
+    for (w, h) in [(15, 14), (14, 15)].iter() {
+      match pack(&[image(2, 2), image(*w, *h)], config) {
+        Err(Error::AtlasPageTooSmall { name, page_size }) => {
+          assert_eq!(name, format!("{}x{}", w, h));
+          assert_eq!(page_size, 16);
+        }
+        _ => panic!("{}x{} shouldn't fit", w, h),
+      }
+    }
+  }
+
+  #[test]
+  fn overflows_onto_more_pages() {
+    let config = config(16, 0, 0);
+    let images: Vec<AtlasImage> = (0..5).map(|_| image(8, 8)).collect();
+    let (placements, pages) = pack(&images, config).unwrap();
+    assert_eq!(pages, 2);
+    assert_eq!(
+      spots(&placements),
+      [(0, 0, 0), (0, 8, 0), (0, 0, 8), (0, 8, 8), (1, 0, 0)]
+    );
+
+    let images = [image(10, 10), image(10, 10), image(10, 10)];
+    let (placements, pages) = pack(&images, config).unwrap();
+    assert_eq!(pages, 3);
+    let pages: Vec<usize> = placements.iter().map(|p| p.page).collect();
+    assert_eq!(pages, [0, 1, 2]);
+  }
+
+  #[test]
+  fn pads_and_extrudes_around_images() {
+    // each image takes 4 + 2 extruded pixels, then 2 of padding
+    let padded = config(32, 2, 1);
+    let (placements, _) = pack(&[image(4, 4), image(4, 4)], padded).unwrap();
+    assert_eq!(spots(&placements), [(0, 1, 1), (0, 9, 1)]);
+
+    let mut pixels = RgbaImage::new(2, 2);
+    let colors = [
+      [255, 0, 0, 255],
+      [0, 255, 0, 255],
+      [0, 0, 255, 255],
+      [255; 4],
+    ];
+    for (i, color) in colors.iter().enumerate() {
+      pixels.put_pixel(i as u32 % 2, i as u32 / 2, Rgba(*color));
+    }
+    let mut page = RgbaImage::new(6, 6);
+    let at = Placement {
+      page: 0,
+      x: 2,
+      y: 2,
+    };
+    blit_extruded(&mut page, &pixels, at, 1);
+
+    let at = |x, y| page.get_pixel(x, y).0;
+    // the image itself
+    assert_eq!(at(2, 2), colors[0]);
+    assert_eq!(at(3, 3), colors[3]);
+    // edges and corners repeated outward
+    assert_eq!(at(1, 1), colors[0]);
+    assert_eq!(at(2, 1), colors[0]);
+    assert_eq!(at(4, 2), colors[1]);
+    assert_eq!(at(1, 3), colors[2]);
+    assert_eq!(at(4, 4), colors[3]);
+    // nothing past the extruded border
+    assert_eq!(at(0, 0), [0; 4]);
+    assert_eq!(at(5, 5), [0; 4]);
+    assert_eq!(at(5, 2), [0; 4]);
+
+    let images = [AtlasImage {
+      name: "colors".to_string(),
+      pixels,
+      pivot: Pivot::Center,
+    }];
+    let config = config(8, 0, 1);
+    let (placements, count) = pack(&images, config).unwrap();
+    let pages = draw_pages(&images, &placements, count, config);
+    assert_eq!(pages[0].get_pixel(0, 0).0, colors[0]);
+    assert_eq!(pages[0].get_pixel(3, 3).0, colors[3]);
+  }
+
+  #[test]
+  fn sprites_cover_their_image_on_the_page() {
+    let mut ctx: Ctx = Default::default();
+    ctx.gfx.headless = true;
+
+    let mut builder = AtlasBuilder::new(config(16, 2, 1));
+    let tall = builder.add_pixels("tall", 4, 8, vec![0; 4 * 8 * 4], Pivot::Center);
+    let small = builder.add_pixels("small", 2, 2, vec![0; 2 * 2 * 4], Pivot::Center);
+    let (tall, small) = (tall.unwrap(), small.unwrap());
+    let atlas = builder.build(&mut ctx).unwrap();
+    assert_eq!(atlas.pages.len(), 1);
+
+    // uvs run from the top left of the page, with v flipped for the y up
+    // corners: bottom left, bottom right, top left, top right
+    let uvs = |sprite: Sprite| -> Vec<Vec2> { sprite.corners.iter().map(|c| c.uv).collect() };
+    let px = |x: f32, y: f32| vec2(x, y) / 16.0;
+    assert_eq!(
+      uvs(atlas.sprites[tall]),
+      [px(1.0, 9.0), px(5.0, 9.0), px(1.0, 1.0), px(5.0, 1.0)]
+    );
+    assert_eq!(
+      uvs(atlas.sprites[small]),
+      [px(9.0, 3.0), px(11.0, 3.0), px(9.0, 1.0), px(11.0, 1.0)]
+    );
+
+    let region = atlas.region(tall, 1, 2, 2, 3, Pivot::Center);
+    assert_eq!(
+      uvs(region),
+      [px(2.0, 6.0), px(4.0, 6.0), px(2.0, 3.0), px(4.0, 3.0)]
+    );
+  }
+}
@@ -0,0 +1,545 @@
+//! Glyph outlines from the `CFF ` table of OpenType fonts, which describes
+//! them as Type 2 charstrings: small programs of PostScript-like drawing
+//! operators.
+
+use crate::graphics::truetype::{PathCmd, ReadBytes};
+use crate::math::*;
+use std::mem;
+
+/// How deep subroutine calls can nest, from the spec
+const MAX_CALL_DEPTH: usize = 10;
+/// The most operands a charstring can push, from the spec
+const MAX_STACK: usize = 48;
+
+pub(crate) struct Cff {
+  charstrings: Index,
+  global_subrs: Index,
+  /// each font dict's private subroutines. Fonts that aren't CID-keyed have
+  /// just one.
+  local_subrs: Vec<Option<Index>>,
+  /// which font dict each glyph uses, for CID-keyed fonts
+  fd_select: Option<usize>,
+}
+
+/// A CFF INDEX: a count, then offsets to each item, then the items
+#[derive(Copy, Clone)]
+struct Index {
+  count: usize,
+  off_size: usize,
+  offsets: usize,
+  /// the byte before the first item, which offsets count from
+  data: usize,
+}
+
+impl Index {
+  fn read(d: &[u8], at: usize) -> Option<Index> {
+    let count = d.u16_at(at)? as usize;
+    if count == 0 {
+      return Some(Index {
+        count,
+        off_size: 0,
+        offsets: at + 2,
+        data: at + 1,
+      });
+    }
+    let off_size = d.u8_at(at + 2)? as usize;
+    if off_size == 0 || off_size > 4 {
+      return None;
+    }
+    let offsets = at + 3;
+    Some(Index {
+      count,
+      off_size,
+      offsets,
+      data: offsets + (count + 1) * off_size - 1,
+    })
+  }
+
+  fn offset(&self, d: &[u8], i: usize) -> Option<usize> {
+    let at = self.offsets + i * self.off_size;
+    let mut n = 0;
+    for k in 0..self.off_size {
+      n = n << 8 | d.u8_at(at + k)? as usize;
+    }
+    Some(self.data + n)
+  }
+
+  /// the start and end of an item
+  fn get(&self, d: &[u8], i: usize) -> Option<(usize, usize)> {
+    if i >= self.count {
+      return None;
+    }
+    let (start, end) = (self.offset(d, i)?, self.offset(d, i + 1)?);
+    if start > end || end > d.len() {
+      return None;
+    }
+    Some((start, end))
+  }
+
+  /// the first byte after the index
+  fn end(&self, d: &[u8]) -> Option<usize> {
+    if self.count == 0 {
+      return Some(self.offsets);
+    }
+    self.offset(d, self.count)
+  }
+}
+
+// ----------------------------------------------------------------------------
+// dicts
+
+/// Reads a DICT's operands and operators, calling `op` with each operator
+/// and the operands before it. Two byte operators are `1200 + second byte`.
+fn read_dict(d: &[u8], start: usize, end: usize, mut op: impl FnMut(u16, &[f64])) -> Option<()> {
+  let mut operands = vec![];
+  let mut at = start;
+  while at < end {
+    let b0 = d.u8_at(at)?;
+    at += 1;
+    match b0 {
+      0..=21 => {
+        let operator = if b0 == 12 {
+          at += 1;
+          1200 + d.u8_at(at - 1)? as u16
+        } else {
+          b0 as u16
+        };
+        op(operator, &operands);
+        operands.clear();
+      }
+      28 => {
+        operands.push(d.i16_at(at)? as f64);
+        at += 2;
+      }
+      29 => {
+        operands.push(d.u32_at(at)? as i32 as f64);
+        at += 4;
+      }
+      // reals are packed in nibbles. They only show up in font matrices and
+      // such, so they're skipped rather than parsed.
+      30 => {
+        loop {
+          let b = d.u8_at(at)?;
+          at += 1;
+          if b & 0x0F == 0x0F || b >> 4 == 0x0F {
+            break;
+          }
+        }
+        operands.push(0.0);
+      }
+      32..=246 => operands.push(b0 as f64 - 139.0),
+      247..=250 => {
+        operands.push((b0 as f64 - 247.0) * 256.0 + d.u8_at(at)? as f64 + 108.0);
+        at += 1;
+      }
+      251..=254 => {
+        operands.push(-(b0 as f64 - 251.0) * 256.0 - d.u8_at(at)? as f64 - 108.0);
+        at += 1;
+      }
+      _ => return None,
+    }
+  }
+  Some(())
+}
+
+const CHARSTRINGS: u16 = 17;
+const PRIVATE: u16 = 18;
+const SUBRS: u16 = 19;
+const FD_ARRAY: u16 = 1236;
+const FD_SELECT: u16 = 1237;
+
+/// a private dict's subroutines, from the font dict that points to it
+fn private_subrs(d: &[u8], cff: usize, (size, offset): (usize, usize)) -> Option<Index> {
+  let private = cff + offset;
+  let mut subrs = None;
+  read_dict(d, private, private + size, |op, args| {
+    if op == SUBRS && !args.is_empty() {
+      subrs = Some(args[0] as usize);
+    }
+  })?;
+  Index::read(d, private + subrs?)
+}
+
+impl Cff {
+  pub(crate) fn parse(d: &[u8], cff: usize) -> Result<Cff, String> {
+    Cff::read(d, cff).ok_or_else(|| "the CFF table is broken".to_string())
+  }
+
+  fn read(d: &[u8], cff: usize) -> Option<Cff> {
+    let header_size = d.u8_at(cff + 2)? as usize;
+    let names = Index::read(d, cff + header_size)?;
+    let top_dicts = Index::read(d, names.end(d)?)?;
+    let strings = Index::read(d, top_dicts.end(d)?)?;
+    let global_subrs = Index::read(d, strings.end(d)?)?;
+
+    let (start, end) = top_dicts.get(d, 0)?;
+    let mut charstrings = None;
+    let mut private = None;
+    let mut fd_array = None;
+    let mut fd_select = None;
+    read_dict(d, start, end, |op, args| match (op, args) {
+      (CHARSTRINGS, [offset]) => charstrings = Some(*offset as usize),
+      (PRIVATE, [size, offset]) => private = Some((*size as usize, *offset as usize)),
+      (FD_ARRAY, [offset]) => fd_array = Some(*offset as usize),
+      (FD_SELECT, [offset]) => fd_select = Some(cff + *offset as usize),
+      _ => {}
+    })?;
+    let charstrings = Index::read(d, cff + charstrings?)?;
+
+    // CID-keyed fonts have a private dict per font dict
+    let local_subrs = match fd_array {
+      Some(fd_array) => {
+        let fonts = Index::read(d, cff + fd_array)?;
+        let mut subrs = vec![];
+        for i in 0..fonts.count {
+          let (start, end) = fonts.get(d, i)?;
+          let mut private = None;
+          read_dict(d, start, end, |op, args| {
+            if let (PRIVATE, [size, offset]) = (op, args) {
+              private = Some((*size as usize, *offset as usize));
+            }
+          })?;
+          subrs.push(private.and_then(|private| private_subrs(d, cff, private)));
+        }
+        subrs
+      }
+      None => vec![private.and_then(|private| private_subrs(d, cff, private))],
+    };
+
+    Some(Cff {
+      charstrings,
+      global_subrs,
+      local_subrs,
+      fd_select: fd_select.filter(|_| fd_array.is_some()),
+    })
+  }
+
+  /// which font dict a glyph uses
+  fn font_dict(&self, d: &[u8], glyph: u16) -> Option<usize> {
+    let fd_select = match self.fd_select {
+      Some(fd_select) => fd_select,
+      None => return Some(0),
+    };
+    match d.u8_at(fd_select)? {
+      0 => Some(d.u8_at(fd_select + 1 + glyph as usize)? as usize),
+      3 => {
+        let ranges = d.u16_at(fd_select + 1)? as usize;
+        for i in 0..ranges {
+          let range = fd_select + 3 + i * 3;
+          let next = d.u16_at(range + 3)?;
+          if glyph < next {
+            return Some(d.u8_at(range + 2)? as usize);
+          }
+        }
+        None
+      }
+      _ => None,
+    }
+  }
+
+  /// Runs a glyph's charstring, adding its outline to `path`. `None` if the
+  /// charstring is broken.
+  pub(crate) fn outline(&self, d: &[u8], glyph: u16, path: &mut Vec<PathCmd>) -> Option<()> {
+    let (start, end) = self.charstrings.get(d, glyph as usize)?;
+    let fd = self.font_dict(d, glyph)?;
+    let mut charstring = Charstring {
+      d,
+      global_subrs: self.global_subrs,
+      local_subrs: self.local_subrs.get(fd).copied().flatten(),
+      stack: Vec::with_capacity(MAX_STACK),
+      stems: 0,
+      pos: Vec2::zero(),
+      open: false,
+      path,
+    };
+    charstring.run(start, end, 0)?;
+    if charstring.open {
+      charstring.path.push(PathCmd::Close);
+    }
+    Some(())
+  }
+}
+
+// ----------------------------------------------------------------------------
+// charstrings
+
+/// subroutine numbers are stored offset by a bias, so small numbers can
+/// reach more of them
+fn subr_bias(subrs: &Index) -> i32 {
+  match subrs.count {
+    n if n < 1240 => 107,
+    n if n < 33900 => 1131,
+    _ => 32768,
+  }
+}
+
+struct Charstring<'a> {
+  d: &'a [u8],
+  global_subrs: Index,
+  local_subrs: Option<Index>,
+  stack: Vec<f32>,
+  /// the number of stem hints, which sets the size of hint masks
+  stems: usize,
+  pos: Vec2,
+  /// whether there's a contour to close before the next move
+  open: bool,
+  path: &'a mut Vec<PathCmd>,
+}
+
+/// how a charstring ended, so `return` can pop back to the caller
+enum Flow {
+  Return,
+  EndChar,
+}
+
+impl<'a> Charstring<'a> {
+  fn move_to(&mut self, delta: Vec2) {
+    if self.open {
+      self.path.push(PathCmd::Close);
+    }
+    self.pos += delta;
+    self.path.push(PathCmd::MoveTo(self.pos));
+    self.open = true;
+  }
+
+  fn line_to(&mut self, delta: Vec2) {
+    self.pos += delta;
+    self.path.push(PathCmd::LineTo(self.pos));
+  }
+
+  fn curve_to(&mut self, d1: Vec2, d2: Vec2, d3: Vec2) {
+    let c1 = self.pos + d1;
+    let c2 = c1 + d2;
+    self.pos = c2 + d3;
+    self.path.push(PathCmd::CubicTo(c1, c2, self.pos));
+  }
+
+  fn run(&mut self, start: usize, end: usize, depth: usize) -> Option<Flow> {
+    if depth > MAX_CALL_DEPTH {
+      return None;
+    }
+    let d = self.d;
+    let mut at = start;
+    while at < end {
+      let b0 = d.u8_at(at)?;
+      at += 1;
+      match b0 {
+        // numbers
+        28 => {
+          self.push(d.i16_at(at)? as f32)?;
+          at += 2;
+        }
+        32..=246 => self.push(b0 as f32 - 139.0)?,
+        247..=250 => {
+          self.push((b0 as f32 - 247.0) * 256.0 + d.u8_at(at)? as f32 + 108.0)?;
+          at += 1;
+        }
+        251..=254 => {
+          self.push(-(b0 as f32 - 251.0) * 256.0 - d.u8_at(at)? as f32 - 108.0)?;
+          at += 1;
+        }
+        255 => {
+          self.push(d.u32_at(at)? as i32 as f32 / 65536.0)?;
+          at += 4;
+        }
+
+        // hints only matter for how many bytes hint masks take up
+        1 | 3 | 18 | 23 => {
+          self.stems += self.stack.len() / 2;
+          self.stack.clear();
+        }
+        19 | 20 => {
+          self.stems += self.stack.len() / 2;
+          self.stack.clear();
+          at += self.stems.div_ceil(8);
+        }
+
+        // moves. Any extra operand at the bottom is the glyph's width.
+        21 => {
+          let s = self.last(2)?;
+          self.move_to(vec2(s[0], s[1]));
+          self.stack.clear();
+        }
+        22 => {
+          let s = self.last(1)?;
+          self.move_to(vec2(s[0], 0.0));
+          self.stack.clear();
+        }
+        4 => {
+          let s = self.last(1)?;
+          self.move_to(vec2(0.0, s[0]));
+          self.stack.clear();
+        }
+
+        // lines
+        5 => {
+          let s = mem::take(&mut self.stack);
+          for pair in s.chunks_exact(2) {
+            self.line_to(vec2(pair[0], pair[1]));
+          }
+        }
+        6 | 7 => {
+          let s = mem::take(&mut self.stack);
+          let mut horizontal = b0 == 6;
+          for n in s {
+            self.line_to(if horizontal {
+              vec2(n, 0.0)
+            } else {
+              vec2(0.0, n)
+            });
+            horizontal = !horizontal;
+          }
+        }
+
+        // curves
+        8 => {
+          let s = mem::take(&mut self.stack);
+          for c in s.chunks_exact(6) {
+            self.curve_to(vec2(c[0], c[1]), vec2(c[2], c[3]), vec2(c[4], c[5]));
+          }
+        }
+        27 => {
+          let s = mem::take(&mut self.stack);
+          let (mut dy1, s) = if s.len() % 2 == 1 {
+            (s[0], &s[1..])
+          } else {
+            (0.0, &s[..])
+          };
+          for c in s.chunks_exact(4) {
+            self.curve_to(vec2(c[0], dy1), vec2(c[1], c[2]), vec2(c[3], 0.0));
+            dy1 = 0.0;
+          }
+        }
+        26 => {
+          let s = mem::take(&mut self.stack);
+          let (mut dx1, s) = if s.len() % 2 == 1 {
+            (s[0], &s[1..])
+          } else {
+            (0.0, &s[..])
+          };
+          for c in s.chunks_exact(4) {
+            self.curve_to(vec2(dx1, c[0]), vec2(c[1], c[2]), vec2(0.0, c[3]));
+            dx1 = 0.0;
+          }
+        }
+        30 | 31 => {
+          let s = mem::take(&mut self.stack);
+          let mut horizontal = b0 == 31;
+          let mut i = 0;
+          while i + 4 <= s.len() {
+            // the last curve can have an extra operand for its end
+            let last = if s.len() - i == 5 { s[i + 4] } else { 0.0 };
+            let c = &s[i..i + 4];
+            if horizontal {
+              self.curve_to(vec2(c[0], 0.0), vec2(c[1], c[2]), vec2(last, c[3]));
+            } else {
+              self.curve_to(vec2(0.0, c[0]), vec2(c[1], c[2]), vec2(c[3], last));
+            }
+            horizontal = !horizontal;
+            i += 4;
+          }
+        }
+        24 => {
+          let s = mem::take(&mut self.stack);
+          if s.len() < 8 {
+            return None;
+          }
+          let (curves, line) = s.split_at(s.len() - 2);
+          for c in curves.chunks_exact(6) {
+            self.curve_to(vec2(c[0], c[1]), vec2(c[2], c[3]), vec2(c[4], c[5]));
+          }
+          self.line_to(vec2(line[0], line[1]));
+        }
+        25 => {
+          let s = mem::take(&mut self.stack);
+          if s.len() < 8 {
+            return None;
+          }
+          let (lines, c) = s.split_at(s.len() - 6);
+          for pair in lines.chunks_exact(2) {
+            self.line_to(vec2(pair[0], pair[1]));
+          }
+          self.curve_to(vec2(c[0], c[1]), vec2(c[2], c[3]), vec2(c[4], c[5]));
+        }
+        12 => {
+          let b1 = d.u8_at(at)?;
+          at += 1;
+          self.flex(b1)?;
+        }
+
+        // subroutines
+        10 | 29 => {
+          let subrs = if b0 == 10 {
+            self.local_subrs?
+          } else {
+            self.global_subrs
+          };
+          let i = self.stack.pop()? as i32 + subr_bias(&subrs);
+          let (start, end) = subrs.get(d, i as usize)?;
+          if let Flow::EndChar = self.run(start, end, depth + 1)? {
+            return Some(Flow::EndChar);
+          }
+        }
+        11 => return Some(Flow::Return),
+        14 => return Some(Flow::EndChar),
+
+        _ => self.stack.clear(),
+      }
+    }
+    Some(Flow::Return)
+  }
+
+  fn push(&mut self, n: f32) -> Option<()> {
+    if self.stack.len() >= MAX_STACK {
+      return None;
+    }
+    self.stack.push(n);
+    Some(())
+  }
+
+  /// the top `n` operands
+  fn last(&self, n: usize) -> Option<&[f32]> {
+    let len = self.stack.len();
+    self.stack.get(len.checked_sub(n)?..)
+  }
+
+  /// the two byte flex operators, which draw two curves. Other two byte
+  /// operators are arithmetic that fonts don't use in practice.
+  fn flex(&mut self, op: u8) -> Option<()> {
+    let s = mem::take(&mut self.stack);
+    match op {
+      // flex
+      35 if s.len() >= 12 => {
+        self.curve_to(vec2(s[0], s[1]), vec2(s[2], s[3]), vec2(s[4], s[5]));
+        self.curve_to(vec2(s[6], s[7]), vec2(s[8], s[9]), vec2(s[10], s[11]));
+      }
+      // hflex
+      34 if s.len() >= 7 => {
+        self.curve_to(vec2(s[0], 0.0), vec2(s[1], s[2]), vec2(s[3], 0.0));
+        self.curve_to(vec2(s[4], 0.0), vec2(s[5], -s[2]), vec2(s[6], 0.0));
+      }
+      // hflex1
+      36 if s.len() >= 9 => {
+        self.curve_to(vec2(s[0], s[1]), vec2(s[2], s[3]), vec2(s[4], 0.0));
+        let dy = -(s[1] + s[3] + s[7]);
+        self.curve_to(vec2(s[5], 0.0), vec2(s[6], s[7]), vec2(s[8], dy));
+      }
+      // flex1: the last point moves along whichever axis moved further
+      37 if s.len() >= 11 => {
+        let start = self.pos;
+        self.curve_to(vec2(s[0], s[1]), vec2(s[2], s[3]), vec2(s[4], s[5]));
+        let d4 = vec2(s[6], s[7]);
+        let d5 = vec2(s[8], s[9]);
+        let moved = self.pos + d4 + d5 - start;
+        let d6 = if moved.x().abs() > moved.y().abs() {
+          vec2(s[10], -moved.y())
+        } else {
+          vec2(-moved.x(), s[10])
+        };
+        self.curve_to(d4, d5, d6);
+      }
+      _ => {}
+    }
+    Some(())
+  }
+}
@@ -1,5 +1,6 @@
 use crate::geometry::*;
-use crate::graphics::line::draw_line;
+use crate::graphics::line::draw_line_on;
+use crate::graphics::triangle::{curve_segments, push_triangle};
 use crate::math::*;
 use crate::*;
 
@@ -8,6 +9,10 @@ const SEGMENT_ARC: f32 = TAU / CIRC_SEGMENTS as f32;
 
 // TODO this is just a special mesh. Use meshes.
 pub fn draw_circ(ctx: &mut Ctx, c: Circle, color: Vec4) {
+  draw_circ_on(ctx, Layer::World, c, color);
+}
+
+pub fn draw_circ_on(ctx: &mut Ctx, layer: Layer, c: Circle, color: Vec4) {
   // draw_point(core, c.center, color);
   let mut points: [Vec2; CIRC_SEGMENTS] = [Vec2::zero(); CIRC_SEGMENTS];
 
@@ -26,18 +31,143 @@ pub fn draw_circ(ctx: &mut Ctx, c: Circle, color: Vec4) {
   // could pre-calc and/or optimize this via reflection
   for i in 0..(CIRC_SEGMENTS - 1) {
     // TODO a lot of repetition here
-    draw_line(
+    draw_line_on(
       ctx,
+      layer,
       c_points[i].extend(0.0).into(),
       c_points[i + 1].extend(0.0).into(),
       color,
     );
   }
 
-  draw_line(
+  draw_line_on(
     ctx,
+    layer,
     c_points[0].extend(0.0).into(),
     c_points[CIRC_SEGMENTS - 1].extend(0.0).into(),
     color,
   );
 }
+
+/// Fills a circle with a solid color
+pub fn fill_circle(ctx: &mut Ctx, c: Circle, color: Vec4) {
+  fill_circle_on(ctx, Layer::World, 0.0, c, color);
+}
+
+/// `fill_circle` on a specific render layer and depth
+pub fn fill_circle_on(ctx: &mut Ctx, layer: Layer, z: f32, c: Circle, color: Vec4) {
+  fill_circle_gradient_on(ctx, layer, z, c, color, color);
+}
+
+/// Fills a circle with a radial gradient, from `inner` at the center to
+/// `outer` at the edge
+pub fn fill_circle_gradient(ctx: &mut Ctx, c: Circle, inner: Vec4, outer: Vec4) {
+  fill_circle_gradient_on(ctx, Layer::World, 0.0, c, inner, outer);
+}
+
+/// `fill_circle_gradient` on a specific render layer and depth
+pub fn fill_circle_gradient_on(
+  ctx: &mut Ctx,
+  layer: Layer,
+  z: f32,
+  c: Circle,
+  inner: Vec4,
+  outer: Vec4,
+) {
+  fill_fan(ctx, layer, z, c, 0.0, TAU, [inner, outer, outer]);
+}
+
+/// Fills a pie slice of a circle, from `start` to `end` in radians counter
+/// clockwise from the right
+pub fn fill_pie(ctx: &mut Ctx, c: Circle, start: f32, end: f32, color: Vec4) {
+  fill_pie_on(ctx, Layer::World, 0.0, c, start, end, color);
+}
+
+/// `fill_pie` on a specific render layer and depth
+pub fn fill_pie_on(
+  ctx: &mut Ctx,
+  layer: Layer,
+  z: f32,
+  c: Circle,
+  start: f32,
+  end: f32,
+  color: Vec4,
+) {
+  let sweep = (end - start).clamp(-TAU, TAU);
+  fill_fan(ctx, layer, z, c, start, sweep, [color; 3]);
+}
+
+/// Fills a band `width` wide along a circle's edge, centered on it, from
+/// `start` to `end` in radians counter clockwise from the right. Useful for
+/// rings, cooldown timers and progress arcs.
+pub fn fill_arc(ctx: &mut Ctx, c: Circle, width: f32, start: f32, end: f32, color: Vec4) {
+  fill_arc_on(ctx, Layer::World, 0.0, c, width, start, end, color);
+}
+
+/// `fill_arc` on a specific render layer and depth
+#[allow(clippy::too_many_arguments)]
+pub fn fill_arc_on(
+  ctx: &mut Ctx,
+  layer: Layer,
+  z: f32,
+  c: Circle,
+  width: f32,
+  start: f32,
+  end: f32,
+  color: Vec4,
+) {
+  let sweep = (end - start).clamp(-TAU, TAU);
+  let inner = (c.r - width * 0.5).max(0.0);
+  let outer = c.r + width * 0.5;
+  let segments = curve_segments(ctx, layer, c.center.extend(z), outer, sweep);
+  let colors = [color; 3];
+  let mut prev = None;
+  for i in 0..=segments {
+    let angle = start + sweep * i as f32 / segments as f32;
+    let dir = vec2(angle.cos(), angle.sin());
+    let next = (c.center + dir * inner, c.center + dir * outer);
+    if let Some((prev_in, prev_out)) = prev {
+      let (next_in, next_out) = next;
+      push_triangle(
+        ctx,
+        layer,
+        z,
+        [prev_in, prev_out, next_out],
+        colors,
+        c.center,
+      );
+      push_triangle(
+        ctx,
+        layer,
+        z,
+        [prev_in, next_out, next_in],
+        colors,
+        c.center,
+      );
+    }
+    prev = Some(next);
+  }
+}
+
+/// fills `sweep` radians of a circle from `start` with triangles fanning out
+/// from its center. `colors` are for the center and then the edge.
+fn fill_fan(
+  ctx: &mut Ctx,
+  layer: Layer,
+  z: f32,
+  c: Circle,
+  start: f32,
+  sweep: f32,
+  colors: [Vec4; 3],
+) {
+  let segments = curve_segments(ctx, layer, c.center.extend(z), c.r, sweep);
+  let mut prev = None;
+  for i in 0..=segments {
+    let angle = start + sweep * i as f32 / segments as f32;
+    let next = c.center + vec2(angle.cos(), angle.sin()) * c.r;
+    if let Some(prev) = prev {
+      push_triangle(ctx, layer, z, [c.center, prev, next], colors, c.center);
+    }
+    prev = Some(next);
+  }
+}
@@ -0,0 +1,299 @@
+//! Orders a frame's draw calls across all primitives, so that a line in
+//! front of a sprite is drawn over it no matter which primitive it is.
+//!
+//! Each draw call gets a sort key, from most to least significant:
+//!
+//! - layer
+//! - opaque or transparent. Opaque draw calls all go first, since they fill
+//!   the depth buffer that hides whatever is behind them
+//! - depth from the camera. Opaque draw calls go front to back, so hidden
+//!   pixels are skipped early. Transparent ones go back to front, so they
+//!   blend over whatever is behind them.
+//! - material (the primitive's pipeline), to group state changes. Only for
+//!   opaque draw calls: transparent ones at the same depth keep the order
+//!   they were made in, so 2d games can still layer things by draw order.
+
+use crate::graphics::*;
+use std::mem;
+
+pub(crate) fn push_cmd(
+  ctx: &mut Ctx,
+  layer: Layer,
+  primitive: PrimitiveKind,
+  index: usize,
+  pos: Vec3,
+) {
+  layer_mut(ctx, layer);
+  ctx.gfx.draw_list.e.push(DrawCmd {
+    primitive,
+    index,
+    pos,
+    layer: layer.index(),
+  });
+}
+
+impl PrimitiveKind {
+  /// whether the primitive writes depth rather than blending
+  fn opaque(self) -> bool {
+    match self {
+      PrimitiveKind::Mesh => true,
+      PrimitiveKind::Quad
+      | PrimitiveKind::Point
+      | PrimitiveKind::Line
+      | PrimitiveKind::Triangle => false,
+    }
+  }
+}
+
+/// whether `next` can be drawn in the same draw call as `prev`, which it
+/// follows in the sorted list
+fn same_batch(ctx: &Ctx, prev: &DrawCmd, next: &DrawCmd) -> bool {
+  if next.primitive != prev.primitive || next.index != prev.index + 1 {
+    return false;
+  }
+  match prev.primitive {
+    PrimitiveKind::Point | PrimitiveKind::Line | PrimitiveKind::Triangle => true,
+    PrimitiveKind::Quad => {
+      let (prev, next) = (
+        &ctx.gfx.quads.e.e[prev.index],
+        &ctx.gfx.quads.e.e[next.index],
+      );
+      prev.img_id == next.img_id && prev.blend == next.blend
+    }
+    PrimitiveKind::Mesh => false,
+  }
+}
+
+/// `rank` is where the command's layer comes in the drawing order
+fn sort_key(cmd: &DrawCmd, rank: usize, view: Mat4) -> u64 {
+  // the camera looks down -z in view space
+  let depth = -(view * cmd.pos.extend(1.0)).z();
+  let depth = ordered_bits(depth) as u64;
+  let layer = (rank as u64) << 48;
+  if cmd.primitive.opaque() {
+    layer | depth << 15 | cmd.primitive as u64
+  } else {
+    layer | 1 << 47 | (!depth & 0xFFFF_FFFF) << 15
+  }
+}
+
+/// the bits of a float, flipped so that they sort in the same order as the
+/// float itself
+fn ordered_bits(f: f32) -> u32 {
+  let bits = f.to_bits();
+  if bits & 0x8000_0000 != 0 {
+    !bits
+  } else {
+    bits | 0x8000_0000
+  }
+}
+
+/// Sorts the frame's draw calls and draws them in a pass per layer,
+/// switching pipelines only when the primitive changes. Finishes with the
+/// software cursor. Headless runs stop short of the gpu, but still sort and
+/// batch so that `draw_calls` counts what would have been drawn.
+pub(crate) fn present(ctx: &mut Ctx) {
+  let mut cmds = mem::take(&mut ctx.gfx.draw_list.e);
+  ctx.gfx.draw_calls = 0;
+  let headless = ctx.gfx.headless;
+
+  if !headless {
+    point::upload(ctx);
+    line::upload(ctx);
+    triangle::upload(ctx);
+  }
+
+  let order = layers_in_order(ctx);
+  let mut ranks = vec![0; order.len()];
+  for (rank, layer) in order.iter().enumerate() {
+    ranks[*layer] = rank;
+  }
+  let cameras: Vec<(Mat4, Mat4)> = (0..order.len())
+    .map(|layer| layer_proj_view(ctx, Layer::from_index(layer)))
+    .collect();
+
+  // stable, so ties keep the order they were drawn in
+  cmds.sort_by_cached_key(|cmd| sort_key(cmd, ranks[cmd.layer], cameras[cmd.layer].1));
+  quad::upload(ctx, &mut cmds);
+
+  let mut first_pass = true;
+  let mut start = 0;
+  for layer in order {
+    let count = cmds[start..]
+      .iter()
+      .take_while(|cmd| cmd.layer == layer)
+      .count();
+    let clear = ctx.gfx.layers.e[layer].clear;
+    if count == 0 && clear.color.is_none() {
+      continue;
+    }
+    let layer_cmds = &cmds[start..start + count];
+    start += count;
+
+    if headless {
+      ctx.gfx.draw_calls += count_batches(ctx, layer_cmds);
+      continue;
+    }
+    begin_pass(ctx, clear, first_pass);
+    first_pass = false;
+    let (proj, view) = cameras[layer];
+    draw_cmds(ctx, layer_cmds, proj * view);
+    sg_end_pass();
+  }
+
+  // the cursor goes over everything. This pass also clears the window on
+  // frames where nothing was drawn.
+  if !headless {
+    let clear = LayerClear {
+      color: None,
+      depth: true,
+    };
+    begin_pass(ctx, clear, first_pass);
+    quad::present_cursor(ctx);
+    sg_end_pass();
+  }
+
+  // hand the allocation back for next frame
+  cmds.clear();
+  ctx.gfx.draw_list.e = cmds;
+}
+
+/// starts a render pass that clears what the layer asks for. The frame's
+/// first pass also clears to `GraphicsCtx::bg`.
+fn begin_pass(ctx: &Ctx, clear: LayerClear, first_pass: bool) {
+  let bg = ctx.gfx.bg.extend(1.0);
+  let color = match clear.color {
+    Some(color) => SgColorAttachmentAction {
+      action: SgAction::Clear,
+      val: color.into(),
+    },
+    None if first_pass => SgColorAttachmentAction {
+      action: SgAction::Clear,
+      val: bg.into(),
+    },
+    None => SgColorAttachmentAction {
+      action: SgAction::Load,
+      val: bg.into(),
+    },
+  };
+  let depth = SgDepthAttachmentAction {
+    action: if clear.depth || first_pass {
+      SgAction::Clear
+    } else {
+      SgAction::Load
+    },
+    val: 1.0,
+  };
+  let action = SgPassAction {
+    colors: vec![color],
+    depth,
+    ..Default::default()
+  };
+  sg_begin_default_pass(&action, sapp_width(), sapp_height());
+}
+
+/// where the batch starting at `i` ends in a layer's sorted commands
+fn batch_end(ctx: &Ctx, cmds: &[DrawCmd], i: usize) -> usize {
+  let mut end = i + 1;
+  while end < cmds.len() && same_batch(ctx, &cmds[end - 1], &cmds[end]) {
+    end += 1;
+  }
+  end
+}
+
+/// the number of draw calls `draw_cmds` would make for a layer's sorted
+/// commands
+fn count_batches(ctx: &Ctx, cmds: &[DrawCmd]) -> usize {
+  let mut count = 0;
+  let mut i = 0;
+  while i < cmds.len() {
+    i = batch_end(ctx, cmds, i);
+    count += 1;
+  }
+  count
+}
+
+/// draws sorted commands from a single layer
+fn draw_cmds(ctx: &mut Ctx, cmds: &[DrawCmd], view_proj: Mat4) {
+  let mut current = None;
+  let mut i = 0;
+  while i < cmds.len() {
+    let cmd = cmds[i];
+    let end = batch_end(ctx, cmds, i);
+
+    if current != Some(cmd.primitive) {
+      match cmd.primitive {
+        PrimitiveKind::Mesh => mesh::apply(ctx),
+        // quads pick a pipeline per batch, see `quad::draw`
+        PrimitiveKind::Quad => {}
+        PrimitiveKind::Point => point::apply(ctx, view_proj),
+        PrimitiveKind::Line => line::apply(ctx, view_proj),
+        PrimitiveKind::Triangle => triangle::apply(ctx, view_proj),
+      }
+      current = Some(cmd.primitive);
+    }
+
+    match cmd.primitive {
+      PrimitiveKind::Mesh => mesh::draw(ctx, cmd.index, view_proj),
+      PrimitiveKind::Quad => quad::draw(ctx, cmd.index, end - i, view_proj),
+      PrimitiveKind::Point => point::draw(ctx, cmd.index, end - i),
+      PrimitiveKind::Line => line::draw(ctx, cmd.index, end - i),
+      PrimitiveKind::Triangle => triangle::draw(ctx, cmd.index, end - i),
+    }
+    i = end;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// draws ten images on the first frame that all share a texture, then ten
+  /// that alternate between two textures
+  #[derive(Default)]
+  struct Quads {
+    textures: Vec<usize>,
+    /// `draw_calls` as seen at the start of each frame, which is the count
+    /// from the frame before
+    draw_calls: Vec<usize>,
+  }
+
+  impl KApp for Quads {
+    fn new() -> Self {
+      Default::default()
+    }
+
+    fn init(&mut self, ctx: &mut Ctx) {
+      let pixels = [255; 4 * 4 * 4];
+      for _ in 0..2 {
+        self.textures.push(add_img(ctx, &pixels, 4, 4).id);
+      }
+    }
+
+    fn frame(&mut self, ctx: &mut Ctx) {
+      self.draw_calls.push(draw_calls(ctx));
+    }
+
+    fn draw(&mut self, ctx: &mut Ctx, alpha: f32) {
+      let alternate = ctx.frame_count > 1;
+      for i in 0..10 {
+        let transform = Transform2d {
+          pos: vec2(i as f32 * 8.0, 0.0),
+          ..Default::default()
+        };
+        let texture = if alternate { i % 2 } else { 0 };
+        draw_image(ctx, self.textures[texture], Pivot::Center, transform);
+      }
+    }
+  }
+
+  #[test]
+  fn counts_batches_when_headless() {
+    let (app, ctx) = run_headless::<Quads>(3, &[]);
+    assert_eq!(app.draw_calls[1], 1);
+    // 2d quads at the same depth keep their order, so switching textures
+    // breaks the batch every time
+    assert_eq!(app.draw_calls[2], 10);
+    assert_eq!(draw_calls(&ctx), 10);
+  }
+}
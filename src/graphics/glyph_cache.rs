@@ -0,0 +1,281 @@
+//! The texture vector font glyphs are drawn into as they're needed.
+//!
+//! Glyphs are packed onto shelves, rows as tall as the first glyph put on
+//! them. When there's no room left, the shelf that's gone unused the
+//! longest is cleared and reused, so text in many sizes and scripts only
+//! keeps what's recently been drawn. Shelves used this frame are never
+//! cleared, since sprites on them are already waiting to be drawn.
+
+use crate::graphics::raster::rasterize;
+use crate::graphics::sprite::sprite_in_sheet;
+use crate::graphics::*;
+
+/// Glyphs are never drawn into the cache bigger than this many pixels per
+/// em. Bigger text scales them up instead.
+pub(crate) const MAX_GLYPH_PX: u16 = 256;
+
+/// The sprite for a vector font glyph drawn at `px` pixels per em, drawing
+/// it into the cache if it isn't there already. `None` if the glyph has
+/// nothing to draw or there's no room for it this frame.
+pub(crate) fn cached_glyph(ctx: &mut Ctx, font: usize, glyph: u16, px: u16) -> Option<Sprite> {
+  let key = (font, glyph, px);
+  let frame = ctx.gfx.glyph_cache.frame;
+  if let Some(cached) = ctx.gfx.glyph_cache.glyphs.get(&key).copied() {
+    if cached.sprite.is_some() {
+      ctx.gfx.glyph_cache.shelves[cached.shelf].last_used = frame;
+    }
+    return cached.sprite;
+  }
+
+  let bitmap = match &ctx.gfx.fonts.e[font].kind {
+    FontKind::Vector(ttf) => rasterize(&ttf.outline(glyph), px as f32 / ttf.units_per_em),
+    FontKind::Bitmap { .. } => return None,
+  };
+  let bitmap = match bitmap {
+    Some(bitmap) => bitmap,
+    None => {
+      let empty = CachedGlyph {
+        sprite: None,
+        shelf: 0,
+      };
+      ctx.gfx.glyph_cache.glyphs.insert(key, empty);
+      return None;
+    }
+  };
+
+  let texture = cache_texture(ctx);
+  let size = texture.w;
+  let cache = &mut ctx.gfx.glyph_cache;
+  let shelf = find_room(cache, size, bitmap.w, bitmap.h)?;
+  let (x, y) = (cache.shelves[shelf].x, cache.shelves[shelf].y);
+
+  for row in 0..bitmap.h {
+    for col in 0..bitmap.w {
+      let alpha = bitmap.coverage[(row * bitmap.w + col) as usize];
+      let i = (((y + row) * size + x + col) * 4) as usize;
+      cache.pixels[i..i + 4].copy_from_slice(&[0xFF, 0xFF, 0xFF, alpha]);
+    }
+  }
+  cache.dirty = true;
+
+  let shelf_ctx = &mut cache.shelves[shelf];
+  shelf_ctx.x += bitmap.w;
+  shelf_ctx.last_used = frame;
+  shelf_ctx.glyphs.push(key);
+
+  // the pen sits at the bitmap's origin, measured from its bottom left
+  let pivot = Pivot::Px(-bitmap.min_x as f32, -bitmap.min_y as f32);
+  let sprite = sprite_in_sheet(texture.id, (size, size), x, y, bitmap.w, bitmap.h, pivot);
+  let cached = CachedGlyph {
+    sprite: Some(sprite),
+    shelf,
+  };
+  cache.glyphs.insert(key, cached);
+  Some(sprite)
+}
+
+/// Sends glyphs drawn this frame to the gpu. Run once per frame, before
+/// drawing, since a dynamic texture can only be updated once a frame.
+pub(crate) fn upload(ctx: &mut Ctx) {
+  let cache = &mut ctx.gfx.glyph_cache;
+  cache.frame += 1;
+  if !cache.dirty {
+    return;
+  }
+  cache.dirty = false;
+  if ctx.gfx.headless {
+    return;
+  }
+  if let Some(texture) = cache.texture {
+    let size = cache.pixels.len() as i32;
+    let img = ctx.gfx.images.e[texture.id].e;
+    sg_update_image(img, &[(cache.pixels.as_ptr(), size)]);
+  }
+}
+
+/// the cache's texture, made the first time it's needed
+fn cache_texture(ctx: &mut Ctx) -> Texture {
+  if let Some(texture) = ctx.gfx.glyph_cache.texture {
+    return texture;
+  }
+
+  let size = ctx.gfx.config.glyph_cache_size;
+  let id = ctx.gfx.images.e.len();
+  let e = if ctx.gfx.headless {
+    Default::default()
+  } else {
+    // dynamic textures can't start with pixels, they arrive with `upload`
+    sg_make_image::<u8>(
+      None,
+      &SgImageDesc {
+        width: size as i32,
+        height: size as i32,
+        usage: SgUsage::Dynamic,
+        pixel_format: SgPixelFormat::RGBA8,
+        min_filter: SgFilter::Linear,
+        mag_filter: SgFilter::Linear,
+        wrap_u: SgWrap::ClampToEdge,
+        wrap_v: SgWrap::ClampToEdge,
+        ..Default::default()
+      },
+    )
+  };
+  ctx.gfx.images.e.push(Image {
+    e,
+    w: size,
+    h: size,
+  });
+
+  let texture = Texture {
+    id,
+    w: size,
+    h: size,
+  };
+  let cache = &mut ctx.gfx.glyph_cache;
+  cache.texture = Some(texture);
+  cache.pixels = vec![0; (size * size * 4) as usize];
+  cache.dirty = true;
+  texture
+}
+
+/// A shelf with room for a `w` by `h` glyph, making or clearing one if
+/// needed
+fn find_room(cache: &mut GlyphCacheCtx, size: u32, w: u32, h: u32) -> Option<usize> {
+  if w > size || h > size {
+    return None;
+  }
+
+  // the shortest shelf it fits on wastes the least space
+  let fits = cache
+    .shelves
+    .iter()
+    .enumerate()
+    .filter(|(_, shelf)| shelf.h >= h && shelf.x + w <= size)
+    .min_by_key(|(_, shelf)| shelf.h);
+  if let Some((i, _)) = fits {
+    return Some(i);
+  }
+
+  let top = cache.shelves.last().map_or(0, |shelf| shelf.y + shelf.h);
+  if top + h <= size {
+    cache.shelves.push(Shelf {
+      y: top,
+      h,
+      x: 0,
+      last_used: cache.frame,
+      glyphs: vec![],
+    });
+    return Some(cache.shelves.len() - 1);
+  }
+
+  // out of room, so clear the least recently used shelf that's tall enough
+  let frame = cache.frame;
+  let unused = cache
+    .shelves
+    .iter()
+    .enumerate()
+    .filter(|(_, shelf)| shelf.h >= h && shelf.last_used < frame)
+    .min_by_key(|(_, shelf)| shelf.last_used);
+  if let Some((i, _)) = unused {
+    clear_shelf(cache, size, i);
+    return Some(i);
+  }
+
+  // every shelf is too short, so if none are in use, start over
+  if cache.shelves.iter().any(|shelf| shelf.last_used == frame) {
+    return None;
+  }
+  for i in 0..cache.shelves.len() {
+    clear_shelf(cache, size, i);
+  }
+  cache.shelves.clear();
+  find_room(cache, size, w, h)
+}
+
+fn clear_shelf(cache: &mut GlyphCacheCtx, size: u32, i: usize) {
+  let shelf = &mut cache.shelves[i];
+  for key in shelf.glyphs.drain(..) {
+    cache.glyphs.remove(&key);
+  }
+  shelf.x = 0;
+  let start = (shelf.y * size * 4) as usize;
+  let end = ((shelf.y + shelf.h) * size * 4) as usize;
+  for byte in &mut cache.pixels[start..end] {
+    *byte = 0;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SIZE: u32 = 16;
+
+  fn cache() -> GlyphCacheCtx {
+    GlyphCacheCtx {
+      pixels: vec![0; (SIZE * SIZE * 4) as usize],
+      ..Default::default()
+    }
+  }
+
+  /// finds room for a glyph and fills the space, like `cached_glyph`
+  fn place(cache: &mut GlyphCacheCtx, glyph: u16, w: u32, h: u32) -> Option<usize> {
+    let shelf = find_room(cache, SIZE, w, h)?;
+    let key = (0, glyph, 16);
+    let cached = CachedGlyph {
+      sprite: None,
+      shelf,
+    };
+    cache.glyphs.insert(key, cached);
+    let shelf_ctx = &mut cache.shelves[shelf];
+    shelf_ctx.x += w;
+    shelf_ctx.last_used = cache.frame;
+    shelf_ctx.glyphs.push(key);
+    Some(shelf)
+  }
+
+  #[test]
+  fn never_clears_shelves_used_this_frame() {
+    let mut cache = cache();
+    assert_eq!(place(&mut cache, 1, SIZE, 8), Some(0));
+    assert_eq!(place(&mut cache, 2, SIZE, 8), Some(1));
+    assert_eq!(place(&mut cache, 3, SIZE, 8), None);
+    assert_eq!(cache.glyphs.len(), 2);
+
+    // next frame, only the second shelf has been drawn from again
+    cache.frame += 1;
+    cache.shelves[1].last_used = cache.frame;
+    assert_eq!(place(&mut cache, 3, SIZE, 8), Some(0));
+    assert!(!cache.glyphs.contains_key(&(0, 1, 16)));
+    assert!(cache.glyphs.contains_key(&(0, 2, 16)));
+    assert_eq!(cache.shelves[0].glyphs, vec![(0, 3, 16)]);
+  }
+
+  #[test]
+  fn reuses_the_least_recently_used_shelf() {
+    let mut cache = cache();
+    for glyph in 0..4 {
+      assert_eq!(place(&mut cache, glyph, SIZE, 4), Some(glyph as usize));
+    }
+    cache.shelves[0].last_used = 3;
+    cache.shelves[1].last_used = 1;
+    cache.shelves[2].last_used = 2;
+    cache.shelves[3].last_used = 4;
+    cache.frame = 5;
+
+    // leftover pixels from the old glyph are cleared too
+    let row = (cache.shelves[1].y * SIZE * 4) as usize;
+    cache.pixels[row + 3] = 0xFF;
+    assert_eq!(place(&mut cache, 9, SIZE, 4), Some(1));
+    assert_eq!(cache.pixels[row + 3], 0);
+    assert!(!cache.glyphs.contains_key(&(0, 1, 16)));
+  }
+
+  #[test]
+  fn leaves_room_on_shelves_for_shorter_glyphs() {
+    let mut cache = cache();
+    assert_eq!(place(&mut cache, 1, 4, 8), Some(0));
+    assert_eq!(place(&mut cache, 2, 4, 6), Some(0));
+    assert_eq!(cache.shelves[0].x, 8);
+  }
+}
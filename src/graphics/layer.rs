@@ -0,0 +1,121 @@
+//! Render layers, for drawing things like a hud in screen space over a 3d
+//! scene. Draw calls target a layer with the `_on` variants, e.g.
+//! `draw_sprite_on(ctx, Layer::Ui, ...)`; the plain ones draw on
+//! `Layer::World`.
+
+use crate::graphics::*;
+
+impl Layer {
+  pub(crate) fn index(self) -> usize {
+    match self {
+      Layer::Background => 0,
+      Layer::World => 1,
+      Layer::Ui => 2,
+      Layer::Custom(n) => 3 + n as usize,
+    }
+  }
+
+  pub(crate) fn from_index(index: usize) -> Layer {
+    match index {
+      0 => Layer::Background,
+      1 => Layer::World,
+      2 => Layer::Ui,
+      n => Layer::Custom((n - 3) as u8),
+    }
+  }
+}
+
+impl LayerCtx {
+  fn new(layer: Layer) -> Self {
+    let camera = match layer {
+      Layer::Ui => LayerCamera::Screen,
+      _ => LayerCamera::World,
+    };
+    Self {
+      camera,
+      clear: LayerClear {
+        color: None,
+        depth: true,
+      },
+      order: layer.index() as i32,
+    }
+  }
+}
+
+/// the settings for a layer, setting it up with defaults on first use
+pub(crate) fn layer_mut(ctx: &mut Ctx, layer: Layer) -> &mut LayerCtx {
+  let layers = &mut ctx.gfx.layers.e;
+  while layers.len() <= layer.index() {
+    layers.push(LayerCtx::new(Layer::from_index(layers.len())));
+  }
+  &mut layers[layer.index()]
+}
+
+/// Sets how a layer's draw calls are projected. By default the ui layer
+/// uses `LayerCamera::Screen` and the rest share the world camera.
+pub fn set_layer_camera(ctx: &mut Ctx, layer: Layer, camera: LayerCamera) {
+  layer_mut(ctx, layer).camera = camera;
+}
+
+/// Sets what's cleared before a layer is drawn. By default only depth is, so
+/// each layer draws over the ones before it.
+pub fn set_layer_clear(ctx: &mut Ctx, layer: Layer, clear: LayerClear) {
+  layer_mut(ctx, layer).clear = clear;
+}
+
+/// Sets when a layer is drawn relative to the others, lowest first. By
+/// default the background comes first, then the world, the ui, and custom
+/// layers by number.
+pub fn set_layer_order(ctx: &mut Ctx, layer: Layer, order: i32) {
+  layer_mut(ctx, layer).order = order;
+}
+
+/// the projection and view a layer is drawn with this frame
+pub fn layer_proj_view(ctx: &Ctx, layer: Layer) -> (Mat4, Mat4) {
+  let camera = match ctx.gfx.layers.e.get(layer.index()) {
+    Some(settings) => settings.camera,
+    None => LayerCtx::new(layer).camera,
+  };
+  match camera {
+    LayerCamera::World => (ctx.gfx.proj, ctx.gfx.view),
+    LayerCamera::Screen => {
+      let proj = Mat4::orthographic_rh_gl(
+        0.0,
+        window_width(ctx),
+        0.0,
+        window_height(ctx),
+        -DEPTH_2D,
+        DEPTH_2D,
+      );
+      (proj, Mat4::identity())
+    }
+    LayerCamera::Custom { proj, view } => (proj, view),
+  }
+}
+
+/// How many pixels one unit is on `layer` around `pos`, for drawing things
+/// in as much detail as they'll show on screen
+pub(crate) fn layer_pixels_per_unit(ctx: &Ctx, layer: Layer, pos: Vec3) -> f32 {
+  let (proj, view) = layer_proj_view(ctx, layer);
+  let clip = proj * view;
+  let to_ndc = |p: Vec3| {
+    let p = clip.mul_vec4(p.extend(1.0));
+    p.truncate().truncate() / p.w()
+  };
+  let window = vec2(window_width(ctx), window_height(ctx));
+  let step = (to_ndc(pos + Vec3::unit_x()) - to_ndc(pos)) * window * 0.5;
+  let ppu = step.length();
+  if ppu.is_finite() && ppu > 0.0 {
+    ppu
+  } else {
+    1.0
+  }
+}
+
+/// the indices of the layers in use, in the order they're drawn
+pub(crate) fn layers_in_order(ctx: &Ctx) -> Vec<usize> {
+  let layers = &ctx.gfx.layers.e;
+  let mut order: Vec<usize> = (0..layers.len()).collect();
+  order.sort_by_key(|i| (layers[*i].order, *i));
+  order
+}
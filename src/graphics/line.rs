@@ -4,18 +4,30 @@ use crate::*;
 use std::mem::size_of;
 
 pub fn draw_line(ctx: &mut Ctx, point_a: Vec3, point_b: Vec3, color: Vec4) {
-  let i = ctx.gfx.lines.count;
-  ctx.gfx.lines.count += 1;
+  draw_line_on(ctx, Layer::World, point_a, point_b, color);
+}
+
+pub fn draw_line_on(ctx: &mut Ctx, layer: Layer, point_a: Vec3, point_b: Vec3, color: Vec4) {
   // TODO this is essentially 2 vertices. Could do this with a mesh?
-  ctx.gfx.lines.e[i] = DrawLine {
+  let line = DrawLine {
     point_a: point_a.extend(1.0),
     color_a: color,
     point_b: point_b.extend(1.0),
     color_b: color,
   };
+  let index = ctx.gfx.lines.e.len();
+  if ctx.gfx.lines.e.push(line, "line") {
+    push_cmd(
+      ctx,
+      layer,
+      PrimitiveKind::Line,
+      index,
+      (point_a + point_b) / 2.0,
+    );
+  }
 }
 
-pub fn init(ctx: &mut Ctx) {
+pub fn init(ctx: &mut Ctx) -> Result<(), Error> {
   // TODO common primitives (line, point, maybe others?) could share the same shaders
   let (vs_src, fs_src) = match sg_api() {
     SgApi::OpenGL33 => (
@@ -23,7 +35,7 @@ pub fn init(ctx: &mut Ctx) {
       include_str!("line.frag.glsl"),
     ),
     SgApi::Metal => (include_str!("line.vs.metal"), include_str!("line.fs.metal")),
-    _ => panic!(),
+    _ => return Err(Error::UnsupportedBackend { primitive: "line" }),
   };
 
   let pipeline = sg_make_pipeline(&SgPipelineDesc {
@@ -56,52 +68,65 @@ pub fn init(ctx: &mut Ctx) {
       ],
       ..Default::default()
     },
-    // depth_stencil: SgDepthStencilState {
-    //     depth_compare_func: SgCompareFunc::LessEqual,
-    //     depth_write_enabled: true,
-    //     ..Default::default()
-    // },
-    // blend: SgBlendState {
-    //     enabled: true,
-    //     color_format: SgPixelFormat::RGBA8,
-    //     depth_format: SgPixelFormat::Depth,
-    //     dst_factor_rgb: SgBlendFactor::OneMinusSrcAlpha,
-    //     ..Default::default()
-    // },
+    depth_stencil: SgDepthStencilState {
+      depth_compare_func: SgCompareFunc::LessEqual,
+      ..Default::default()
+    },
+    blend: SgBlendState {
+      enabled: true,
+      dst_factor_rgb: SgBlendFactor::OneMinusSrcAlpha,
+      src_factor_rgb: SgBlendFactor::SrcAlpha,
+      ..Default::default()
+    },
     ..Default::default()
   });
 
+  let capacity = ctx.gfx.lines.e.capacity;
   let bindings = SgBindings {
-    vertex_buffers: vec![sg_make_buffer::<()>(
-      None,
-      &SgBufferDesc {
-        size: BYTES_LINES,
-        usage: SgUsage::Stream,
-        ..Default::default()
-      },
-    )],
+    vertex_buffers: vec![make_stream_buffer::<DrawLine>(capacity)],
     ..Default::default()
   };
+  ctx.gfx.lines.gpu_capacity = capacity;
 
   ctx.gfx.lines.shape = GlShape { bindings, pipeline };
+
+  Ok(())
 }
 
-pub fn present(ctx: &mut Ctx) {
+/// copies this frame's lines to the gpu, ready for `draw`
+pub fn upload(ctx: &mut Ctx) {
+  let lines = &mut ctx.gfx.lines;
+  let count = lines.e.len();
+  if count == 0 {
+    return;
+  }
+
+  fit_stream_buffer::<DrawLine>(
+    &mut lines.shape.bindings.vertex_buffers[0],
+    &mut lines.gpu_capacity,
+    lines.e.capacity,
+  );
+
   sg_update_buffer(
-    ctx.gfx.lines.shape.bindings.vertex_buffers[0],
-    &ctx.gfx.lines.e,
-    (ctx.gfx.lines.count * size_of::<DrawLine>()) as i32,
+    lines.shape.bindings.vertex_buffers[0],
+    &lines.e.e[0],
+    (count * size_of::<DrawLine>()) as i32,
   );
+}
 
+pub fn apply(ctx: &mut Ctx, view_proj: Mat4) {
   sg_apply_pipeline(ctx.gfx.lines.shape.pipeline);
   sg_apply_bindings(&ctx.gfx.lines.shape.bindings);
-
   sg_apply_uniforms(
     SgShaderStage::Vertex,
     0,
-    &ctx.gfx.view_proj,
+    &view_proj,
     size_of::<Mat4>() as i32,
   );
+}
 
-  sg_draw(0, (ctx.gfx.lines.count * 2) as i32, 1);
+/// draws `count` lines starting at `first` in this frame's draw buffer
+pub fn draw(ctx: &mut Ctx, first: usize, count: usize) {
+  sg_draw((first * 2) as i32, (count * 2) as i32, 1);
+  ctx.gfx.draw_calls += 1;
 }
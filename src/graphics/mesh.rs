@@ -1,3 +1,4 @@
+use crate::graphics::draw_list::push_cmd;
 use crate::*;
 use std::mem;
 use std::mem::size_of_val;
@@ -20,22 +21,20 @@ pub(crate) struct MeshVert {
 // } model_t,
 
 pub fn draw_mesh(ctx: &mut Ctx, mesh_i: usize, transform: Mat4) {
-  let mesh = &mut ctx.gfx.mesh;
-
-  let i = mesh.count;
+  draw_mesh_on(ctx, Layer::World, mesh_i, transform);
+}
 
-  if i == MAX_MESHES {
-    panic!("Too many mesh draw calls! Maximum is {}", MAX_MESHES);
+pub fn draw_mesh_on(ctx: &mut Ctx, layer: Layer, mesh_i: usize, transform: Mat4) {
+  let index = ctx.gfx.mesh.e.len();
+  if ctx.gfx.mesh.e.push(DrawMesh { mesh_i, transform }, "mesh") {
+    let pos = Vec3::from(transform.w_axis().truncate());
+    push_cmd(ctx, layer, PrimitiveKind::Mesh, index, pos);
   }
-
-  mesh.count += 1;
-
-  mesh.e[i] = DrawMesh { mesh_i, transform };
 }
 
 pub fn add_cube_mesh(ctx: &mut Ctx) {}
 
-pub fn init(ctx: &mut Ctx) {
+pub fn init(ctx: &mut Ctx) -> Result<(), Error> {
   let shape = &mut ctx.gfx.mesh.shape;
 
   // cube vertex buffer
@@ -208,7 +207,7 @@ pub fn init(ctx: &mut Ctx) {
               frag_color = color;
             }",
     ),
-    _ => panic!("unimplemented graphics backened"),
+    _ => return Err(Error::UnsupportedBackend { primitive: "mesh" }),
   };
 
   let shd = sg_make_shader(&SgShaderDesc {
@@ -280,17 +279,21 @@ pub fn init(ctx: &mut Ctx) {
     index_buffer: ibuf,
     ..Default::default()
   };
+
+  Ok(())
 }
 
-pub fn present(ctx: &mut Ctx) {
-  let shape = &mut ctx.gfx.mesh.shape;
+pub fn apply(ctx: &mut Ctx) {
+  let shape = &ctx.gfx.mesh.shape;
+  sg_apply_pipeline(shape.pipeline);
+  sg_apply_bindings(&shape.bindings);
+}
 
-  for i in 0..ctx.gfx.mesh.count {
-    let model = ctx.gfx.mesh.e[i].transform;
-    let mvp = ctx.gfx.view_proj * model;
-    sg_apply_pipeline(shape.pipeline);
-    sg_apply_bindings(&shape.bindings);
-    sg_apply_uniforms(SgShaderStage::Vertex, 0, &mvp, 64);
-    sg_draw(0, 36, 1);
-  }
+/// draws the mesh at `i` in this frame's draw buffer
+pub fn draw(ctx: &mut Ctx, i: usize, view_proj: Mat4) {
+  let model = ctx.gfx.mesh.e.e[i].transform;
+  let mvp = view_proj * model;
+  sg_apply_uniforms(SgShaderStage::Vertex, 0, &mvp, 64);
+  sg_draw(0, 36, 1);
+  ctx.gfx.draw_calls += 1;
 }
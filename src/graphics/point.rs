@@ -4,19 +4,18 @@ use crate::*;
 use std::mem::size_of;
 
 pub fn draw_point(ctx: &mut Ctx, pos: Vec3, color: Vec4) {
-  let i = ctx.gfx.points.count;
+  draw_point_on(ctx, Layer::World, pos, color);
+}
 
-  // TODO debug only?
-  if i >= MAX_POINTS {
-    panic!("can't draw that many points!")
+pub fn draw_point_on(ctx: &mut Ctx, layer: Layer, pos: Vec3, color: Vec4) {
+  let point = DrawPoint::new(pos.x(), pos.y(), pos.z(), color);
+  let index = ctx.gfx.points.e.len();
+  if ctx.gfx.points.e.push(point, "point") {
+    push_cmd(ctx, layer, PrimitiveKind::Point, index, pos);
   }
-
-  ctx.gfx.points.count += 1;
-
-  ctx.gfx.points.e[i] = DrawPoint::new(pos.x(), pos.y(), pos.z(), color);
 }
 
-pub fn init(ctx: &mut Ctx) {
+pub fn init(ctx: &mut Ctx) -> Result<(), Error> {
   let (vs_src, fs_src) = match sg_api() {
     SgApi::OpenGL33 => (
       include_str!("point.vert.glsl"),
@@ -26,7 +25,7 @@ pub fn init(ctx: &mut Ctx) {
       include_str!("point.vs.metal"),
       include_str!("point.fs.metal"),
     ),
-    _ => panic!(),
+    _ => return Err(Error::UnsupportedBackend { primitive: "point" }),
   };
 
   let shader_desc = SgShaderDesc {
@@ -64,18 +63,24 @@ pub fn init(ctx: &mut Ctx) {
     primitive_type: SgPrimitiveType::Points,
     shader,
     layout,
+    blend: SgBlendState {
+      enabled: true,
+      dst_factor_rgb: SgBlendFactor::OneMinusSrcAlpha,
+      src_factor_rgb: SgBlendFactor::SrcAlpha,
+      ..Default::default()
+    },
+    depth_stencil: SgDepthStencilState {
+      depth_compare_func: SgCompareFunc::LessEqual,
+      ..Default::default()
+    },
     ..Default::default()
   };
 
   let pipeline = sg_make_pipeline(&point_pipeline_desc);
 
-  let vertex_buffer_desc = SgBufferDesc {
-    buffer_type: SgBufferType::VertexBuffer,
-    size: BYTES_POINTS,
-    usage: SgUsage::Stream,
-  };
-
-  let vertex_buffer = sg_make_buffer::<()>(None, &vertex_buffer_desc);
+  let capacity = ctx.gfx.points.e.capacity;
+  let vertex_buffer = make_stream_buffer::<DrawPoint>(capacity);
+  ctx.gfx.points.gpu_capacity = capacity;
 
   let vertex_buffers = vec![vertex_buffer];
 
@@ -85,21 +90,44 @@ pub fn init(ctx: &mut Ctx) {
   };
 
   ctx.gfx.points.shape = GlShape { bindings, pipeline };
+
+  Ok(())
 }
 
-pub fn present(ctx: &mut Ctx) {
+/// copies this frame's points to the gpu, ready for `draw`
+pub fn upload(ctx: &mut Ctx) {
+  let points = &mut ctx.gfx.points;
+  let count = points.e.len();
+  if count == 0 {
+    return;
+  }
+
+  fit_stream_buffer::<DrawPoint>(
+    &mut points.shape.bindings.vertex_buffers[0],
+    &mut points.gpu_capacity,
+    points.e.capacity,
+  );
+
   sg_update_buffer(
-    ctx.gfx.points.shape.bindings.vertex_buffers[0],
-    &ctx.gfx.points.e,
-    (ctx.gfx.points.count * size_of::<DrawPoint>()) as i32,
+    points.shape.bindings.vertex_buffers[0],
+    &points.e.e[0],
+    (count * size_of::<DrawPoint>()) as i32,
   );
+}
+
+pub fn apply(ctx: &mut Ctx, view_proj: Mat4) {
   sg_apply_pipeline(ctx.gfx.points.shape.pipeline);
   sg_apply_bindings(&ctx.gfx.points.shape.bindings);
   sg_apply_uniforms(
     SgShaderStage::Vertex,
     0,
-    &ctx.gfx.view_proj,
+    &view_proj,
     size_of::<Mat4>() as i32,
   );
-  sg_draw(0, ctx.gfx.points.count as i32, 1);
+}
+
+/// draws `count` points starting at `first` in this frame's draw buffer
+pub fn draw(ctx: &mut Ctx, first: usize, count: usize) {
+  sg_draw(first as i32, count as i32, 1);
+  ctx.gfx.draw_calls += 1;
 }
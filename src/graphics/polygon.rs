@@ -0,0 +1,63 @@
+use crate::geometry::triangulate;
+use crate::graphics::triangle::push_triangle;
+use crate::math::*;
+use crate::{Ctx, Layer};
+
+/// Fills a polygon with a solid color. It can be convex or concave, wound
+/// either way, as long as its edges don't cross.
+pub fn fill_polygon(ctx: &mut Ctx, points: &[Vec2], color: Vec4) {
+  fill_polygon_on(ctx, Layer::World, 0.0, points, color);
+}
+
+/// `fill_polygon` on a specific render layer and depth
+pub fn fill_polygon_on(ctx: &mut Ctx, layer: Layer, z: f32, points: &[Vec2], color: Vec4) {
+  fill_polygon_with(ctx, layer, z, points, |_| color);
+}
+
+/// Fills a polygon, blending between a color for each of its points. Points
+/// past the end of `colors` use its last color, and nothing is drawn if it's
+/// empty.
+pub fn fill_polygon_gradient(ctx: &mut Ctx, points: &[Vec2], colors: &[Vec4]) {
+  fill_polygon_gradient_on(ctx, Layer::World, 0.0, points, colors);
+}
+
+/// `fill_polygon_gradient` on a specific render layer and depth
+pub fn fill_polygon_gradient_on(
+  ctx: &mut Ctx,
+  layer: Layer,
+  z: f32,
+  points: &[Vec2],
+  colors: &[Vec4],
+) {
+  let last = match colors.last() {
+    Some(last) => *last,
+    None => return,
+  };
+  fill_polygon_with(ctx, layer, z, points, |i| {
+    colors.get(i).copied().unwrap_or(last)
+  });
+}
+
+fn fill_polygon_with(
+  ctx: &mut Ctx,
+  layer: Layer,
+  z: f32,
+  points: &[Vec2],
+  color: impl Fn(usize) -> Vec4,
+) {
+  if points.is_empty() {
+    return;
+  }
+  let center = points.iter().fold(Vec2::zero(), |sum, p| sum + *p) / points.len() as f32;
+  for [a, b, c] in triangulate(points) {
+    let corners = [points[a], points[b], points[c]];
+    push_triangle(
+      ctx,
+      layer,
+      z,
+      corners,
+      [color(a), color(b), color(c)],
+      center,
+    );
+  }
+}
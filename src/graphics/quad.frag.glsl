@@ -1,7 +1,9 @@
 #version 410 core
 
 in vec2 uv;
-in vec4 debug_color;
+in vec4 color;
+in vec3 flash;
+flat in int alpha_mode;
 
 out vec4 final_color;
 
@@ -9,8 +11,21 @@ uniform sampler2D our_texture;
 
 void main()
 { 
-  final_color = texture(our_texture, uv);
-  if (final_color.a <= 0.01) {
+  vec4 texel = texture(our_texture, uv);
+  if (texel.a <= 0.01) {
     discard;
   }
+
+  if (alpha_mode == 2) {
+    // the texture is premultiplied, so the tint has to be too
+    final_color = texel * vec4(color.rgb * color.a, color.a);
+    final_color.rgb += flash * final_color.a;
+  } else {
+    final_color = texel * color;
+    final_color.rgb += flash;
+    // multiply blending needs premultiplied output to respect alpha
+    if (alpha_mode == 1) {
+      final_color.rgb *= final_color.a;
+    }
+  }
 }
@@ -2,9 +2,27 @@
 using namespace metal;
 struct fs_in {
     float2 uv;
+    float4 color;
+    float3 flash;
+    int alpha_mode [[flat]];
 };
 fragment float4 _main(fs_in in [[stage_in]],
    texture2d<float> tex [[texture(0)]],
    sampler smp [[sampler(0)]]) {
-    return tex.sample(smp, in.uv).xyzw;
+    float4 texel = tex.sample(smp, in.uv);
+    if (texel.a <= 0.01) {
+        discard_fragment();
+    }
+    float4 color;
+    if (in.alpha_mode == 2) {
+        color = texel * float4(in.color.rgb * in.color.a, in.color.a);
+        color.rgb += in.flash * color.a;
+    } else {
+        color = texel * in.color;
+        color.rgb += in.flash;
+        if (in.alpha_mode == 1) {
+            color.rgb *= color.a;
+        }
+    }
+    return color;
 };
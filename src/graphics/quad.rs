@@ -13,23 +13,87 @@ const VERTICES_PER_QUAD: usize = 4;
 
 const INDICES_PER_QUAD: usize = 6;
 
-const MAX_QUAD_VERTS: usize = MAX_QUADS * VERTICES_PER_QUAD;
+/// matches the uniform block in the quad vertex shader
+#[repr(C)]
+struct QuadUniforms {
+  projection: Mat4,
+  /// x is how the fragment shader treats alpha: 0 as is, 1 multiplied into
+  /// the output colors for multiply blending, 2 already premultiplied
+  blend: Vec4,
+}
 
-pub(crate) fn draw_quad(ctx: &mut Ctx, quad: DrawQuad) {
-  let i = ctx.gfx.quads.count;
-  ctx.gfx.quads.count += 1;
-  ctx.gfx.quads.e[i] = quad;
+impl QuadUniforms {
+  fn new(projection: Mat4, blend: BlendMode) -> Self {
+    let alpha_mode = match blend {
+      BlendMode::Alpha | BlendMode::Additive => 0.0,
+      BlendMode::Multiply => 1.0,
+      BlendMode::Premultiplied => 2.0,
+    };
+    Self {
+      projection,
+      blend: vec4(alpha_mode, 0.0, 0.0, 0.0),
+    }
+  }
 }
 
-pub fn init(ctx: &mut Ctx) {
-  let shape = &mut ctx.gfx.quads.shape;
+/// the blend state that gives each mode its look
+fn blend_state(blend: BlendMode) -> SgBlendState {
+  let (src, dst) = match blend {
+    BlendMode::Alpha => (SgBlendFactor::SrcAlpha, SgBlendFactor::OneMinusSrcAlpha),
+    BlendMode::Additive => (SgBlendFactor::SrcAlpha, SgBlendFactor::One),
+    BlendMode::Multiply => (SgBlendFactor::DstColor, SgBlendFactor::OneMinusSrcAlpha),
+    BlendMode::Premultiplied => (SgBlendFactor::One, SgBlendFactor::OneMinusSrcAlpha),
+  };
+  SgBlendState {
+    enabled: true,
+    src_factor_rgb: src,
+    dst_factor_rgb: dst,
+    ..Default::default()
+  }
+}
+
+pub(crate) fn draw_quad(ctx: &mut Ctx, layer: Layer, quad: DrawQuad) {
+  let index = ctx.gfx.quads.e.len();
+  let pos = Vec3::from(quad.transform.w_axis().truncate());
+  if ctx.gfx.quads.e.push(quad, "quad") {
+    push_cmd(ctx, layer, PrimitiveKind::Quad, index, pos);
+  }
+}
+
+/// makes the vertex and index buffers with room for `capacity` quads
+fn make_buffers(capacity: usize) -> (SgBuffer, SgBuffer) {
+  let capacity = max(capacity, 1);
+  let vertex_buffer = make_stream_buffer::<QuadCorners>(capacity);
+
+  // quad index order never changes, so I can pre-populate it for all possible quads
+  let mut indices: Vec<[u32; INDICES_PER_QUAD]> = vec![[0, 1, 2, 2, 1, 3]; capacity];
+  for (quad_i, quad_indices) in indices.iter_mut().enumerate() {
+    let offset = (quad_i * VERTICES_PER_QUAD) as u32;
+    for index in quad_indices.iter_mut() {
+      *index += offset;
+    }
+  }
+
+  let index_buffer = sg_make_buffer(
+    Some(&indices[0]),
+    &SgBufferDesc {
+      buffer_type: SgBufferType::IndexBuffer,
+      size: capacity * size_of::<[u32; INDICES_PER_QUAD]>(),
+      usage: SgUsage::Immutable,
+    },
+  );
+
+  (vertex_buffer, index_buffer)
+}
+
+pub fn init(ctx: &mut Ctx) -> Result<(), Error> {
   let (vs_src, fs_src) = match sg_api() {
     SgApi::OpenGL33 => (
       include_str!("quad.vert.glsl"),
       include_str!("quad.frag.glsl"),
     ),
     SgApi::Metal => (include_str!("quad.vs.metal"), include_str!("quad.fs.metal")),
-    _ => panic!("quad shaders not implemented for this platform"),
+    _ => return Err(Error::UnsupportedBackend { primitive: "quad" }),
   };
 
   // create a checkerboard texture
@@ -47,33 +111,15 @@ pub fn init(ctx: &mut Ctx) {
     },
   );
 
-  shape.bindings.fs_images.push(debug_image);
-  shape.bindings.vertex_buffers.push(sg_make_buffer::<()>(
-    None,
-    &SgBufferDesc {
-      buffer_type: SgBufferType::VertexBuffer,
-      size: MAX_QUAD_VERTS * size_of::<QuadVert>(),
-      usage: SgUsage::Stream,
-    },
-  ));
+  let capacity = ctx.gfx.quads.e.capacity;
+  let (vertex_buffer, index_buffer) = make_buffers(capacity);
+  ctx.gfx.quads.gpu_capacity = capacity;
+  ctx.gfx.quads.cursor_buffer = make_stream_buffer::<QuadCorners>(1);
 
-  // quad index order never changes, so I can pre-populate it for all possible quads
-  let mut indices: [[u16; INDICES_PER_QUAD]; MAX_QUADS] = [[0, 1, 2, 2, 1, 3]; MAX_QUADS];
-  for quad_i in 0..MAX_QUADS {
-    let offset = (quad_i * VERTICES_PER_QUAD) as u16;
-    for i in 0..INDICES_PER_QUAD {
-      indices[quad_i][i] += offset;
-    }
-  }
-
-  shape.bindings.index_buffer = sg_make_buffer(
-    Some(&indices),
-    &SgBufferDesc {
-      buffer_type: SgBufferType::IndexBuffer,
-      size: size_of_val(&indices),
-      usage: SgUsage::Immutable,
-    },
-  );
+  let shape = &mut ctx.gfx.quads.shape;
+  shape.bindings.fs_images.push(debug_image);
+  shape.bindings.vertex_buffers.push(vertex_buffer);
+  shape.bindings.index_buffer = index_buffer;
 
   // a shader (use separate shader sources here
   let shd = sg_make_shader(&SgShaderDesc {
@@ -86,10 +132,32 @@ pub fn init(ctx: &mut Ctx) {
         name: "in_uv",
         ..Default::default()
       },
+      SgShaderAttrDesc {
+        name: "in_color",
+        ..Default::default()
+      },
+      SgShaderAttrDesc {
+        name: "in_flash",
+        ..Default::default()
+      },
     ],
     vs: SgShaderStageDesc {
       source: Some(vs_src),
-      uniform_blocks: vec![std_uniform_block()],
+      uniform_blocks: vec![SgShaderUniformBlockDesc {
+        size: size_of::<QuadUniforms>() as i32,
+        uniforms: vec![
+          SgShaderUniformDesc {
+            name: "projection",
+            uniform_type: SgUniformType::Mat4,
+            array_count: 0,
+          },
+          SgShaderUniformDesc {
+            name: "blend",
+            uniform_type: SgUniformType::Float4,
+            array_count: 0,
+          },
+        ],
+      }],
       ..Default::default()
     },
     fs: SgShaderStageDesc {
@@ -103,101 +171,194 @@ pub fn init(ctx: &mut Ctx) {
     },
   });
 
-  // a pipeline state object
-  shape.pipeline = sg_make_pipeline(&SgPipelineDesc {
-    // TODO use triangle strip to reduce the number of indices sent?
-    primitive_type: SgPrimitiveType::Triangles,
-    shader: shd,
-    index_type: SgIndexType::UInt16,
-    layout: SgLayoutDesc {
-      // providing attr offsets, but no buffer stride, this should compute the stride
-      // TODO I added buffer_index because it was required... it's just set to the default value
-      attrs: vec![
-        SgVertexAttrDesc {
-          offset: 0,
-          format: SgVertexFormat::Float3,
-          buffer_index: 0,
-        },
-        SgVertexAttrDesc {
-          offset: 12,
-          format: SgVertexFormat::Float2,
-          buffer_index: 0,
-        },
-      ],
-      ..Default::default()
-    },
-    blend: SgBlendState {
-      enabled: true,
-      dst_factor_rgb: SgBlendFactor::OneMinusSrcAlpha,
-      src_factor_rgb: SgBlendFactor::SrcAlpha,
+  // a pipeline state object per blend mode
+  let make_pipeline = |blend: BlendMode| {
+    sg_make_pipeline(&SgPipelineDesc {
+      // TODO use triangle strip to reduce the number of indices sent?
+      primitive_type: SgPrimitiveType::Triangles,
+      shader: shd,
+      index_type: SgIndexType::UInt32,
+      layout: SgLayoutDesc {
+        buffers: vec![SgBufferLayoutDesc {
+          stride: size_of::<QuadVert>(),
+          ..Default::default()
+        }],
+        // TODO I added buffer_index because it was required... it's just set to the default value
+        attrs: vec![
+          SgVertexAttrDesc {
+            offset: 0,
+            format: SgVertexFormat::Float3,
+            buffer_index: 0,
+          },
+          SgVertexAttrDesc {
+            offset: 12,
+            format: SgVertexFormat::Float2,
+            buffer_index: 0,
+          },
+          SgVertexAttrDesc {
+            offset: 20,
+            format: SgVertexFormat::Float4,
+            buffer_index: 0,
+          },
+          SgVertexAttrDesc {
+            offset: 36,
+            format: SgVertexFormat::Float3,
+            buffer_index: 0,
+          },
+        ],
+      },
+      blend: blend_state(blend),
+      // hidden behind opaque primitives, but quads are sorted back to front
+      // among themselves rather than writing depth
+      depth_stencil: SgDepthStencilState {
+        depth_compare_func: SgCompareFunc::LessEqual,
+        ..Default::default()
+      },
+      // rasterizer: SgRasterizerState {
+      //     cull_mode: SgCullMode::Back,
+      //     sample_count: SAMPLE_COUNT,
+      //     ..Default::default()
+      // },
       ..Default::default()
-    },
-    // depth_stencil: SgDepthStencilState {
-    //     depth_compare_func: SgCompareFunc::LessEqual,
-    //     depth_write_enabled: true,
-    //     ..Default::default()
-    // },
-    // rasterizer: SgRasterizerState {
-    //     cull_mode: SgCullMode::Back,
-    //     sample_count: SAMPLE_COUNT,
-    //     ..Default::default()
-    // },
-    ..Default::default()
-  });
+    })
+  };
+  let pipelines = [
+    make_pipeline(BlendMode::Alpha),
+    make_pipeline(BlendMode::Additive),
+    make_pipeline(BlendMode::Multiply),
+    make_pipeline(BlendMode::Premultiplied),
+  ];
+  shape.pipeline = pipelines[BlendMode::Alpha as usize];
+  ctx.gfx.quads.pipelines = pipelines;
+
+  Ok(())
 }
 
-pub fn present(ctx: &mut Ctx) {
-  let shape = &mut ctx.gfx.quads.shape;
+/// Rearranges this frame's quads into the order of the sorted draw commands,
+/// so that runs sharing a texture sit next to each other and can be drawn in
+/// one call, then copies them to the gpu. Quad commands are pointed at their
+/// new place.
+pub fn upload(ctx: &mut Ctx, cmds: &mut [DrawCmd]) {
+  let quads = &mut ctx.gfx.quads;
+  let count = quads.e.len();
+  if count == 0 {
+    return;
+  }
 
-  // populate the quad vertex buffer for quad vertices
-  // we need to strip out extra info (uniforms) and create a contiguous array of vertices
-  // TODO could use vertex stride and
-  let mut vertices: [QuadCorners; MAX_QUADS] = [Default::default(); MAX_QUADS];
+  let mut sorted = Vec::with_capacity(quads.e.capacity);
+  for cmd in cmds.iter_mut() {
+    if cmd.primitive == PrimitiveKind::Quad {
+      sorted.push(quads.e.e[cmd.index]);
+      cmd.index = sorted.len() - 1;
+    }
+  }
+  quads.e.e = sorted;
+  // headless runs only need the order, to count batches
+  if ctx.gfx.headless {
+    return;
+  }
 
-  for quad_i in 0..ctx.gfx.quads.count {
-    vertices[quad_i] = ctx.gfx.quads.e[quad_i].corners;
+  // the index buffer is immutable, so both buffers are remade when growing
+  if quads.e.capacity > quads.gpu_capacity {
+    let shape = &mut quads.shape;
+    sg_destroy_buffer(shape.bindings.vertex_buffers[0]);
+    sg_destroy_buffer(shape.bindings.index_buffer);
+    let (vertex_buffer, index_buffer) = make_buffers(quads.e.capacity);
+    shape.bindings.vertex_buffers[0] = vertex_buffer;
+    shape.bindings.index_buffer = index_buffer;
+    quads.gpu_capacity = quads.e.capacity;
   }
 
+  // transforming on the cpu means every quad shares the layer's camera
+  // uniform, so nothing but the texture has to change between them
+  // TODO billboarding for sprites in 3d scenes
+  let vertices: Vec<QuadCorners> = quads
+    .e
+    .e
+    .iter()
+    .map(|quad| {
+      let mut corners = quad.corners;
+      for corner in corners.iter_mut() {
+        corner.pos = quad.transform.transform_point3(corner.pos);
+        corner.color = quad.color.into();
+        corner.flash = quad.flash.into();
+      }
+      corners
+    })
+    .collect();
+
   sg_update_buffer(
-    shape.bindings.vertex_buffers[0],
-    &vertices,
-    (ctx.gfx.quads.count * size_of::<QuadCorners>()) as i32,
+    quads.shape.bindings.vertex_buffers[0],
+    &vertices[0],
+    (count * size_of::<QuadCorners>()) as i32,
   );
+}
 
-  // draw quad batches
-  sg_apply_pipeline(shape.pipeline);
-  sg_apply_bindings(&shape.bindings);
-
-  for i in 0..ctx.gfx.quads.count {
-    let quad = &ctx.gfx.quads.e[i];
-    let transform = quad.transform;
-    let mut mv = ctx.gfx.view * transform;
+/// Draws `count` quads starting at `first` in this frame's draw buffer. They
+/// all have to share the first one's texture and blend mode, which picks the
+/// pipeline, so unlike other primitives quads apply their own.
+pub fn draw(ctx: &mut Ctx, first: usize, count: usize, view_proj: Mat4) {
+  let quad = ctx.gfx.quads.e.e[first];
+  let uniforms = QuadUniforms::new(view_proj, quad.blend);
+  let shape = &mut ctx.gfx.quads.shape;
+  shape.bindings.fs_images[0] = ctx.gfx.images.e[quad.img_id].e;
 
-    // cancel out some parts of the model_shape view matrix in order to
-    // billboard the sprite
-    // https://www.geeks3d.com/20140807/billboarding-vertex-shader-glsl/
-    // mv.e[0][0] = transform.e[0][0];
-    // mv.e[0][1] = 0.0;
-    // mv.e[0][2] = 0.0;
+  sg_apply_pipeline(ctx.gfx.quads.pipelines[quad.blend as usize]);
+  sg_apply_bindings(&shape.bindings);
+  sg_apply_uniforms(
+    SgShaderStage::Vertex,
+    0,
+    &uniforms,
+    size_of::<QuadUniforms>() as i32,
+  );
 
-    // mv.e[1][0] = 0.0;
-    // mv.e[1][1] = transform.e[1][1];
-    // mv.e[1][2] = 0.0;
+  let base = first * INDICES_PER_QUAD;
+  sg_draw(base as i32, (count * INDICES_PER_QUAD) as i32, 1);
+  ctx.gfx.draw_calls += 1;
+}
 
-    // mv.e[2][0] = 0.0;
-    // mv.e[2][1] = 0.0;
-    // mv.e[2][2] = transform.e[2][2];
+/// Draws the software cursor set with `set_cursor_sprite`, if any. Runs in
+/// its own pass after every layer so that the cursor ends up on top.
+pub fn present_cursor(ctx: &mut Ctx) {
+  let sprite = match ctx.input.mouse.cursor_sprite {
+    Some(sprite) if cursor_sprite_shown(ctx) => sprite,
+    _ => return,
+  };
 
-    let mvp = ctx.gfx.proj * mv;
+  // window pixels with y down. Sprites are built y up, so flip them around
+  // the pivot to keep them upright.
+  let w = window_width(ctx);
+  let h = window_height(ctx);
+  let proj = Mat4::orthographic_rh_gl(0.0, w, h, 0.0, -1.0, 1.0);
+  let pos = ctx.input.mouse.pos;
+  let model =
+    Mat4::from_scale_rotation_translation(vec3(1.0, -1.0, 1.0), Quat::identity(), pos.extend(0.0));
+  let uniforms = QuadUniforms::new(proj * model, BlendMode::Alpha);
 
-    sg_apply_uniforms(SgShaderStage::Vertex, 0, &mvp, size_of::<Mat4>() as i32);
+  let quads = &mut ctx.gfx.quads;
+  sg_update_buffer(
+    quads.cursor_buffer,
+    &sprite.corners,
+    size_of::<QuadCorners>() as i32,
+  );
 
-    let img_id = quad.img_id;
-    shape.bindings.fs_images[0] = ctx.gfx.images.e[img_id].e;
+  // the index buffer's first quad works for any single quad, so only the
+  // vertex buffer is swapped out for the draw
+  let shape = &mut quads.shape;
+  let vertex_buffer = shape.bindings.vertex_buffers[0];
+  shape.bindings.vertex_buffers[0] = quads.cursor_buffer;
+  shape.bindings.fs_images[0] = ctx.gfx.images.e[sprite.img_id].e;
 
-    sg_apply_bindings(&shape.bindings); // do I need to re-apply this for each draw call?
+  sg_apply_pipeline(shape.pipeline);
+  sg_apply_bindings(&shape.bindings);
+  sg_apply_uniforms(
+    SgShaderStage::Vertex,
+    0,
+    &uniforms,
+    size_of::<QuadUniforms>() as i32,
+  );
+  sg_draw(0, INDICES_PER_QUAD as i32, 1);
+  ctx.gfx.draw_calls += 1;
 
-    // TODO batching would be nice for sequential sprites with the same texture asset
-    sg_draw((i * INDICES_PER_QUAD) as i32, INDICES_PER_QUAD as i32, 1);
-  }
+  shape.bindings.vertex_buffers[0] = vertex_buffer;
 }
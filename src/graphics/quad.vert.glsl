@@ -1,16 +1,23 @@
 #version 410 core
 
 uniform mat4 projection;
+// x picks how the fragment shader treats alpha, see `BlendMode`
+uniform vec4 blend;
 layout(location = 0) in vec3 in_position;
 layout(location = 1) in vec2 in_uv;
+layout(location = 2) in vec4 in_color;
+layout(location = 3) in vec3 in_flash;
 
 out vec2 uv;
-out vec4 debug_color;
+out vec4 color;
+out vec3 flash;
+flat out int alpha_mode;
 
 void
 main() {
   uv = in_uv;
+  color = in_color;
+  flash = in_flash;
+  alpha_mode = int(blend.x);
   gl_Position = projection * (vec4(in_position, 1.0));
-  float z = in_position.z;
-  debug_color = vec4(z, z, z, 1.0);
 }
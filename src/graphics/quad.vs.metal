@@ -28,18 +28,27 @@
 using namespace metal;
 struct params_t {
   float4x4 mvp;
+  float4 blend;
 }; 
 struct vs_in {
     float4 position [[attribute(0)]];
     float2 uv [[attribute(1)]];
+    float4 color [[attribute(2)]];
+    float3 flash [[attribute(3)]];
 };
 struct vs_out {
     float4 position [[position]];
     float2 uv;
+    float4 color;
+    float3 flash;
+    int alpha_mode [[flat]];
 };
 vertex vs_out _main(vs_in in [[stage_in]], constant params_t& params [[buffer(0)]]) {
     vs_out out;
     out.position = params.mvp * in.position;
     out.uv = in.uv;
+    out.color = in.color;
+    out.flash = in.flash;
+    out.alpha_mode = int(params.blend.x);
     return out;
-}
\ No newline at end of file
+}
@@ -0,0 +1,253 @@
+//! Turns glyph outlines into antialiased coverage bitmaps. Curves are split
+//! into lines, and each line adds the area it covers to the pixels it
+//! crosses; summing along each row then gives how much of each pixel is
+//! inside the outline.
+
+use crate::graphics::truetype::PathCmd;
+use crate::math::*;
+
+/// how far flattened curves can stray from the real ones, in pixels
+const TOLERANCE: f32 = 0.2;
+
+/// A glyph drawn as coverage, one byte per pixel, rows from the top
+pub(crate) struct GlyphBitmap {
+  pub(crate) w: u32,
+  pub(crate) h: u32,
+  /// the bitmap's bottom left corner relative to the pen position, in
+  /// pixels with y up
+  pub(crate) min_x: i32,
+  pub(crate) min_y: i32,
+  pub(crate) coverage: Vec<u8>,
+}
+
+/// Draws an outline at `scale` pixels per font unit. `None` if it has
+/// nothing to draw.
+pub(crate) fn rasterize(path: &[PathCmd], scale: f32) -> Option<GlyphBitmap> {
+  let lines = flatten(path, scale);
+  if lines.is_empty() {
+    return None;
+  }
+
+  // a pixel of room on each side keeps antialiased edges from being cut off
+  let (mut lo, mut hi) = (lines[0].0, lines[0].0);
+  for (a, b) in &lines {
+    lo = lo.min(a.min(*b));
+    hi = hi.max(a.max(*b));
+  }
+  let min_x = lo.x().floor() as i32 - 1;
+  let min_y = lo.y().floor() as i32 - 1;
+  let max_x = hi.x().ceil() as i32 + 1;
+  let max_y = hi.y().ceil() as i32 + 1;
+  let w = (max_x - min_x) as usize;
+  let h = (max_y - min_y) as usize;
+
+  let mut acc = Accumulator {
+    w,
+    h,
+    area: vec![0.0; w * h + 2],
+  };
+  // into bitmap space, where y goes down
+  let to_bitmap = |p: Vec2| vec2(p.x() - min_x as f32, max_y as f32 - p.y());
+  for (a, b) in lines {
+    acc.line(to_bitmap(a), to_bitmap(b));
+  }
+
+  let mut coverage = Vec::with_capacity(w * h);
+  let mut sum = 0.0;
+  for (i, area) in acc.area.iter().take(w * h).enumerate() {
+    if i % w == 0 {
+      sum = 0.0;
+    }
+    sum += area;
+    coverage.push((sum.abs().min(1.0) * 255.0).round() as u8);
+  }
+
+  Some(GlyphBitmap {
+    w: w as u32,
+    h: h as u32,
+    min_x,
+    min_y,
+    coverage,
+  })
+}
+
+/// the outline as line segments in pixels
+fn flatten(path: &[PathCmd], scale: f32) -> Vec<(Vec2, Vec2)> {
+  let mut lines = vec![];
+  let mut start = Vec2::zero();
+  let mut pos = Vec2::zero();
+  for cmd in path {
+    match *cmd {
+      PathCmd::MoveTo(p) => {
+        if pos != start {
+          lines.push((pos, start));
+        }
+        start = p * scale;
+        pos = start;
+      }
+      PathCmd::LineTo(p) => {
+        let p = p * scale;
+        lines.push((pos, p));
+        pos = p;
+      }
+      PathCmd::QuadTo(c, p) => {
+        let (c, p) = (c * scale, p * scale);
+        let bend = (pos - c * 2.0 + p).length();
+        let steps = 1 + (bend / (8.0 * TOLERANCE)).sqrt().ceil() as usize;
+        let mut prev = pos;
+        for i in 1..=steps {
+          let t = i as f32 / steps as f32;
+          let u = 1.0 - t;
+          let next = pos * (u * u) + c * (2.0 * u * t) + p * (t * t);
+          lines.push((prev, next));
+          prev = next;
+        }
+        pos = p;
+      }
+      PathCmd::CubicTo(c1, c2, p) => {
+        let (c1, c2, p) = (c1 * scale, c2 * scale, p * scale);
+        let bend = (pos - c1 * 2.0 + c2)
+          .length()
+          .max((c1 - c2 * 2.0 + p).length());
+        let steps = 1 + (3.0 * bend / (4.0 * TOLERANCE)).sqrt().ceil() as usize;
+        let mut prev = pos;
+        for i in 1..=steps {
+          let t = i as f32 / steps as f32;
+          let u = 1.0 - t;
+          let next =
+            pos * (u * u * u) + c1 * (3.0 * u * u * t) + c2 * (3.0 * u * t * t) + p * (t * t * t);
+          lines.push((prev, next));
+          prev = next;
+        }
+        pos = p;
+      }
+      PathCmd::Close => {
+        if pos != start {
+          lines.push((pos, start));
+        }
+        pos = start;
+      }
+    }
+  }
+  if pos != start {
+    lines.push((pos, start));
+  }
+  lines
+}
+
+/// signed area per pixel, which adds up along each row to coverage
+struct Accumulator {
+  w: usize,
+  h: usize,
+  area: Vec<f32>,
+}
+
+impl Accumulator {
+  /// Adds a line's contribution. Lines going down add and lines going up
+  /// subtract, so filled areas come out at 1 whichever way the outline
+  /// winds.
+  fn line(&mut self, p0: Vec2, p1: Vec2) {
+    if (p0.y() - p1.y()).abs() <= f32::EPSILON {
+      return;
+    }
+    let (dir, p0, p1) = if p0.y() < p1.y() {
+      (1.0, p0, p1)
+    } else {
+      (-1.0, p1, p0)
+    };
+    let dxdy = (p1.x() - p0.x()) / (p1.y() - p0.y());
+    let mut x = p0.x();
+    if p0.y() < 0.0 {
+      x -= p0.y() * dxdy;
+    }
+
+    let first_row = p0.y().max(0.0) as usize;
+    let last_row = self.h.min(p1.y().ceil() as usize);
+    for y in first_row..last_row {
+      let row = y * self.w;
+      let dy = ((y + 1) as f32).min(p1.y()) - (y as f32).max(p0.y());
+      let x_next = x + dxdy * dy;
+      let d = dy * dir;
+      let (x0, x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+      let x0_floor = x0.floor();
+      let x0i = x0_floor as usize;
+      let x1_ceil = x1.ceil();
+      let x1i = x1_ceil as usize;
+
+      if x1i <= x0i + 1 {
+        // within one pixel
+        let mid = 0.5 * (x + x_next) - x0_floor;
+        self.area[row + x0i] += d - d * mid;
+        self.area[row + x0i + 1] += d * mid;
+      } else {
+        // across several pixels, split by how much of each it covers
+        let s = (x1 - x0).recip();
+        let x0f = x0 - x0_floor;
+        let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+        let x1f = x1 - x1_ceil + 1.0;
+        let am = 0.5 * s * x1f * x1f;
+        self.area[row + x0i] += d * a0;
+        if x1i == x0i + 2 {
+          self.area[row + x0i + 1] += d * (1.0 - a0 - am);
+        } else {
+          let a1 = s * (1.5 - x0f);
+          self.area[row + x0i + 1] += d * (a1 - a0);
+          for xi in x0i + 2..x1i - 1 {
+            self.area[row + xi] += d * s;
+          }
+          let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+          self.area[row + x1i - 1] += d * (1.0 - a2 - am);
+        }
+        self.area[row + x1i] += d * am;
+      }
+      x = x_next;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fills_a_square() {
+    let square = [
+      PathCmd::MoveTo(vec2(0.0, 0.0)),
+      PathCmd::LineTo(vec2(1.0, 0.0)),
+      PathCmd::LineTo(vec2(1.0, 1.0)),
+      PathCmd::LineTo(vec2(0.0, 1.0)),
+      PathCmd::Close,
+    ];
+    let bitmap = rasterize(&square, 8.0).expect("a square has something to draw");
+
+    // 8 pixels, plus one of padding on each side
+    assert_eq!((bitmap.w, bitmap.h), (10, 10));
+    assert_eq!((bitmap.min_x, bitmap.min_y), (-1, -1));
+    for y in 0..bitmap.h {
+      for x in 0..bitmap.w {
+        let inside = (1..9).contains(&x) && (1..9).contains(&y);
+        let expected = if inside { 255 } else { 0 };
+        let coverage = bitmap.coverage[(y * bitmap.w + x) as usize];
+        assert_eq!(coverage, expected, "pixel {}, {}", x, y);
+      }
+    }
+  }
+
+  #[test]
+  fn fills_either_winding() {
+    let square = [
+      PathCmd::MoveTo(vec2(0.0, 0.0)),
+      PathCmd::LineTo(vec2(0.0, 1.0)),
+      PathCmd::LineTo(vec2(1.0, 1.0)),
+      PathCmd::LineTo(vec2(1.0, 0.0)),
+      PathCmd::Close,
+    ];
+    let bitmap = rasterize(&square, 4.0).unwrap();
+    assert_eq!(bitmap.coverage[(2 * bitmap.w + 2) as usize], 255);
+  }
+
+  #[test]
+  fn has_nothing_to_draw_for_empty_paths() {
+    assert!(rasterize(&[], 8.0).is_none());
+  }
+}
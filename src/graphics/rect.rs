@@ -1,9 +1,14 @@
 use crate::geometry::*;
-use crate::graphics::line::draw_line;
+use crate::graphics::line::draw_line_on;
+use crate::graphics::triangle::{curve_segments, push_triangle};
 use crate::math::*;
-use crate::Ctx;
+use crate::{Ctx, Layer};
 
 pub fn draw_rect(ctx: &mut Ctx, aabb: Rect, color: Vec4) {
+  draw_rect_on(ctx, Layer::World, aabb, color);
+}
+
+pub fn draw_rect_on(ctx: &mut Ctx, layer: Layer, aabb: Rect, color: Vec4) {
   let a = vec2(aabb.max_x, aabb.max_y);
 
   let b = vec2(aabb.max_x, aabb.min_y);
@@ -12,11 +17,92 @@ pub fn draw_rect(ctx: &mut Ctx, aabb: Rect, color: Vec4) {
 
   let d = vec2(aabb.min_x, aabb.max_y);
 
-  draw_line(ctx, a.extend(0.0), b.extend(0.0), color);
+  draw_line_on(ctx, layer, a.extend(0.0), b.extend(0.0), color);
+
+  draw_line_on(ctx, layer, b.extend(0.0), c.extend(0.0), color);
+
+  draw_line_on(ctx, layer, c.extend(0.0), d.extend(0.0), color);
+
+  draw_line_on(ctx, layer, d.extend(0.0), a.extend(0.0), color);
+}
+
+/// Fills a rect with a solid color
+pub fn fill_rect(ctx: &mut Ctx, aabb: Rect, color: Vec4) {
+  fill_rect_on(ctx, Layer::World, 0.0, aabb, color);
+}
 
-  draw_line(ctx, b.extend(0.0), c.extend(0.0), color);
+/// `fill_rect` on a specific render layer and depth
+pub fn fill_rect_on(ctx: &mut Ctx, layer: Layer, z: f32, aabb: Rect, color: Vec4) {
+  fill_rect_gradient_on(ctx, layer, z, aabb, [color; 4]);
+}
+
+/// Fills a rect, blending between a color at each corner, in the order
+/// bottom left, bottom right, top left, top right. E.g. a vertical gradient
+/// for a sky is `[bottom, bottom, top, top]`.
+pub fn fill_rect_gradient(ctx: &mut Ctx, aabb: Rect, colors: [Vec4; 4]) {
+  fill_rect_gradient_on(ctx, Layer::World, 0.0, aabb, colors);
+}
+
+/// `fill_rect_gradient` on a specific render layer and depth
+pub fn fill_rect_gradient_on(ctx: &mut Ctx, layer: Layer, z: f32, aabb: Rect, colors: [Vec4; 4]) {
+  let bl = vec2(aabb.min_x, aabb.min_y);
+  let br = vec2(aabb.max_x, aabb.min_y);
+  let tl = vec2(aabb.min_x, aabb.max_y);
+  let tr = vec2(aabb.max_x, aabb.max_y);
+  let center = (bl + tr) * 0.5;
+  let [c_bl, c_br, c_tl, c_tr] = colors;
+  push_triangle(ctx, layer, z, [bl, br, tr], [c_bl, c_br, c_tr], center);
+  push_triangle(ctx, layer, z, [bl, tr, tl], [c_bl, c_tr, c_tl], center);
+}
+
+/// Fills a rect with its corners rounded off to `radius`. The radius is
+/// limited to half the rect's shorter side, which makes a pill shape.
+pub fn fill_rounded_rect(ctx: &mut Ctx, aabb: Rect, radius: f32, color: Vec4) {
+  fill_rounded_rect_on(ctx, Layer::World, 0.0, aabb, radius, color);
+}
 
-  draw_line(ctx, c.extend(0.0), d.extend(0.0), color);
+/// `fill_rounded_rect` on a specific render layer and depth
+pub fn fill_rounded_rect_on(
+  ctx: &mut Ctx,
+  layer: Layer,
+  z: f32,
+  aabb: Rect,
+  radius: f32,
+  color: Vec4,
+) {
+  let w = aabb.max_x - aabb.min_x;
+  let h = aabb.max_y - aabb.min_y;
+  let r = radius.min(w.abs() * 0.5).min(h.abs() * 0.5);
+  if r <= 0.0 {
+    fill_rect_on(ctx, layer, z, aabb, color);
+    return;
+  }
 
-  draw_line(ctx, d.extend(0.0), a.extend(0.0), color);
+  let center = vec2(aabb.min_x + w * 0.5, aabb.min_y + h * 0.5);
+  // corners' arc centers, counter clockwise from the top right
+  let inset = vec2(w.abs() * 0.5 - r, h.abs() * 0.5 - r);
+  let corners = [
+    center + inset,
+    center + vec2(-inset.x(), inset.y()),
+    center - inset,
+    center + vec2(inset.x(), -inset.y()),
+  ];
+  let segments = curve_segments(ctx, layer, center.extend(z), r, TAU / 4.0);
+  let colors = [color; 3];
+  let mut first = None;
+  let mut prev = None;
+  for (i, corner) in corners.iter().enumerate() {
+    for step in 0..=segments {
+      let angle = (i as f32 + step as f32 / segments as f32) * TAU / 4.0;
+      let next = *corner + vec2(angle.cos(), angle.sin()) * r;
+      match prev {
+        Some(prev) => push_triangle(ctx, layer, z, [center, prev, next], colors, center),
+        None => first = Some(next),
+      }
+      prev = Some(next);
+    }
+  }
+  if let (Some(first), Some(prev)) = (first, prev) {
+    push_triangle(ctx, layer, z, [center, prev, first], colors, center);
+  }
 }
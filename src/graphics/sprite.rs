@@ -10,15 +10,46 @@ use crate::*;
 ///
 /// Use `draw_image` instead if you just want to draw the whole image.
 pub fn draw_sprite(ctx: &mut Ctx, sprite: Sprite, transform: Transform2d) {
+  draw_sprite_on(ctx, Layer::World, sprite, transform);
+}
+
+/// `draw_sprite` on a specific render layer
+pub fn draw_sprite_on(ctx: &mut Ctx, layer: Layer, sprite: Sprite, transform: Transform2d) {
+  draw_sprite_styled_on(ctx, layer, sprite, transform, SpriteStyle::default());
+}
+
+/// `draw_sprite` with a tint, flash color or blend mode, e.g. to fade a
+/// sprite out with `SpriteStyle::with_alpha`
+pub fn draw_sprite_styled(
+  ctx: &mut Ctx,
+  sprite: Sprite,
+  transform: Transform2d,
+  style: SpriteStyle,
+) {
+  draw_sprite_styled_on(ctx, Layer::World, sprite, transform, style);
+}
+
+/// `draw_sprite_styled` on a specific render layer
+pub fn draw_sprite_styled_on(
+  ctx: &mut Ctx,
+  layer: Layer,
+  sprite: Sprite,
+  transform: Transform2d,
+  style: SpriteStyle,
+) {
   let corners = sprite.corners;
   let img_id = sprite.img_id;
   let transform = sprite_transform(transform);
   draw_quad(
     ctx,
+    layer,
     DrawQuad {
       img_id,
       corners,
       transform,
+      color: style.color,
+      flash: style.flash,
+      blend: style.blend,
     },
   );
 }
@@ -45,19 +76,70 @@ fn sprite_transform(transform: Transform2d) -> Mat4 {
 ///
 /// TODO more options for pivots... center, for instance
 pub fn draw_image(ctx: &mut Ctx, img_id: usize, pivot: Pivot, transform: Transform2d) {
+  draw_image_on(ctx, Layer::World, img_id, pivot, transform);
+}
+
+/// `draw_image` on a specific render layer
+pub fn draw_image_on(
+  ctx: &mut Ctx,
+  layer: Layer,
+  img_id: usize,
+  pivot: Pivot,
+  transform: Transform2d,
+) {
+  let style = SpriteStyle::default();
+  draw_image_styled_on(ctx, layer, img_id, pivot, transform, style);
+}
+
+/// `draw_image` with a tint, flash color or blend mode
+pub fn draw_image_styled(
+  ctx: &mut Ctx,
+  img_id: usize,
+  pivot: Pivot,
+  transform: Transform2d,
+  style: SpriteStyle,
+) {
+  draw_image_styled_on(ctx, Layer::World, img_id, pivot, transform, style);
+}
+
+/// `draw_image_styled` on a specific render layer
+pub fn draw_image_styled_on(
+  ctx: &mut Ctx,
+  layer: Layer,
+  img_id: usize,
+  pivot: Pivot,
+  transform: Transform2d,
+  style: SpriteStyle,
+) {
   let w = ctx.gfx.images.e[img_id].w as f32;
   let h = ctx.gfx.images.e[img_id].h as f32;
   let uv = Rect::one();
   let corners = sprite_corners(w, h, uv, pivot);
   let sprite = Sprite { img_id, corners };
-  draw_sprite(ctx, sprite, transform);
+  draw_sprite_styled_on(ctx, layer, sprite, transform, style);
 }
 
 /// Sprite builder. The result can be consumed immediately by `draw_sprite` or stored
 /// to avoid recalculating the sprite's corners every frame.
 pub fn sprite(ctx: &Ctx, img_id: usize, x: u32, y: u32, w: u32, h: u32, pivot: Pivot) -> Sprite {
-  let sheet_w = ctx.gfx.images.e[img_id].w as f32;
-  let sheet_h = ctx.gfx.images.e[img_id].h as f32;
+  let sheet_w = ctx.gfx.images.e[img_id].w;
+  let sheet_h = ctx.gfx.images.e[img_id].h;
+  sprite_in_sheet(img_id, (sheet_w, sheet_h), x, y, w, h, pivot)
+}
+
+/// a sprite covering part of an image of the given size, in pixels from the
+/// top left
+pub(crate) fn sprite_in_sheet(
+  img_id: usize,
+  (sheet_w, sheet_h): (u32, u32),
+  x: u32,
+  y: u32,
+  w: u32,
+  h: u32,
+  pivot: Pivot,
+) -> Sprite {
+  let sheet_w = sheet_w as f32;
+  let sheet_h = sheet_h as f32;
   // TODO maybe just use f32 for everything to avoid the casts?
   let x_max = (x + w) as f32;
   let y_max = (y + h) as f32;
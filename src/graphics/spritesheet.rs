@@ -0,0 +1,605 @@
+//! Loads spritesheets exported from Aseprite or TexturePacker as JSON, so
+//! frame positions, pivots and animations can be edited in those tools
+//! instead of in code.
+//!
+//! Both tools' "hash" and "array" layouts work. The image the JSON points to
+//! is looked up next to the JSON file. To share a texture with other images,
+//! add the sheet to an `AtlasBuilder` instead.
+
+use crate::graphics::sprite::sprite_in_sheet;
+use crate::graphics::*;
+use crate::json::{parse_json, Json};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// how long frames show for when the file doesn't say, in seconds. The same
+/// as Aseprite's default.
+const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+/// Named sprites and animations cut from one texture
+#[derive(Clone, Default)]
+pub struct Spritesheet {
+  /// the texture the sprites are cut from
+  pub texture: Texture,
+  /// every frame, in the order they appear in the file
+  pub frames: Vec<Sprite>,
+  /// every frame by the name it has in the file
+  pub sprites: BTreeMap<String, Sprite>,
+  /// Aseprite tags, or TexturePacker animations
+  pub clips: BTreeMap<String, Clip>,
+}
+
+/// A sequence of frames to play one after another
+#[derive(Clone, Default)]
+pub struct Clip {
+  pub frames: Vec<Sprite>,
+  /// how long each frame shows for, in seconds
+  pub durations: Vec<f32>,
+  /// whether the clip starts over after its last frame
+  pub looping: bool,
+}
+
+impl Spritesheet {
+  /// The frame with the given name. Falls back to the whole texture, so a
+  /// sheet that failed to load still draws its placeholder.
+  pub fn sprite(&self, name: &str) -> Sprite {
+    match self.sprites.get(name) {
+      Some(sprite) => *sprite,
+      None => self.whole_texture(),
+    }
+  }
+
+  /// The animation with the given name. Falls back to a single frame of the
+  /// whole texture, like `sprite`.
+  pub fn clip(&self, name: &str) -> Clip {
+    match self.clips.get(name) {
+      Some(clip) => clip.clone(),
+      None => Clip {
+        frames: vec![self.whole_texture()],
+        durations: vec![DEFAULT_FRAME_DURATION],
+        looping: true,
+      },
+    }
+  }
+
+  fn whole_texture(&self) -> Sprite {
+    let Texture { id, w, h } = self.texture;
+    sprite_in_sheet(id, (w, h), 0, 0, w, h, Pivot::Center)
+  }
+}
+
+impl Clip {
+  /// how long one play through takes, in seconds
+  pub fn duration(&self) -> f32 {
+    self.durations.iter().sum()
+  }
+
+  /// The frame showing `time` seconds after the clip started. Looping clips
+  /// wrap around, others stay on their last frame.
+  pub fn sprite_at(&self, time: f32) -> Sprite {
+    let total = self.duration();
+    let mut time = if self.looping && total > 0.0 {
+      time.rem_euclid(total)
+    } else {
+      time
+    };
+    for (sprite, duration) in self.frames.iter().zip(&self.durations) {
+      if time < *duration {
+        return *sprite;
+      }
+      time -= duration;
+    }
+    self.frames.last().copied().unwrap_or_default()
+  }
+
+  /// whether a clip that doesn't loop has finished `time` seconds after it
+  /// started
+  pub fn is_finished(&self, time: f32) -> bool {
+    !self.looping && time >= self.duration()
+  }
+}
+
+// ----------------------------------------------------------------------------
+// loading
+
+/// Loads an Aseprite JSON export, with its tags as clips. A slice with a
+/// pivot sets the pivot of the frames it has keys for; frames without one
+/// use `pivot`, relative to the untrimmed frame.
+///
+/// If the sheet can't be loaded, the error is printed and an empty sheet
+/// with a placeholder texture is returned instead.
+pub fn load_aseprite(ctx: &mut Ctx, filename: &str, pivot: Pivot) -> Spritesheet {
+  let result = try_load_aseprite(ctx, filename, pivot);
+  or_placeholder(ctx, result)
+}
+
+/// Loads an Aseprite JSON export, like `load_aseprite`, but returns an error
+/// if it can't be read.
+pub fn try_load_aseprite(
+  ctx: &mut Ctx,
+  filename: &str,
+  pivot: Pivot,
+) -> Result<Spritesheet, Error> {
+  let data = read_aseprite(filename, pivot)?;
+  let texture = try_load_img(ctx, &data.image)?;
+  Ok(data.build(texture, (texture.w, texture.h), (0, 0)))
+}
+
+/// Loads a TexturePacker JSON export. Frames without a pivot use `pivot`,
+/// relative to the untrimmed frame. Clips come from an `animations` list of
+/// frame names, as exported for pixi.js.
+///
+/// If the sheet can't be loaded, the error is printed and an empty sheet
+/// with a placeholder texture is returned instead.
+pub fn load_texturepacker(ctx: &mut Ctx, filename: &str, pivot: Pivot) -> Spritesheet {
+  let result = try_load_texturepacker(ctx, filename, pivot);
+  or_placeholder(ctx, result)
+}
+
+/// Loads a TexturePacker JSON export, like `load_texturepacker`, but
+/// returns an error if it can't be read.
+pub fn try_load_texturepacker(
+  ctx: &mut Ctx,
+  filename: &str,
+  pivot: Pivot,
+) -> Result<Spritesheet, Error> {
+  let data = read_texturepacker(filename, pivot)?;
+  let texture = try_load_img(ctx, &data.image)?;
+  Ok(data.build(texture, (texture.w, texture.h), (0, 0)))
+}
+
+fn or_placeholder(ctx: &mut Ctx, result: Result<Spritesheet, Error>) -> Spritesheet {
+  match result {
+    Ok(sheet) => sheet,
+    Err(e) => {
+      println!("{}", e);
+      Spritesheet {
+        texture: placeholder_img(ctx),
+        ..Default::default()
+      }
+    }
+  }
+}
+
+/// a spritesheet's frames, before its image is loaded
+pub(crate) struct SheetData {
+  /// the image's filename, relative to the application root like `load_img`
+  pub image: String,
+  frames: Vec<FrameData>,
+  clips: Vec<(String, ClipData)>,
+}
+
+struct FrameData {
+  name: String,
+  /// where the frame is in the image, in pixels from the top left
+  x: u32,
+  y: u32,
+  w: u32,
+  h: u32,
+  /// turned 90° clockwise in the image, so it takes up `h` by `w` pixels
+  rotated: bool,
+  /// where the trimmed frame sits in the untrimmed one
+  trim_x: u32,
+  trim_y: u32,
+  /// the pivot in the untrimmed frame, in pixels from its top left
+  pivot: (f32, f32),
+  duration: f32,
+}
+
+struct ClipData {
+  frames: Vec<usize>,
+  looping: bool,
+}
+
+impl SheetData {
+  /// Cuts the frames out of a texture. `offset` is where the sheet's image
+  /// starts in the texture, for sheets packed into an atlas.
+  pub(crate) fn build(
+    &self,
+    texture: Texture,
+    size: (u32, u32),
+    offset: (u32, u32),
+  ) -> Spritesheet {
+    let frames: Vec<Sprite> = self
+      .frames
+      .iter()
+      .map(|frame| frame.sprite(texture.id, size, offset))
+      .collect();
+    let sprites = self
+      .frames
+      .iter()
+      .zip(&frames)
+      .map(|(frame, sprite)| (frame.name.clone(), *sprite))
+      .collect();
+    let clips = self
+      .clips
+      .iter()
+      .map(|(name, clip)| {
+        let clip = Clip {
+          frames: clip.frames.iter().map(|i| frames[*i]).collect(),
+          durations: clip
+            .frames
+            .iter()
+            .map(|i| self.frames[*i].duration)
+            .collect(),
+          looping: clip.looping,
+        };
+        (name.clone(), clip)
+      })
+      .collect();
+    Spritesheet {
+      texture,
+      frames,
+      sprites,
+      clips,
+    }
+  }
+}
+
+impl FrameData {
+  fn sprite(&self, img_id: usize, size: (u32, u32), (x, y): (u32, u32)) -> Sprite {
+    let (x, y) = (x + self.x, y + self.y);
+    // the pivot from the trimmed frame's bottom left
+    let pivot = Pivot::Px(
+      self.pivot.0 - self.trim_x as f32,
+      (self.trim_y + self.h) as f32 - self.pivot.1,
+    );
+    let mut sprite = sprite_in_sheet(img_id, size, x, y, self.w, self.h, pivot);
+    if self.rotated {
+      // turn the uvs back: the frame's top edge is the right edge in the image
+      let region = sprite_in_sheet(img_id, size, x, y, self.h, self.w, pivot).corners;
+      sprite.corners[0].uv = region[2].uv;
+      sprite.corners[1].uv = region[0].uv;
+      sprite.corners[2].uv = region[3].uv;
+      sprite.corners[3].uv = region[1].uv;
+    }
+    sprite
+  }
+}
+
+fn read_json(filename: &str) -> Result<Json, Error> {
+  let path = application_root_dir().join(filename);
+  let text = fs::read_to_string(&path).map_err(|source| Error::Io {
+    path: path.clone(),
+    source,
+  })?;
+  parse_json(&text).map_err(|message| Error::Spritesheet { path, message })
+}
+
+pub(crate) fn read_aseprite(filename: &str, pivot: Pivot) -> Result<SheetData, Error> {
+  let json = read_json(filename)?;
+  let error = |message: String| Error::Spritesheet {
+    path: application_root_dir().join(filename),
+    message,
+  };
+  let mut data = read_frames(filename, &json, pivot).map_err(error)?;
+  read_slices(&json, &mut data.frames).map_err(error)?;
+  data.clips = read_tags(&json, data.frames.len()).map_err(error)?;
+  Ok(data)
+}
+
+pub(crate) fn read_texturepacker(filename: &str, pivot: Pivot) -> Result<SheetData, Error> {
+  let json = read_json(filename)?;
+  let error = |message: String| Error::Spritesheet {
+    path: application_root_dir().join(filename),
+    message,
+  };
+  let mut data = read_frames(filename, &json, pivot).map_err(error)?;
+  data.clips = read_animations(&json, &data.frames).map_err(error)?;
+  Ok(data)
+}
+
+/// the frames and image, which both tools write the same way
+fn read_frames(filename: &str, json: &Json, pivot: Pivot) -> Result<SheetData, String> {
+  let image = json
+    .get("meta")
+    .get("image")
+    .as_str()
+    .ok_or("missing meta.image")?;
+  let image = match Path::new(filename).parent() {
+    Some(dir) => dir.join(image).to_string_lossy().into_owned(),
+    None => image.to_string(),
+  };
+
+  // the hash layout is keyed by name, the array layout has a filename field
+  let entries: Vec<(&str, &Json)> = match json.get("frames") {
+    Json::Obj(fields) => fields.iter().map(|(k, v)| (k.as_str(), v)).collect(),
+    Json::Arr(items) => items
+      .iter()
+      .map(|v| (v.get("filename").as_str().unwrap_or_default(), v))
+      .collect(),
+    _ => return Err("missing frames".to_string()),
+  };
+
+  let mut frames = vec![];
+  for (name, entry) in entries {
+    let (x, y, w, h) =
+      read_rect(entry.get("frame")).ok_or_else(|| format!("bad frame for {}", name))?;
+    let (trim_x, trim_y) = match read_rect(entry.get("spriteSourceSize")) {
+      Some((x, y, _, _)) => (x, y),
+      None => (0, 0),
+    };
+    let source = entry.get("sourceSize");
+    let source_w = source.get("w").as_u32().unwrap_or(w) as f32;
+    let source_h = source.get("h").as_u32().unwrap_or(h) as f32;
+
+    // TexturePacker pivots are fractions of the untrimmed frame
+    let frame_pivot = entry.get("pivot");
+    let pivot = match (frame_pivot.get("x").as_f64(), frame_pivot.get("y").as_f64()) {
+      (Some(px), Some(py)) => (px as f32 * source_w, py as f32 * source_h),
+      _ => match pivot {
+        Pivot::Px(px, py) => (px, source_h - py),
+        Pivot::Center => (source_w * 0.5, source_h * 0.5),
+      },
+    };
+
+    let duration = match entry.get("duration").as_f64() {
+      Some(ms) => ms as f32 / 1000.0,
+      None => DEFAULT_FRAME_DURATION,
+    };
+
+    frames.push(FrameData {
+      name: name.to_string(),
+      x,
+      y,
+      w,
+      h,
+      rotated: entry.get("rotated").as_bool().unwrap_or(false),
+      trim_x,
+      trim_y,
+      pivot,
+      duration,
+    });
+  }
+
+  Ok(SheetData {
+    image,
+    frames,
+    clips: vec![],
+  })
+}
+
+/// Aseprite slices with a pivot set the pivot of every frame from their key
+/// onward, until their next key
+fn read_slices(json: &Json, frames: &mut [FrameData]) -> Result<(), String> {
+  let slices = json.get("meta").get("slices").as_arr().unwrap_or_default();
+  for slice in slices {
+    let mut keys = vec![];
+    for key in slice.get("keys").as_arr().unwrap_or_default() {
+      let frame = key
+        .get("frame")
+        .as_u32()
+        .ok_or("slice key without a frame")?;
+      let (x, y, _, _) = read_rect(key.get("bounds")).ok_or("slice key without bounds")?;
+      let pivot = key.get("pivot");
+      let pivot = match (pivot.get("x").as_f64(), pivot.get("y").as_f64()) {
+        (Some(px), Some(py)) => Some((x as f32 + px as f32, y as f32 + py as f32)),
+        _ => None,
+      };
+      keys.push((frame as usize, pivot));
+    }
+    keys.sort_by_key(|(frame, _)| *frame);
+
+    for (i, frame) in frames.iter_mut().enumerate() {
+      let key = keys.iter().rev().find(|(first, _)| *first <= i);
+      if let Some((_, Some(pivot))) = key {
+        frame.pivot = *pivot;
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Aseprite tags, played in their direction and repeated as many times as
+/// they say, or forever
+fn read_tags(json: &Json, frame_count: usize) -> Result<Vec<(String, ClipData)>, String> {
+  let tags = json
+    .get("meta")
+    .get("frameTags")
+    .as_arr()
+    .unwrap_or_default();
+  let mut clips = vec![];
+  for tag in tags {
+    let name = tag.get("name").as_str().ok_or("tag without a name")?;
+    let from = tag.get("from").as_u32().ok_or("tag without a start")? as usize;
+    let to = tag.get("to").as_u32().ok_or("tag without an end")? as usize;
+    if from > to || to >= frame_count {
+      return Err(format!("tag {} is out of range", name));
+    }
+
+    let forward: Vec<usize> = (from..=to).collect();
+    let backward: Vec<usize> = (from..=to).rev().collect();
+    let inner = |frames: &[usize]| frames[1..frames.len().saturating_sub(1).max(1)].to_vec();
+    let frames = match tag.get("direction").as_str().unwrap_or("forward") {
+      "reverse" => backward,
+      "pingpong" => [forward, inner(&backward)].concat(),
+      "pingpong_reverse" => [backward, inner(&forward)].concat(),
+      _ => forward,
+    };
+
+    // newer versions of Aseprite write the repeat count as a string
+    let repeat = match tag.get("repeat") {
+      Json::Str(s) => s.parse().unwrap_or(0),
+      other => other.as_u32().unwrap_or(0) as usize,
+    };
+    let clip = match repeat {
+      0 => ClipData {
+        frames,
+        looping: true,
+      },
+      n => ClipData {
+        frames: frames.repeat(n),
+        looping: false,
+      },
+    };
+    clips.push((name.to_string(), clip));
+  }
+  Ok(clips)
+}
+
+/// TexturePacker animations, as lists of frame names
+fn read_animations(json: &Json, frames: &[FrameData]) -> Result<Vec<(String, ClipData)>, String> {
+  let animations = json.get("animations").as_obj().unwrap_or_default();
+  let mut clips = vec![];
+  for (name, names) in animations {
+    let mut indices = vec![];
+    for frame_name in names.as_arr().unwrap_or_default() {
+      let frame_name = frame_name.as_str().unwrap_or_default();
+      let i = frames
+        .iter()
+        .position(|frame| frame.name == frame_name)
+        .ok_or_else(|| format!("animation {} has unknown frame {}", name, frame_name))?;
+      indices.push(i);
+    }
+    let clip = ClipData {
+      frames: indices,
+      looping: true,
+    };
+    clips.push((name.clone(), clip));
+  }
+  Ok(clips)
+}
+
+/// an `{x, y, w, h}` object
+fn read_rect(json: &Json) -> Option<(u32, u32, u32, u32)> {
+  Some((
+    json.get("x").as_u32()?,
+    json.get("y").as_u32()?,
+    json.get("w").as_u32()?,
+    json.get("h").as_u32()?,
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TEXTURE: Texture = Texture {
+    id: 0,
+    w: 64,
+    h: 64,
+  };
+
+  fn pos(sprite: &Sprite, corner: usize) -> (f32, f32) {
+    let pos = sprite.corners[corner].pos;
+    (pos.x(), pos.y())
+  }
+
+  fn uv(sprite: &Sprite, corner: usize) -> (f32, f32) {
+    let uv = sprite.corners[corner].uv;
+    (uv.x(), uv.y())
+  }
+
+  #[test]
+  fn reads_aseprite_hash_sheets() {
+    let json = parse_json(
+      r#"{
+        "frames": {
+          "hero 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "duration": 100 },
+          "hero 1.aseprite": { "frame": { "x": 8, "y": 0, "w": 8, "h": 8 }, "duration": 250 },
+          "hero 2.aseprite": { "frame": { "x": 16, "y": 0, "w": 8, "h": 8 }, "duration": 100 }
+        },
+        "meta": {
+          "image": "hero.png",
+          "frameTags": [
+            { "name": "walk", "from": 0, "to": 2, "direction": "pingpong" },
+            { "name": "hit", "from": 1, "to": 1, "direction": "forward", "repeat": "2" }
+          ],
+          "slices": [
+            { "name": "feet", "keys": [
+              { "frame": 1, "bounds": { "x": 0, "y": 0, "w": 8, "h": 8 }, "pivot": { "x": 2, "y": 6 } }
+            ] }
+          ]
+        }
+      }"#,
+    )
+    .unwrap();
+    let mut data = read_frames("sheets/hero.json", &json, Pivot::Px(4.0, 0.0)).unwrap();
+    read_slices(&json, &mut data.frames).unwrap();
+    data.clips = read_tags(&json, data.frames.len()).unwrap();
+    assert_eq!(
+      data.image,
+      Path::new("sheets").join("hero.png").to_string_lossy()
+    );
+
+    let sheet = data.build(TEXTURE, (64, 64), (0, 0));
+    assert_eq!(sheet.frames.len(), 3);
+    // the first frame uses the given pivot, the slice's key covers the rest
+    assert_eq!(pos(&sheet.sprite("hero 0.aseprite"), 0), (-4.0, 0.0));
+    assert_eq!(pos(&sheet.sprite("hero 2.aseprite"), 0), (-2.0, -2.0));
+    assert_eq!(uv(&sheet.frames[1], 0), (8.0 / 64.0, 8.0 / 64.0));
+
+    let walk = sheet.clip("walk");
+    assert!(walk.looping);
+    assert_eq!(walk.durations, vec![0.1, 0.25, 0.1, 0.25]);
+    assert_eq!(uv(&walk.sprite_at(0.4), 0), uv(&sheet.frames[2], 0));
+    assert_eq!(uv(&walk.sprite_at(0.5), 0), uv(&sheet.frames[1], 0));
+    let hit = sheet.clip("hit");
+    assert!(!hit.looping);
+    assert_eq!(hit.durations, vec![0.25, 0.25]);
+    assert!(hit.is_finished(0.5));
+  }
+
+  #[test]
+  fn reads_texturepacker_array_sheets() {
+    let json = parse_json(
+      r#"{
+        "frames": [
+          {
+            "filename": "idle",
+            "frame": { "x": 0, "y": 0, "w": 16, "h": 32 },
+            "rotated": false,
+            "trimmed": true,
+            "spriteSourceSize": { "x": 4, "y": 2, "w": 16, "h": 32 },
+            "sourceSize": { "w": 24, "h": 40 },
+            "pivot": { "x": 0.5, "y": 1 }
+          },
+          {
+            "filename": "run",
+            "frame": { "x": 16, "y": 0, "w": 8, "h": 4 },
+            "rotated": true,
+            "trimmed": false,
+            "spriteSourceSize": { "x": 0, "y": 0, "w": 8, "h": 4 },
+            "sourceSize": { "w": 8, "h": 4 }
+          }
+        ],
+        "animations": { "move": ["idle", "run"] },
+        "meta": { "image": "hero.png" }
+      }"#,
+    )
+    .unwrap();
+    let mut data = read_frames("hero.json", &json, Pivot::Center).unwrap();
+    data.clips = read_animations(&json, &data.frames).unwrap();
+    assert_eq!(data.image, "hero.png");
+
+    let sheet = data.build(TEXTURE, (64, 64), (0, 0));
+    // the pivot is at the bottom middle of the untrimmed 24x40 frame, and
+    // trimming left 6 pixels empty below the 16x32 frame
+    let idle = sheet.sprite("idle");
+    assert_eq!(pos(&idle, 0), (-8.0, 6.0));
+    assert_eq!(pos(&idle, 3), (8.0, 38.0));
+    assert_eq!(uv(&idle, 0), (0.0, 0.5));
+
+    // the 8x4 frame is stored as a 4x8 region turned clockwise, so its
+    // bottom left is the region's top left
+    let run = sheet.sprite("run");
+    assert_eq!(pos(&run, 0), (-4.0, -2.0));
+    assert_eq!(pos(&run, 3), (4.0, 2.0));
+    assert_eq!(uv(&run, 0), (16.0 / 64.0, 0.0));
+    assert_eq!(uv(&run, 1), (16.0 / 64.0, 8.0 / 64.0));
+    assert_eq!(uv(&run, 2), (20.0 / 64.0, 0.0));
+    assert_eq!(uv(&run, 3), (20.0 / 64.0, 8.0 / 64.0));
+
+    let clip = sheet.clip("move");
+    assert!(clip.looping);
+    assert_eq!(clip.frames.len(), 2);
+  }
+
+  #[test]
+  fn rejects_tags_past_the_last_frame() {
+    let json =
+      parse_json(r#"{ "meta": { "frameTags": [{ "name": "x", "from": 0, "to": 3 }] } }"#).unwrap();
+    let result = read_tags(&json, 2).err();
+    assert_eq!(result.as_deref(), Some("tag x is out of range"));
+  }
+}
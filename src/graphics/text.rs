@@ -0,0 +1,656 @@
+//! Text drawn with bitmap fonts in AngelCode's BMFont text format, as
+//! exported by BMFont, Hiero and most other bitmap font tools, or with
+//! TrueType and OpenType fonts.
+//!
+//! Text is positioned by the top of its first line. Sizes are the height of
+//! the font's em square in the same units as positions, so on the ui layer
+//! they're in pixels.
+//!
+//! Vector fonts are drawn into the glyph cache at the size text ends up on
+//! screen, counting the layer's camera and the display's pixel density, so
+//! they stay sharp at any size or zoom.
+
+use crate::graphics::glyph_cache::{cached_glyph, MAX_GLYPH_PX};
+use crate::graphics::sprite::sprite_in_sheet;
+use crate::graphics::*;
+use std::fs;
+use std::iter;
+
+/// Loads a font. `.ttf`, `.otf` and `.ttc` files are read as TrueType or
+/// OpenType fonts, and anything else as a BMFont `.fnt` file in the text
+/// format, along with the images for its pages, which are looked up next
+/// to it. Returns an id for `draw_text`.
+///
+/// If the font can't be loaded, the error is printed and the returned font
+/// draws nothing, the same way `load_img` falls back to a placeholder. Use
+/// `try_load_font` to handle failures yourself.
+pub fn load_font(ctx: &mut Ctx, filename: &str) -> usize {
+  match try_load_font(ctx, filename) {
+    Ok(font) => font,
+    Err(e) => {
+      println!("{}", e);
+      add_font(
+        ctx,
+        Font {
+          size: 1.0,
+          ..Default::default()
+        },
+      )
+    }
+  }
+}
+
+/// Loads a font, like `load_font`, but returns an error if it or one of its
+/// pages can't be read.
+pub fn try_load_font(ctx: &mut Ctx, filename: &str) -> Result<usize, Error> {
+  let path = application_root_dir().join(filename);
+  let extension = path.extension().and_then(|e| e.to_str());
+  match extension.map(|e| e.to_ascii_lowercase()).as_deref() {
+    Some("ttf") | Some("otf") | Some("ttc") => try_load_ttf(ctx, path),
+    _ => try_load_fnt(ctx, filename, path),
+  }
+}
+
+/// Sets the fonts to draw characters `font` doesn't have with, tried in
+/// order, e.g. a font with CJK characters or emoji behind a latin one.
+/// Only `font`'s own fallbacks are used, not theirs.
+pub fn set_font_fallbacks(ctx: &mut Ctx, font: usize, fallbacks: &[usize]) {
+  ctx.gfx.fonts.e[font].fallbacks = fallbacks.to_vec();
+}
+
+fn try_load_ttf(ctx: &mut Ctx, path: PathBuf) -> Result<usize, Error> {
+  let data = fs::read(&path).map_err(|source| Error::Io {
+    path: path.clone(),
+    source,
+  })?;
+  let ttf = TtfFont::parse(data).map_err(|message| Error::Font { path, message })?;
+  let font = Font {
+    size: ttf.units_per_em,
+    line_height: ttf.ascender - ttf.descender + ttf.line_gap,
+    base: ttf.ascender,
+    kind: FontKind::Vector(ttf),
+    ..Default::default()
+  };
+  Ok(add_font(ctx, font))
+}
+
+fn try_load_fnt(ctx: &mut Ctx, filename: &str, path: PathBuf) -> Result<usize, Error> {
+  let text = fs::read_to_string(&path).map_err(|source| Error::Io {
+    path: path.clone(),
+    source,
+  })?;
+  let fnt = parse_fnt(&text).map_err(|message| Error::Font { path, message })?;
+
+  let dir = Path::new(filename)
+    .parent()
+    .unwrap_or_else(|| Path::new(""));
+  let mut pages = vec![];
+  for file in &fnt.pages {
+    let page = dir.join(file).to_string_lossy().into_owned();
+    pages.push(try_load_img(ctx, &page)?);
+  }
+
+  let mut glyphs = HashMap::new();
+  for c in &fnt.chars {
+    let sprite = match pages.get(c.page) {
+      Some(page) if c.w > 0 && c.h > 0 => {
+        // from the top of the glyph's box to the pen on the baseline
+        let pivot = Pivot::Px(-c.x_offset, c.y_offset + c.h as f32 - fnt.base);
+        let size = (page.w, page.h);
+        Some(sprite_in_sheet(page.id, size, c.x, c.y, c.w, c.h, pivot))
+      }
+      _ => None,
+    };
+    let glyph = Glyph {
+      sprite,
+      advance: c.advance,
+    };
+    glyphs.insert(c.id, glyph);
+  }
+  let font = Font {
+    size: fnt.size,
+    line_height: fnt.line_height,
+    base: fnt.base,
+    kind: FontKind::Bitmap {
+      glyphs,
+      kerning: fnt.kerning.into_iter().collect(),
+    },
+    ..Default::default()
+  };
+  Ok(add_font(ctx, font))
+}
+
+fn add_font(ctx: &mut Ctx, font: Font) -> usize {
+  ctx.gfx.fonts.e.push(font);
+  ctx.gfx.fonts.e.len() - 1
+}
+
+// ----------------------------------------------------------------------------
+// drawing
+
+/// Draws white, left aligned text with its top left at `pos`. Lines break at
+/// `\n`.
+pub fn draw_text(ctx: &mut Ctx, text: &str, font: usize, size: f32, pos: Vec2) {
+  draw_text_on(ctx, Layer::World, text, font, size, pos);
+}
+
+/// `draw_text` on a specific render layer
+pub fn draw_text_on(ctx: &mut Ctx, layer: Layer, text: &str, font: usize, size: f32, pos: Vec2) {
+  let style = TextStyle::default();
+  draw_text_styled_on(ctx, layer, text, font, size, pos, style);
+}
+
+/// `draw_text` with a color, alignment or wrapping. With `TextAlign::Center`
+/// or `Right`, `pos` is the top center or top right of the text.
+pub fn draw_text_styled(
+  ctx: &mut Ctx,
+  text: &str,
+  font: usize,
+  size: f32,
+  pos: Vec2,
+  style: TextStyle,
+) {
+  draw_text_styled_on(ctx, Layer::World, text, font, size, pos, style);
+}
+
+/// `draw_text_styled` on a specific render layer
+pub fn draw_text_styled_on(
+  ctx: &mut Ctx,
+  layer: Layer,
+  text: &str,
+  font: usize,
+  size: f32,
+  pos: Vec2,
+  style: TextStyle,
+) {
+  let glyphs = place_glyphs(&ctx.gfx.fonts.e, font, text, size, pos, style);
+  let sprite_style = SpriteStyle {
+    color: style.color,
+    ..Default::default()
+  };
+  // vector glyphs are drawn into the cache at the size they'll be on screen
+  let px = (size * layer_pixels_per_unit(ctx, layer, pos.extend(0.0))).round();
+  let px = px.max(1.0).min(MAX_GLYPH_PX as f32) as u16;
+
+  for (font, id, pos) in glyphs {
+    let (sprite, scale) = match id {
+      GlyphId::Bitmap(c) => match &ctx.gfx.fonts.e[font].kind {
+        FontKind::Bitmap { glyphs, .. } => (glyphs[&c].sprite, size / ctx.gfx.fonts.e[font].size),
+        FontKind::Vector(_) => continue,
+      },
+      GlyphId::Vector(glyph) => (cached_glyph(ctx, font, glyph, px), size / px as f32),
+    };
+    if let Some(sprite) = sprite {
+      let transform = Transform2d {
+        pos,
+        rotation: 0.0,
+        scale: Vec2::splat(scale),
+      };
+      draw_sprite_styled_on(ctx, layer, sprite, transform, sprite_style);
+    }
+  }
+}
+
+/// The width and height text takes up when drawn with the same font, size
+/// and style
+pub fn measure_text(ctx: &Ctx, text: &str, font: usize, size: f32, style: TextStyle) -> Vec2 {
+  let fonts = &ctx.gfx.fonts.e;
+  let lines = layout(fonts, font, text, wrap_width(size, style));
+  let width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+  let line_height = fonts[font].line_height / fonts[font].size;
+  vec2(width, lines.len() as f32 * line_height) * size
+}
+
+/// each glyph, the font it's from and where its pen position ends up
+fn place_glyphs(
+  fonts: &[Font],
+  font: usize,
+  text: &str,
+  size: f32,
+  pos: Vec2,
+  style: TextStyle,
+) -> Vec<(usize, GlyphId, Vec2)> {
+  let base = fonts[font].base / fonts[font].size;
+  let line_height = fonts[font].line_height / fonts[font].size;
+  let mut placed = vec![];
+  for (i, line) in layout(fonts, font, text, wrap_width(size, style))
+    .iter()
+    .enumerate()
+  {
+    let x = match style.align {
+      TextAlign::Left => 0.0,
+      TextAlign::Center => -line.width * 0.5,
+      TextAlign::Right => -line.width,
+    };
+    let baseline = -base - i as f32 * line_height;
+    for glyph in &line.glyphs {
+      let offset = vec2(x + glyph.x, baseline) * size;
+      placed.push((glyph.font, glyph.id, pos + offset));
+    }
+  }
+  placed
+}
+
+/// the wrap width in ems
+fn wrap_width(size: f32, style: TextStyle) -> Option<f32> {
+  style.wrap_width.map(|w| w / size)
+}
+
+// ----------------------------------------------------------------------------
+// layout
+
+/// a glyph in one font, looked up by character in bitmap fonts and by index
+/// in vector fonts
+#[derive(Copy, Clone)]
+enum GlyphId {
+  Bitmap(char),
+  Vector(u16),
+}
+
+impl Font {
+  fn glyph_id(&self, c: char) -> Option<GlyphId> {
+    match &self.kind {
+      FontKind::Bitmap { glyphs, .. } if glyphs.contains_key(&c) => Some(GlyphId::Bitmap(c)),
+      FontKind::Bitmap { .. } => None,
+      FontKind::Vector(ttf) => ttf.glyph_index(c).map(GlyphId::Vector),
+    }
+  }
+
+  /// how far the pen moves after a glyph, in ems
+  fn advance(&self, id: GlyphId) -> f32 {
+    let advance = match (&self.kind, id) {
+      (FontKind::Bitmap { glyphs, .. }, GlyphId::Bitmap(c)) => glyphs[&c].advance,
+      (FontKind::Vector(ttf), GlyphId::Vector(glyph)) => ttf.advance(glyph),
+      _ => 0.0,
+    };
+    advance / self.size
+  }
+
+  /// extra space between two glyphs, in ems
+  fn kerning(&self, left: GlyphId, right: GlyphId) -> f32 {
+    let kerning = match (&self.kind, left, right) {
+      (FontKind::Bitmap { kerning, .. }, GlyphId::Bitmap(l), GlyphId::Bitmap(r)) => {
+        kerning.get(&(l, r)).copied().unwrap_or(0.0)
+      }
+      (FontKind::Vector(ttf), GlyphId::Vector(l), GlyphId::Vector(r)) => ttf.kerning(l, r),
+      _ => 0.0,
+    };
+    kerning / self.size
+  }
+}
+
+/// The first font in `font`'s fallback chain with a glyph for `c`, or for
+/// `?` if none of them have one
+fn find_glyph(fonts: &[Font], font: usize, c: char) -> Option<(usize, GlyphId)> {
+  let chain = iter::once(font).chain(fonts[font].fallbacks.iter().copied());
+  for c in &[c, '?'] {
+    for font in chain.clone() {
+      if let Some(id) = fonts[font].glyph_id(*c) {
+        return Some((font, id));
+      }
+    }
+  }
+  None
+}
+
+#[derive(Copy, Clone)]
+struct LaidGlyph {
+  c: char,
+  font: usize,
+  id: GlyphId,
+  /// where its pen position is along the line, in ems
+  x: f32,
+  advance: f32,
+}
+
+#[derive(Default)]
+struct Line {
+  glyphs: Vec<LaidGlyph>,
+  /// up to the end of the last character that isn't a space, in ems
+  width: f32,
+}
+
+impl Line {
+  fn finish(mut self) -> Self {
+    self.width = self
+      .glyphs
+      .iter()
+      .rev()
+      .find(|glyph| glyph.c != ' ')
+      .map_or(0.0, |glyph| glyph.x + glyph.advance);
+    self
+  }
+}
+
+/// Breaks text into lines, at `\n` and before words that would go past
+/// `wrap_width`. Words too long for a line of their own are broken between
+/// characters. Characters missing from the font and its fallbacks show as
+/// `?` if one of them has it.
+fn layout(fonts: &[Font], font: usize, text: &str, wrap_width: Option<f32>) -> Vec<Line> {
+  let mut lines = vec![];
+  for paragraph in text.split('\n') {
+    let paragraph = paragraph.trim_end_matches('\r');
+    let mut line = Line::default();
+    let mut pen = 0.0;
+    let mut prev: Option<(usize, GlyphId)> = None;
+    // the index of the space after the last word that fits
+    let mut last_space = None;
+
+    for c in paragraph.chars() {
+      let (glyph_font, id) = match find_glyph(fonts, font, c) {
+        Some(found) => found,
+        None => continue,
+      };
+      // kerning only applies between glyphs from the same font
+      let kerning = match prev {
+        Some((prev_font, prev_id)) if prev_font == glyph_font => {
+          fonts[glyph_font].kerning(prev_id, id)
+        }
+        _ => 0.0,
+      };
+      let mut x = pen + kerning;
+      let advance = fonts[glyph_font].advance(id);
+
+      let too_wide = match wrap_width {
+        Some(w) => x + advance > w,
+        None => false,
+      };
+      if c == ' ' {
+        last_space = Some(line.glyphs.len());
+      } else if too_wide {
+        if let Some(space) = last_space.take() {
+          // move the word that doesn't fit onto the next line
+          let mut rest = line.glyphs.split_off(space);
+          rest.remove(0);
+          let shift = rest.first().map_or(x, |glyph| glyph.x);
+          for glyph in &mut rest {
+            glyph.x -= shift;
+          }
+          lines.push(line.finish());
+          line = Line {
+            glyphs: rest,
+            width: 0.0,
+          };
+          x -= shift;
+        } else if !line.glyphs.is_empty() {
+          lines.push(line.finish());
+          line = Line::default();
+          x = 0.0;
+        }
+      }
+
+      line.glyphs.push(LaidGlyph {
+        c,
+        font: glyph_font,
+        id,
+        x,
+        advance,
+      });
+      pen = x + advance;
+      prev = Some((glyph_font, id));
+    }
+    lines.push(line.finish());
+  }
+  lines
+}
+
+// ----------------------------------------------------------------------------
+// BMFont files
+
+#[derive(Default)]
+struct Fnt {
+  size: f32,
+  line_height: f32,
+  base: f32,
+  /// image filenames, relative to the .fnt file
+  pages: Vec<String>,
+  chars: Vec<FntChar>,
+  kerning: Vec<((char, char), f32)>,
+}
+
+struct FntChar {
+  id: char,
+  /// where the glyph is on its page, in pixels from the top left
+  x: u32,
+  y: u32,
+  w: u32,
+  h: u32,
+  /// from the pen position to the top left of the glyph, with y down
+  x_offset: f32,
+  y_offset: f32,
+  advance: f32,
+  page: usize,
+}
+
+/// Reads the text format, where each line is a tag followed by
+/// `key=value` pairs. Values with spaces are quoted.
+fn parse_fnt(text: &str) -> Result<Fnt, String> {
+  let mut fnt = Fnt::default();
+  for (i, line) in text.lines().enumerate() {
+    let error = |message: &str| format!("{} on line {}", message, i + 1);
+    let mut words = split_fnt_line(line).into_iter();
+    let tag = match words.next() {
+      Some(tag) => tag,
+      None => continue,
+    };
+    let attrs: Vec<(String, String)> = words
+      .filter_map(|word| {
+        let mut parts = word.splitn(2, '=');
+        Some((parts.next()?.to_string(), parts.next()?.to_string()))
+      })
+      .collect();
+    let get = |key: &str| {
+      attrs
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+        .ok_or_else(|| error(&format!("{} is missing {}", tag, key)))
+    };
+    let num = |key: &str| -> Result<f32, String> {
+      get(key)?
+        .parse()
+        .map_err(|_| error(&format!("{} isn't a number", key)))
+    };
+
+    match tag.as_str() {
+      // negative sizes mean the size was matched to character height
+      "info" => fnt.size = num("size")?.abs(),
+      "common" => {
+        fnt.line_height = num("lineHeight")?;
+        fnt.base = num("base")?;
+      }
+      "page" => {
+        let id = num("id")? as usize;
+        if fnt.pages.len() <= id {
+          fnt.pages.resize(id + 1, String::new());
+        }
+        fnt.pages[id] = get("file")?.to_string();
+      }
+      "char" => {
+        // some tools write -1 for the glyph drawn for missing characters
+        let id = num("id")?;
+        let id = match std::char::from_u32(id as u32) {
+          Some(c) if id >= 0.0 => c,
+          _ => continue,
+        };
+        fnt.chars.push(FntChar {
+          id,
+          x: num("x")? as u32,
+          y: num("y")? as u32,
+          w: num("width")? as u32,
+          h: num("height")? as u32,
+          x_offset: num("xoffset")?,
+          y_offset: num("yoffset")?,
+          advance: num("xadvance")?,
+          page: num("page")? as usize,
+        });
+      }
+      "kerning" => {
+        let first = std::char::from_u32(num("first")? as u32);
+        let second = std::char::from_u32(num("second")? as u32);
+        if let (Some(first), Some(second)) = (first, second) {
+          fnt.kerning.push(((first, second), num("amount")?));
+        }
+      }
+      _ => {}
+    }
+  }
+
+  if fnt.size == 0.0 || fnt.line_height == 0.0 {
+    return Err("missing info or common line, is this a text .fnt file?".to_string());
+  }
+  Ok(fnt)
+}
+
+/// splits on spaces that aren't inside quotes, and drops the quotes
+fn split_fnt_line(line: &str) -> Vec<String> {
+  let mut words = vec![];
+  let mut word = String::new();
+  let mut quoted = false;
+  for c in line.chars() {
+    match c {
+      '"' => quoted = !quoted,
+      ' ' | '\t' if !quoted => {
+        if !word.is_empty() {
+          words.push(std::mem::take(&mut word));
+        }
+      }
+      c => word.push(c),
+    }
+  }
+  if !word.is_empty() {
+    words.push(word);
+  }
+  words
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// a bitmap font with every lowercase letter, space and `?`, each 1em wide
+  fn fonts() -> Vec<Font> {
+    let glyphs = ('a'..='z')
+      .chain(" ?".chars())
+      .map(|c| {
+        let glyph = Glyph {
+          sprite: None,
+          advance: 10.0,
+        };
+        (c, glyph)
+      })
+      .collect();
+    let mut kerning = HashMap::new();
+    kerning.insert(('a', 'v'), -5.0);
+    let font = Font {
+      size: 10.0,
+      line_height: 12.0,
+      base: 8.0,
+      fallbacks: vec![],
+      kind: FontKind::Bitmap { glyphs, kerning },
+    };
+    vec![font]
+  }
+
+  fn text(line: &Line) -> String {
+    line.glyphs.iter().map(|glyph| glyph.c).collect()
+  }
+
+  fn lines(s: &str, wrap_width: Option<f32>) -> Vec<(String, f32)> {
+    layout(&fonts(), 0, s, wrap_width)
+      .iter()
+      .map(|line| (text(line), line.width))
+      .collect()
+  }
+
+  #[test]
+  fn wraps_back_to_the_last_space() {
+    assert_eq!(
+      lines("ab cd ef", Some(5.5)),
+      vec![("ab cd".to_string(), 5.0), ("ef".to_string(), 2.0)]
+    );
+    let laid = layout(&fonts(), 0, "ab cd", Some(4.5));
+    assert_eq!(text(&laid[1]), "cd");
+    // the moved word starts at the beginning of its new line
+    assert_eq!(laid[1].glyphs[0].x, 0.0);
+    assert_eq!(laid[1].glyphs[1].x, 1.0);
+  }
+
+  #[test]
+  fn breaks_words_too_long_for_a_line() {
+    assert_eq!(
+      lines("abcdef", Some(2.5)),
+      vec![
+        ("ab".to_string(), 2.0),
+        ("cd".to_string(), 2.0),
+        ("ef".to_string(), 2.0)
+      ]
+    );
+  }
+
+  #[test]
+  fn leaves_trailing_spaces_out_of_the_width() {
+    assert_eq!(lines("ab  ", None), vec![("ab  ".to_string(), 2.0)]);
+    assert_eq!(lines("   ", None), vec![("   ".to_string(), 0.0)]);
+  }
+
+  #[test]
+  fn breaks_lines_and_kerns() {
+    assert_eq!(
+      lines("av\r\nb", None),
+      vec![("av".to_string(), 1.5), ("b".to_string(), 1.0)]
+    );
+    // missing characters show as `?`
+    let laid = layout(&fonts(), 0, "aΩ", None);
+    assert!(matches!(laid[0].glyphs[1].id, GlyphId::Bitmap('?')));
+    assert_eq!(laid[0].width, 2.0);
+  }
+
+  const FNT: &str = "info face=\"Pixel Font\" size=-16 bold=0
+common lineHeight=18 base=14 scaleW=64 scaleH=64 pages=1
+page id=0 file=\"pixel font.png\"
+chars count=2
+char id=65   x=0 y=2 width=8 height=12 xoffset=1 yoffset=2 xadvance=9 page=0 chnl=15
+char id=-1   x=8 y=0 width=8 height=12 xoffset=0 yoffset=2 xadvance=9 page=0 chnl=15
+kernings count=1
+kerning first=65 second=86 amount=-1
+";
+
+  #[test]
+  fn parses_fnt_files() {
+    let fnt = parse_fnt(FNT).unwrap();
+    assert_eq!(fnt.size, 16.0);
+    assert_eq!(fnt.line_height, 18.0);
+    assert_eq!(fnt.base, 14.0);
+    assert_eq!(fnt.pages, vec!["pixel font.png".to_string()]);
+    // the missing character glyph has no character to go with
+    assert_eq!(fnt.chars.len(), 1);
+    let a = &fnt.chars[0];
+    assert_eq!(a.id, 'A');
+    assert_eq!((a.x, a.y, a.w, a.h), (0, 2, 8, 12));
+    assert_eq!((a.x_offset, a.y_offset, a.advance), (1.0, 2.0, 9.0));
+    assert_eq!(fnt.kerning, vec![(('A', 'V'), -1.0)]);
+  }
+
+  #[test]
+  fn says_what_is_wrong_with_fnt_files() {
+    let bad_number = "info size=16\ncommon lineHeight=18 base=14\nchar id=66 x=left";
+    assert_eq!(
+      parse_fnt(bad_number).err().as_deref(),
+      Some("x isn't a number on line 3")
+    );
+    assert_eq!(
+      parse_fnt("info size=16\ncommon base=14").err().as_deref(),
+      Some("common is missing lineHeight on line 2")
+    );
+    assert!(parse_fnt("<?xml version=\"1.0\"?>").is_err());
+  }
+
+  #[test]
+  fn splits_fnt_lines_outside_quotes() {
+    assert_eq!(
+      split_fnt_line("info face=\"Pixel  Font\"\tsize=16  file=\"\""),
+      vec!["info", "face=Pixel  Font", "size=16", "file="]
+    );
+  }
+}
@@ -0,0 +1,226 @@
+//! The primitive filled shapes are drawn with: triangles with a color at
+//! each corner, blended across the middle. `fill_circle`, `fill_rect`,
+//! `fill_polygon` and the rest break their shapes into these. They lie flat
+//! at z = 0 like sprites, or at a given depth with the `_on` versions, so
+//! that they sort against quads, lines and meshes.
+
+use crate::graphics::draw_list::push_cmd;
+use crate::graphics::layer::layer_pixels_per_unit;
+use crate::graphics::{fit_stream_buffer, make_stream_buffer, std_uniform_block};
+use crate::math::*;
+use crate::*;
+use std::mem::size_of;
+
+/// How far the straight edges of filled curves can stray from the real
+/// curve, in pixels
+const CURVE_TOLERANCE: f32 = 0.25;
+const MIN_CURVE_SEGMENTS: usize = 8;
+const MAX_CURVE_SEGMENTS: usize = 256;
+
+/// Fills a triangle, blending between a color at each corner
+pub fn fill_triangle(ctx: &mut Ctx, points: [Vec2; 3], colors: [Vec4; 3]) {
+  fill_triangle_on(ctx, Layer::World, 0.0, points, colors);
+}
+
+/// `fill_triangle` on a specific render layer and depth
+pub fn fill_triangle_on(ctx: &mut Ctx, layer: Layer, z: f32, points: [Vec2; 3], colors: [Vec4; 3]) {
+  let center = (points[0] + points[1] + points[2]) / 3.0;
+  push_triangle(ctx, layer, z, points, colors, center);
+}
+
+/// Adds one of a shape's triangles, flat at depth `z`. Every triangle in a
+/// shape sorts by the shape's `center`, so they're drawn together.
+pub(crate) fn push_triangle(
+  ctx: &mut Ctx,
+  layer: Layer,
+  z: f32,
+  points: [Vec2; 3],
+  colors: [Vec4; 3],
+  center: Vec2,
+) {
+  let triangle = DrawTriangle {
+    point_a: points[0].extend(z).extend(1.0),
+    color_a: colors[0],
+    point_b: points[1].extend(z).extend(1.0),
+    color_b: colors[1],
+    point_c: points[2].extend(z).extend(1.0),
+    color_c: colors[2],
+  };
+  let index = ctx.gfx.triangles.e.len();
+  if ctx.gfx.triangles.e.push(triangle, "triangle") {
+    push_cmd(ctx, layer, PrimitiveKind::Triangle, index, center.extend(z));
+  }
+}
+
+/// How many straight edges a `sweep` radians arc of radius `r` needs to
+/// look smooth at the size it's drawn on `layer`
+pub(crate) fn curve_segments(ctx: &Ctx, layer: Layer, center: Vec3, r: f32, sweep: f32) -> usize {
+  let r_px = r.abs() * layer_pixels_per_unit(ctx, layer, center);
+  if r_px <= CURVE_TOLERANCE {
+    return MIN_CURVE_SEGMENTS;
+  }
+  // the angle each edge can cover before its middle strays too far
+  let step = 2.0 * (1.0 - CURVE_TOLERANCE / r_px).acos();
+  let segments = (sweep.abs() / step).ceil() as usize;
+  segments.clamp(MIN_CURVE_SEGMENTS, MAX_CURVE_SEGMENTS)
+}
+
+// ----------------------------------------------------------------------------
+// pipeline
+
+pub fn init(ctx: &mut Ctx) -> Result<(), Error> {
+  // triangles have the same vertices as lines, so they share shaders
+  let (vs_src, fs_src) = match sg_api() {
+    SgApi::OpenGL33 => (
+      include_str!("line.vert.glsl"),
+      include_str!("line.frag.glsl"),
+    ),
+    SgApi::Metal => (include_str!("line.vs.metal"), include_str!("line.fs.metal")),
+    _ => {
+      return Err(Error::UnsupportedBackend {
+        primitive: "triangle",
+      })
+    }
+  };
+
+  let pipeline = sg_make_pipeline(&SgPipelineDesc {
+    primitive_type: SgPrimitiveType::Triangles,
+    shader: sg_make_shader(&SgShaderDesc {
+      vs: SgShaderStageDesc {
+        source: Some(vs_src),
+        uniform_blocks: vec![std_uniform_block()],
+        ..Default::default()
+      },
+      fs: SgShaderStageDesc {
+        source: Some(fs_src),
+        ..Default::default()
+      },
+      attrs: vec![],
+    }),
+    layout: SgLayoutDesc {
+      attrs: vec![
+        SgVertexAttrDesc {
+          // name : "in_position",
+          format: SgVertexFormat::Float4,
+          ..Default::default()
+        },
+        SgVertexAttrDesc {
+          // name : "in_color",
+          format: SgVertexFormat::Float4,
+          ..Default::default()
+        },
+      ],
+      ..Default::default()
+    },
+    depth_stencil: SgDepthStencilState {
+      depth_compare_func: SgCompareFunc::LessEqual,
+      ..Default::default()
+    },
+    blend: SgBlendState {
+      enabled: true,
+      dst_factor_rgb: SgBlendFactor::OneMinusSrcAlpha,
+      src_factor_rgb: SgBlendFactor::SrcAlpha,
+      ..Default::default()
+    },
+    // shapes can be wound either way, so nothing is culled
+    ..Default::default()
+  });
+
+  let capacity = ctx.gfx.triangles.e.capacity;
+  let bindings = SgBindings {
+    vertex_buffers: vec![make_stream_buffer::<DrawTriangle>(capacity)],
+    ..Default::default()
+  };
+  ctx.gfx.triangles.gpu_capacity = capacity;
+
+  ctx.gfx.triangles.shape = GlShape { bindings, pipeline };
+
+  Ok(())
+}
+
+/// copies this frame's triangles to the gpu, ready for `draw`
+pub fn upload(ctx: &mut Ctx) {
+  let triangles = &mut ctx.gfx.triangles;
+  let count = triangles.e.len();
+  if count == 0 {
+    return;
+  }
+
+  fit_stream_buffer::<DrawTriangle>(
+    &mut triangles.shape.bindings.vertex_buffers[0],
+    &mut triangles.gpu_capacity,
+    triangles.e.capacity,
+  );
+
+  sg_update_buffer(
+    triangles.shape.bindings.vertex_buffers[0],
+    &triangles.e.e[0],
+    (count * size_of::<DrawTriangle>()) as i32,
+  );
+}
+
+pub fn apply(ctx: &mut Ctx, view_proj: Mat4) {
+  sg_apply_pipeline(ctx.gfx.triangles.shape.pipeline);
+  sg_apply_bindings(&ctx.gfx.triangles.shape.bindings);
+  sg_apply_uniforms(
+    SgShaderStage::Vertex,
+    0,
+    &view_proj,
+    size_of::<Mat4>() as i32,
+  );
+}
+
+/// draws `count` triangles starting at `first` in this frame's draw buffer
+pub fn draw(ctx: &mut Ctx, first: usize, count: usize) {
+  sg_draw((first * 3) as i32, (count * 3) as i32, 1);
+  ctx.gfx.draw_calls += 1;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fills_at_the_given_depth() {
+    let mut ctx: Ctx = Default::default();
+    ctx.gfx.headless = true;
+    let white = Vec4::one();
+    let rect = Rect::centered(10.0, 10.0);
+    fill_rect_on(&mut ctx, Layer::World, 5.0, rect, white);
+    fill_circle_on(
+      &mut ctx,
+      Layer::World,
+      -2.0,
+      Circle {
+        center: Vec2::zero(),
+        r: 4.0,
+      },
+      white,
+    );
+    fill_polygon(
+      &mut ctx,
+      &[vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(0.0, 1.0)],
+      white,
+    );
+
+    let zs: Vec<f32> = ctx
+      .gfx
+      .triangles
+      .e
+      .e
+      .iter()
+      .map(|t| t.point_a.z())
+      .collect();
+    assert_eq!(zs[..2], [5.0, 5.0]);
+    assert!(zs[2..zs.len() - 1].iter().all(|z| *z == -2.0));
+    assert_eq!(zs.last(), Some(&0.0));
+    for t in &ctx.gfx.triangles.e.e {
+      assert_eq!(t.point_a.z(), t.point_b.z());
+      assert_eq!(t.point_a.z(), t.point_c.z());
+    }
+    // and sort at that depth
+    let cmds = &ctx.gfx.draw_list.e;
+    assert_eq!(cmds[0].pos.z(), 5.0);
+    assert_eq!(cmds[2].pos.z(), -2.0);
+  }
+}
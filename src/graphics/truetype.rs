@@ -0,0 +1,716 @@
+//! Reads the parts of TrueType and OpenType fonts needed to lay out and
+//! draw text: character mapping, metrics, kerning and glyph outlines.
+//!
+//! Outlines come from the `glyf` table for TrueType fonts, or the `CFF `
+//! table for OpenType fonts with PostScript outlines, see `cff`.
+
+use crate::graphics::cff::Cff;
+use crate::math::*;
+use std::collections::HashMap;
+
+/// How deep composite glyphs can nest before they're assumed to be broken
+const MAX_COMPONENT_DEPTH: usize = 8;
+
+/// A glyph outline command, in font units with y up
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum PathCmd {
+  MoveTo(Vec2),
+  LineTo(Vec2),
+  QuadTo(Vec2, Vec2),
+  CubicTo(Vec2, Vec2, Vec2),
+  Close,
+}
+
+pub(crate) struct TtfFont {
+  data: Vec<u8>,
+  pub(crate) units_per_em: f32,
+  /// from the baseline up to the top of the tallest glyphs
+  pub(crate) ascender: f32,
+  /// from the baseline down to the bottom of the lowest glyphs, negative
+  pub(crate) descender: f32,
+  pub(crate) line_gap: f32,
+  hmtx: usize,
+  num_h_metrics: u16,
+  cmap: Cmap,
+  outlines: Outlines,
+  kerning: HashMap<(u16, u16), i16>,
+}
+
+enum Cmap {
+  /// segments of 16 bit character codes
+  Format4(usize),
+  /// groups of 32 bit character codes
+  Format12(usize),
+}
+
+enum Outlines {
+  Glyf {
+    glyf: usize,
+    loca: usize,
+    long_loca: bool,
+  },
+  Cff(Cff),
+}
+
+// ----------------------------------------------------------------------------
+// reading bytes
+
+/// big endian reads that fail instead of panicking on truncated data
+pub(crate) trait ReadBytes {
+  fn u8_at(&self, offset: usize) -> Option<u8>;
+  fn u16_at(&self, offset: usize) -> Option<u16>;
+  fn u32_at(&self, offset: usize) -> Option<u32>;
+  fn i16_at(&self, offset: usize) -> Option<i16> {
+    self.u16_at(offset).map(|n| n as i16)
+  }
+}
+
+impl ReadBytes for [u8] {
+  fn u8_at(&self, offset: usize) -> Option<u8> {
+    self.get(offset).copied()
+  }
+
+  fn u16_at(&self, offset: usize) -> Option<u16> {
+    let bytes = self.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+  }
+
+  fn u32_at(&self, offset: usize) -> Option<u32> {
+    let bytes = self.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+}
+
+// ----------------------------------------------------------------------------
+// loading
+
+impl TtfFont {
+  /// Reads a font file. Collections (.ttc) use their first font.
+  pub(crate) fn parse(data: Vec<u8>) -> Result<TtfFont, String> {
+    let d = &data[..];
+    let bad = |table: &str| format!("the {} table is missing or broken", table);
+
+    let mut start = 0;
+    if d.starts_with(b"ttcf") {
+      start = d.u32_at(12).ok_or("broken font collection")? as usize;
+    }
+    match d.u32_at(start) {
+      Some(0x0001_0000) | Some(0x7472_7565) | Some(0x4F54_544F) => {}
+      _ => return Err("not a TrueType or OpenType font".to_string()),
+    }
+
+    // the table directory
+    let num_tables = d.u16_at(start + 4).ok_or("broken table directory")?;
+    let mut tables = HashMap::new();
+    for i in 0..num_tables as usize {
+      let record = start + 12 + i * 16;
+      let tag = d.get(record..record + 4).ok_or("broken table directory")?;
+      let offset = d.u32_at(record + 8).ok_or("broken table directory")?;
+      tables.insert(tag.to_vec(), offset as usize);
+    }
+    let table = |tag: &[u8]| tables.get(tag).copied();
+
+    let head = table(b"head").ok_or_else(|| bad("head"))?;
+    // everything is scaled by this, so zero would make every size infinite
+    let units_per_em = match d.u16_at(head + 18) {
+      Some(units) if units > 0 => units as f32,
+      _ => return Err(bad("head")),
+    };
+    let long_loca = d.i16_at(head + 50).ok_or_else(|| bad("head"))? != 0;
+
+    let hhea = table(b"hhea").ok_or_else(|| bad("hhea"))?;
+    let ascender = d.i16_at(hhea + 4).ok_or_else(|| bad("hhea"))? as f32;
+    let descender = d.i16_at(hhea + 6).ok_or_else(|| bad("hhea"))? as f32;
+    let line_gap = d.i16_at(hhea + 8).ok_or_else(|| bad("hhea"))? as f32;
+    let num_h_metrics = d.u16_at(hhea + 34).ok_or_else(|| bad("hhea"))?;
+    let hmtx = table(b"hmtx").ok_or_else(|| bad("hmtx"))?;
+
+    let cmap = table(b"cmap").ok_or_else(|| bad("cmap"))?;
+    let cmap = find_cmap(d, cmap).ok_or("no unicode character map")?;
+
+    let outlines = match (table(b"glyf"), table(b"loca"), table(b"CFF ")) {
+      (Some(glyf), Some(loca), _) => Outlines::Glyf {
+        glyf,
+        loca,
+        long_loca,
+      },
+      (_, _, Some(cff)) => Outlines::Cff(Cff::parse(d, cff)?),
+      _ => return Err("no glyph outlines".to_string()),
+    };
+
+    let kerning = match table(b"kern") {
+      Some(kern) => read_kern(d, kern).unwrap_or_default(),
+      None => HashMap::new(),
+    };
+
+    Ok(TtfFont {
+      units_per_em,
+      ascender,
+      descender,
+      line_gap,
+      hmtx,
+      num_h_metrics,
+      cmap,
+      outlines,
+      kerning,
+      data,
+    })
+  }
+}
+
+/// picks the subtable that covers the most of unicode
+fn find_cmap(d: &[u8], cmap: usize) -> Option<Cmap> {
+  let count = d.u16_at(cmap + 2)?;
+  let mut best = None;
+  for i in 0..count as usize {
+    let record = cmap + 4 + i * 8;
+    let platform = d.u16_at(record)?;
+    let encoding = d.u16_at(record + 2)?;
+    let offset = cmap + d.u32_at(record + 4)? as usize;
+    let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+    if !unicode {
+      continue;
+    }
+    match d.u16_at(offset)? {
+      12 => return Some(Cmap::Format12(offset)),
+      4 if best.is_none() => best = Some(Cmap::Format4(offset)),
+      _ => {}
+    }
+  }
+  best
+}
+
+/// horizontal pairs from the first format 0 subtable of an old style kern
+/// table. Fonts with only GPOS kerning aren't kerned.
+fn read_kern(d: &[u8], kern: usize) -> Option<HashMap<(u16, u16), i16>> {
+  let mut pairs = HashMap::new();
+  if d.u16_at(kern)? != 0 {
+    return None;
+  }
+  let count = d.u16_at(kern + 2)?;
+  let mut subtable = kern + 4;
+  for _ in 0..count {
+    let length = d.u16_at(subtable + 2)? as usize;
+    let coverage = d.u16_at(subtable + 4)?;
+    let format = coverage >> 8;
+    let horizontal = coverage & 1 != 0;
+    if format == 0 && horizontal {
+      let num_pairs = d.u16_at(subtable + 6)?;
+      for i in 0..num_pairs as usize {
+        let pair = subtable + 14 + i * 6;
+        pairs.insert((d.u16_at(pair)?, d.u16_at(pair + 2)?), d.i16_at(pair + 4)?);
+      }
+      break;
+    }
+    subtable += length;
+  }
+  Some(pairs)
+}
+
+// ----------------------------------------------------------------------------
+// lookups
+
+impl TtfFont {
+  /// The glyph for a character, or `None` if the font doesn't have one
+  pub(crate) fn glyph_index(&self, c: char) -> Option<u16> {
+    let d = &self.data[..];
+    let c = c as u32;
+    let glyph = match self.cmap {
+      Cmap::Format4(table) => {
+        if c > 0xFFFF {
+          return None;
+        }
+        let segs = d.u16_at(table + 6)? as usize / 2;
+        let ends = table + 14;
+        let starts = ends + segs * 2 + 2;
+        let deltas = starts + segs * 2;
+        let range_offsets = deltas + segs * 2;
+        // segments are sorted by their end code
+        let (mut lo, mut hi) = (0, segs);
+        while lo < hi {
+          let mid = (lo + hi) / 2;
+          if (d.u16_at(ends + mid * 2)? as u32) < c {
+            lo = mid + 1;
+          } else {
+            hi = mid;
+          }
+        }
+        let seg = lo;
+        if seg >= segs {
+          return None;
+        }
+        let start = d.u16_at(starts + seg * 2)? as u32;
+        if c < start {
+          return None;
+        }
+        let delta = d.u16_at(deltas + seg * 2)?;
+        let range_offset = d.u16_at(range_offsets + seg * 2)? as usize;
+        if range_offset == 0 {
+          (c as u16).wrapping_add(delta)
+        } else {
+          let at = range_offsets + seg * 2 + range_offset + (c - start) as usize * 2;
+          match d.u16_at(at)? {
+            0 => 0,
+            glyph => glyph.wrapping_add(delta),
+          }
+        }
+      }
+      Cmap::Format12(table) => {
+        let groups = d.u32_at(table + 12)? as usize;
+        let (mut lo, mut hi) = (0, groups);
+        let mut found = 0;
+        while lo < hi {
+          let mid = (lo + hi) / 2;
+          let group = table + 16 + mid * 12;
+          let start = d.u32_at(group)?;
+          let end = d.u32_at(group + 4)?;
+          if c < start {
+            hi = mid;
+          } else if c > end {
+            lo = mid + 1;
+          } else {
+            found = d.u32_at(group + 8)? + (c - start);
+            break;
+          }
+        }
+        found as u16
+      }
+    };
+    // glyph 0 is the font's "missing character" box
+    Some(glyph).filter(|glyph| *glyph != 0)
+  }
+
+  /// how far the pen moves after a glyph, in font units
+  pub(crate) fn advance(&self, glyph: u16) -> f32 {
+    let metric = glyph.min(self.num_h_metrics.saturating_sub(1)) as usize;
+    self.data.u16_at(self.hmtx + metric * 4).unwrap_or(0) as f32
+  }
+
+  /// extra space between two glyphs, in font units
+  pub(crate) fn kerning(&self, left: u16, right: u16) -> f32 {
+    self.kerning.get(&(left, right)).copied().unwrap_or(0) as f32
+  }
+
+  /// A glyph's outline, in font units. Empty for glyphs with nothing to
+  /// draw, like spaces, and for glyphs that can't be read.
+  pub(crate) fn outline(&self, glyph: u16) -> Vec<PathCmd> {
+    let mut path = vec![];
+    let ok = match &self.outlines {
+      Outlines::Glyf { .. } => self.glyf_outline(glyph, Affine::IDENTITY, 0, &mut path),
+      Outlines::Cff(cff) => cff.outline(&self.data, glyph, &mut path),
+    };
+    if ok.is_none() {
+      path.clear();
+    }
+    path
+  }
+}
+
+// ----------------------------------------------------------------------------
+// glyf outlines
+
+/// a 2d transform for placing the parts of composite glyphs
+#[derive(Copy, Clone)]
+struct Affine {
+  xx: f32,
+  xy: f32,
+  yx: f32,
+  yy: f32,
+  dx: f32,
+  dy: f32,
+}
+
+impl Affine {
+  const IDENTITY: Affine = Affine {
+    xx: 1.0,
+    xy: 0.0,
+    yx: 0.0,
+    yy: 1.0,
+    dx: 0.0,
+    dy: 0.0,
+  };
+
+  fn apply(&self, x: f32, y: f32) -> Vec2 {
+    vec2(
+      self.xx * x + self.yx * y + self.dx,
+      self.xy * x + self.yy * y + self.dy,
+    )
+  }
+
+  /// `inner` followed by `self`
+  fn then(&self, inner: Affine) -> Affine {
+    let origin = self.apply(inner.dx, inner.dy);
+    Affine {
+      xx: self.xx * inner.xx + self.yx * inner.xy,
+      xy: self.xy * inner.xx + self.yy * inner.xy,
+      yx: self.xx * inner.yx + self.yx * inner.yy,
+      yy: self.xy * inner.yx + self.yy * inner.yy,
+      dx: origin.x(),
+      dy: origin.y(),
+    }
+  }
+}
+
+impl TtfFont {
+  /// where a glyph's data is in the glyf table, `None` for empty glyphs
+  fn glyf_range(&self, glyph: u16) -> Option<(usize, usize)> {
+    let (glyf, loca, long_loca) = match self.outlines {
+      Outlines::Glyf {
+        glyf,
+        loca,
+        long_loca,
+      } => (glyf, loca, long_loca),
+      Outlines::Cff(_) => return None,
+    };
+    let d = &self.data[..];
+    let i = glyph as usize;
+    let (start, end) = if long_loca {
+      (
+        d.u32_at(loca + i * 4)? as usize,
+        d.u32_at(loca + i * 4 + 4)? as usize,
+      )
+    } else {
+      (
+        d.u16_at(loca + i * 2)? as usize * 2,
+        d.u16_at(loca + i * 2 + 2)? as usize * 2,
+      )
+    };
+    if end <= start {
+      return None;
+    }
+    Some((glyf + start, glyf + end))
+  }
+
+  fn glyf_outline(
+    &self,
+    glyph: u16,
+    transform: Affine,
+    depth: usize,
+    path: &mut Vec<PathCmd>,
+  ) -> Option<()> {
+    let (start, _) = match self.glyf_range(glyph) {
+      Some(range) => range,
+      None => return Some(()),
+    };
+    let d = &self.data[..];
+    let contours = d.i16_at(start)?;
+    if contours >= 0 {
+      self.simple_outline(start, contours as usize, transform, path)
+    } else if depth < MAX_COMPONENT_DEPTH {
+      self.composite_outline(start, transform, depth, path)
+    } else {
+      None
+    }
+  }
+
+  fn simple_outline(
+    &self,
+    start: usize,
+    contours: usize,
+    transform: Affine,
+    path: &mut Vec<PathCmd>,
+  ) -> Option<()> {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const REPEAT: u8 = 0x08;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+    let d = &self.data[..];
+    let mut ends = vec![];
+    for i in 0..contours {
+      ends.push(d.u16_at(start + 10 + i * 2)? as usize);
+    }
+    let num_points = ends.last().map_or(0, |end| end + 1);
+    let instructions = d.u16_at(start + 10 + contours * 2)? as usize;
+    let mut at = start + 12 + contours * 2 + instructions;
+
+    let mut flags = Vec::with_capacity(num_points);
+    while flags.len() < num_points {
+      let flag = d.u8_at(at)?;
+      at += 1;
+      flags.push(flag);
+      if flag & REPEAT != 0 {
+        let count = d.u8_at(at)?;
+        at += 1;
+        for _ in 0..count {
+          flags.push(flag);
+        }
+      }
+    }
+    flags.truncate(num_points);
+
+    // coordinates are deltas from the previous point
+    let mut read_coords = |short: u8, same_or_positive: u8| -> Option<Vec<f32>> {
+      let mut coords = Vec::with_capacity(num_points);
+      let mut value = 0i32;
+      for flag in &flags {
+        if flag & short != 0 {
+          let delta = d.u8_at(at)? as i32;
+          at += 1;
+          value += if flag & same_or_positive != 0 {
+            delta
+          } else {
+            -delta
+          };
+        } else if flag & same_or_positive == 0 {
+          value += d.i16_at(at)? as i32;
+          at += 2;
+        }
+        coords.push(value as f32);
+      }
+      Some(coords)
+    };
+    let xs = read_coords(X_SHORT, X_SAME_OR_POSITIVE)?;
+    let ys = read_coords(Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+    let mut first = 0;
+    for end in ends {
+      if end < first || end >= num_points {
+        return None;
+      }
+      let points: Vec<(Vec2, bool)> = (first..=end)
+        .map(|i| (transform.apply(xs[i], ys[i]), flags[i] & ON_CURVE != 0))
+        .collect();
+      contour_path(&points, path);
+      first = end + 1;
+    }
+    Some(())
+  }
+
+  fn composite_outline(
+    &self,
+    start: usize,
+    transform: Affine,
+    depth: usize,
+    path: &mut Vec<PathCmd>,
+  ) -> Option<()> {
+    const ARGS_ARE_WORDS: u16 = 0x0001;
+    const ARGS_ARE_XY: u16 = 0x0002;
+    const HAVE_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const HAVE_XY_SCALE: u16 = 0x0040;
+    const HAVE_2X2: u16 = 0x0080;
+
+    let d = &self.data[..];
+    let mut at = start + 10;
+    loop {
+      let flags = d.u16_at(at)?;
+      let glyph = d.u16_at(at + 2)?;
+      at += 4;
+      let (arg1, arg2) = if flags & ARGS_ARE_WORDS != 0 {
+        at += 4;
+        (d.i16_at(at - 4)? as f32, d.i16_at(at - 2)? as f32)
+      } else {
+        at += 2;
+        (d.u8_at(at - 2)? as i8 as f32, d.u8_at(at - 1)? as i8 as f32)
+      };
+      let f2dot14 = |at: usize| d.i16_at(at).map(|n| n as f32 / 16384.0);
+      let mut part = Affine::IDENTITY;
+      if flags & HAVE_SCALE != 0 {
+        part.xx = f2dot14(at)?;
+        part.yy = part.xx;
+        at += 2;
+      } else if flags & HAVE_XY_SCALE != 0 {
+        part.xx = f2dot14(at)?;
+        part.yy = f2dot14(at + 2)?;
+        at += 4;
+      } else if flags & HAVE_2X2 != 0 {
+        part.xx = f2dot14(at)?;
+        part.xy = f2dot14(at + 2)?;
+        part.yx = f2dot14(at + 4)?;
+        part.yy = f2dot14(at + 6)?;
+        at += 8;
+      }
+      // parts placed by matching up points are rare, and left where they are
+      if flags & ARGS_ARE_XY != 0 {
+        part.dx = arg1;
+        part.dy = arg2;
+      }
+
+      self.glyf_outline(glyph, transform.then(part), depth + 1, path)?;
+      if flags & MORE_COMPONENTS == 0 {
+        return Some(());
+      }
+    }
+  }
+}
+
+/// Turns a contour of on and off curve points into path commands. Two off
+/// curve points in a row have an implied on curve point halfway between them.
+fn contour_path(points: &[(Vec2, bool)], path: &mut Vec<PathCmd>) {
+  if points.is_empty() {
+    return;
+  }
+  // start from an on curve point, making one up if there aren't any
+  let first_on = points.iter().position(|(_, on)| *on);
+  let (start, rotate) = match first_on {
+    Some(i) => (points[i].0, i),
+    None => ((points[0].0 + points[1 % points.len()].0) * 0.5, 1),
+  };
+  path.push(PathCmd::MoveTo(start));
+
+  let mut control: Option<Vec2> = None;
+  let n = points.len();
+  let skip = if first_on.is_some() { 1 } else { 0 };
+  for k in skip..=n {
+    let (p, on) = if k == n {
+      (start, true)
+    } else {
+      points[(rotate + k) % n]
+    };
+    match (control, on) {
+      (None, true) => path.push(PathCmd::LineTo(p)),
+      (None, false) => control = Some(p),
+      (Some(c), true) => {
+        path.push(PathCmd::QuadTo(c, p));
+        control = None;
+      }
+      (Some(c), false) => {
+        path.push(PathCmd::QuadTo(c, (c + p) * 0.5));
+        control = Some(p);
+      }
+    }
+  }
+  path.push(PathCmd::Close);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// a font that's nothing but a character map starting at byte 0
+  fn font_with_cmap(data: Vec<u8>, cmap: Cmap) -> TtfFont {
+    TtfFont {
+      data,
+      units_per_em: 1000.0,
+      ascender: 800.0,
+      descender: -200.0,
+      line_gap: 0.0,
+      hmtx: 0,
+      num_h_metrics: 0,
+      cmap,
+      outlines: Outlines::Glyf {
+        glyf: 0,
+        loca: 0,
+        long_loca: false,
+      },
+      kerning: HashMap::new(),
+    }
+  }
+
+  fn push_u16(d: &mut Vec<u8>, n: u16) {
+    d.extend_from_slice(&n.to_be_bytes());
+  }
+
+  fn push_u32(d: &mut Vec<u8>, n: u32) {
+    d.extend_from_slice(&n.to_be_bytes());
+  }
+
+  #[test]
+  fn looks_up_format_4_segments() {
+    // A-C by delta, a-b through the glyph id array, and the required
+    // final segment at 0xFFFF
+    let ends = [0x43, 0x62, 0xFFFF];
+    let starts = [0x41, 0x61, 0xFFFF];
+    let deltas = [1u16.wrapping_sub(0x41), 0, 1];
+    // from the segment's own range offset to the start of the id array
+    let range_offsets = [0, 4, 0];
+    let glyph_ids = [10, 0];
+
+    let mut d = vec![];
+    push_u16(&mut d, 4);
+    push_u16(&mut d, 0); // length, unused
+    push_u16(&mut d, 0); // language
+    push_u16(&mut d, ends.len() as u16 * 2);
+    push_u16(&mut d, 0); // search range, entry selector and range shift
+    push_u16(&mut d, 0);
+    push_u16(&mut d, 0);
+    ends.iter().for_each(|n| push_u16(&mut d, *n));
+    push_u16(&mut d, 0); // reserved
+    starts.iter().for_each(|n| push_u16(&mut d, *n));
+    deltas.iter().for_each(|n| push_u16(&mut d, *n));
+    range_offsets.iter().for_each(|n| push_u16(&mut d, *n));
+    glyph_ids.iter().for_each(|n| push_u16(&mut d, *n));
+    let font = font_with_cmap(d, Cmap::Format4(0));
+
+    assert_eq!(font.glyph_index('A'), Some(1));
+    assert_eq!(font.glyph_index('C'), Some(3));
+    assert_eq!(font.glyph_index('D'), None);
+    assert_eq!(font.glyph_index('a'), Some(10));
+    // mapped to the missing glyph
+    assert_eq!(font.glyph_index('b'), None);
+    assert_eq!(font.glyph_index('@'), None);
+    assert_eq!(font.glyph_index('\u{1F600}'), None);
+  }
+
+  #[test]
+  fn looks_up_format_12_groups() {
+    let groups = [(0x20, 0x7E, 1), (0x1F600, 0x1F64F, 200)];
+
+    let mut d = vec![];
+    push_u16(&mut d, 12);
+    push_u16(&mut d, 0); // reserved
+    push_u32(&mut d, 0); // length, unused
+    push_u32(&mut d, 0); // language
+    push_u32(&mut d, groups.len() as u32);
+    for (start, end, glyph) in &groups {
+      push_u32(&mut d, *start);
+      push_u32(&mut d, *end);
+      push_u32(&mut d, *glyph);
+    }
+    let font = font_with_cmap(d, Cmap::Format12(0));
+
+    assert_eq!(font.glyph_index(' '), Some(1));
+    assert_eq!(font.glyph_index('A'), Some(0x41 - 0x20 + 1));
+    assert_eq!(font.glyph_index('\u{1F601}'), Some(201));
+    assert_eq!(font.glyph_index('\u{7F}'), None);
+    assert_eq!(font.glyph_index('\u{1F650}'), None);
+    assert_eq!(font.glyph_index('\u{1F}'), None);
+  }
+
+  #[test]
+  fn rejects_zero_units_per_em() {
+    // a table directory with just a head table, all zeroes
+    let mut d = vec![];
+    push_u32(&mut d, 0x0001_0000);
+    push_u16(&mut d, 1);
+    d.extend_from_slice(&[0; 6]);
+    d.extend_from_slice(b"head");
+    push_u32(&mut d, 0); // checksum
+    push_u32(&mut d, 28);
+    push_u32(&mut d, 54);
+    d.extend_from_slice(&[0; 54]);
+
+    let error = TtfFont::parse(d).err();
+    assert_eq!(
+      error.as_deref(),
+      Some("the head table is missing or broken")
+    );
+  }
+
+  #[test]
+  fn makes_up_a_start_for_all_off_curve_contours() {
+    let points = [
+      (vec2(0.0, 0.0), false),
+      (vec2(2.0, 0.0), false),
+      (vec2(2.0, 2.0), false),
+      (vec2(0.0, 2.0), false),
+    ];
+    let mut path = vec![];
+    contour_path(&points, &mut path);
+
+    // each pair of off curve points has an implied on curve point
+    // between them, and the contour starts and ends at the first one
+    let expected = vec![
+      PathCmd::MoveTo(vec2(1.0, 0.0)),
+      PathCmd::QuadTo(vec2(2.0, 0.0), vec2(2.0, 1.0)),
+      PathCmd::QuadTo(vec2(2.0, 2.0), vec2(1.0, 2.0)),
+      PathCmd::QuadTo(vec2(0.0, 2.0), vec2(0.0, 1.0)),
+      PathCmd::QuadTo(vec2(0.0, 0.0), vec2(1.0, 0.0)),
+      PathCmd::Close,
+    ];
+    assert_eq!(format!("{:?}", path), format!("{:?}", expected));
+  }
+}
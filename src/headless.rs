@@ -0,0 +1,256 @@
+//! Runs a game without a window or gpu so that game logic can be exercised
+//! in tests. Input is scripted ahead of time and fed through the same event
+//! handling as a real window.
+
+use crate::*;
+
+/// A synthetic input event that can be fed to a game running headless.
+/// Mouse positions are in window coordinates, like `MouseCtx::pos`.
+#[derive(Clone, Debug)]
+pub enum ScriptEvent {
+  MouseMove(f32, f32),
+  MouseDown(MouseButton),
+  MouseUp(MouseButton),
+  MouseScroll(f32, f32),
+  KeyDown(Keycode),
+  KeyUp(Keycode),
+  /// a typed character. Pair with `KeyDown` to also press the key.
+  Char(char),
+  /// a finger with the given id touched down at a window position
+  TouchBegan(usize, f32, f32),
+  TouchMoved(usize, f32, f32),
+  TouchEnded(usize, f32, f32),
+  /// delivered through a `MockGamepads` backend, which headless runs use
+  /// unless the game installs its own
+  Gamepad(GamepadEvent),
+}
+
+impl ScriptEvent {
+  /// builds the sokol event a real window would have sent for this input
+  pub(crate) fn to_sapp_event(&self, frame_count: u32) -> SAppEvent {
+    let mut event = blank_sapp_event(frame_count);
+
+    match *self {
+      ScriptEvent::MouseMove(x, y) => {
+        event.event_type = SAppEventType::MouseMove;
+        event.mouse_x = x;
+        event.mouse_y = y;
+      }
+      ScriptEvent::MouseDown(button) => {
+        event.event_type = SAppEventType::MouseDown;
+        event.mouse_button = button;
+      }
+      ScriptEvent::MouseUp(button) => {
+        event.event_type = SAppEventType::MouseUp;
+        event.mouse_button = button;
+      }
+      ScriptEvent::MouseScroll(x, y) => {
+        event.event_type = SAppEventType::MouseScroll;
+        event.scroll_x = x;
+        event.scroll_y = y;
+      }
+      ScriptEvent::KeyDown(key_code) => {
+        event.event_type = SAppEventType::KeyDown;
+        event.key_code = key_code;
+      }
+      ScriptEvent::KeyUp(key_code) => {
+        event.event_type = SAppEventType::KeyUp;
+        event.key_code = key_code;
+      }
+      ScriptEvent::Char(c) => {
+        event.event_type = SAppEventType::Char;
+        event.char_code = c as u32;
+      }
+      ScriptEvent::TouchBegan(id, x, y) => {
+        event.event_type = SAppEventType::TouchesBegan;
+        set_touch(&mut event, id, x, y);
+      }
+      ScriptEvent::TouchMoved(id, x, y) => {
+        event.event_type = SAppEventType::TouchesMoved;
+        set_touch(&mut event, id, x, y);
+      }
+      ScriptEvent::TouchEnded(id, x, y) => {
+        event.event_type = SAppEventType::TouchesEnded;
+        set_touch(&mut event, id, x, y);
+      }
+      ScriptEvent::Gamepad(_) => {}
+    }
+
+    event
+  }
+}
+
+/// an event with nothing set, to fill in with the parts that matter
+pub(crate) fn blank_sapp_event(frame_count: u32) -> SAppEvent {
+  SAppEvent {
+    event_type: SAppEventType::Invalid,
+    frame_count,
+    key_code: Keycode::KeyInvalid,
+    char_code: 0,
+    modifiers: SAppModifier::empty(),
+    mouse_button: MouseButton::Invalid,
+    mouse_x: 0.0,
+    mouse_y: 0.0,
+    scroll_x: 0.0,
+    scroll_y: 0.0,
+    num_touches: 0,
+    touches: [SAppTouchPoint {
+      identifier: 0,
+      pos_x: 0.0,
+      pos_y: 0.0,
+      changed: false,
+    }; sokol::app::ffi::SAPP_MAX_TOUCHPOINTS],
+    window_width: HEADLESS_WIDTH as i32,
+    window_height: HEADLESS_HEIGHT as i32,
+    framebuffer_width: HEADLESS_WIDTH as i32,
+    framebuffer_height: HEADLESS_HEIGHT as i32,
+  }
+}
+
+/// makes the event carry a single changed touch point
+fn set_touch(event: &mut SAppEvent, id: usize, x: f32, y: f32) {
+  event.num_touches = 1;
+  event.touches[0] = SAppTouchPoint {
+    identifier: id,
+    pos_x: x,
+    pos_y: y,
+    changed: true,
+  };
+}
+
+/// Runs a game for a set number of frames without opening a window. Each
+/// entry in `script` is a frame index (starting at 0) and an event that is
+/// delivered at the start of that frame. Entries can come in any order;
+/// events for the same frame are delivered in the order they're listed. Time
+/// advances by exactly one fixed step per frame so that runs are
+/// deterministic.
+///
+/// Nothing is drawn, but draw calls are still collected and cleared each
+/// frame. The run ends early if the game quits via `request_quit`, and
+/// `KApp::cleanup` is called either way. Returns the game and engine state
+/// once the last frame has run so that tests can make assertions about them.
+pub fn run_headless<K: KApp>(frames: u32, script: &[(u32, ScriptEvent)]) -> (K, Ctx) {
+  let mut ctx: Ctx = Default::default();
+  ctx.gfx.headless = true;
+  let gamepads = MockGamepads::new();
+  set_gamepad_backend(&mut ctx, Box::new(gamepads.clone()));
+  let app: K = K::new();
+  let mut app = App {
+    ctx,
+    app: Some(app),
+  };
+
+  app.sapp_init();
+
+  // stable, so events on the same frame keep their order
+  let mut script: Vec<&(u32, ScriptEvent)> = script.iter().collect();
+  script.sort_by_key(|(at, _)| *at);
+  let mut script = script.into_iter().peekable();
+  for frame in 0..frames {
+    while let Some((_, event)) = script.next_if(|(at, _)| *at <= frame) {
+      if let ScriptEvent::Gamepad(event) = event {
+        gamepads.send(event.clone());
+        continue;
+      }
+      let event = event.to_sapp_event(app.ctx.frame_count);
+      app.sapp_event(event);
+    }
+    let real_delta = app.ctx.step.dt;
+    if !app.frame(real_delta) {
+      break;
+    }
+  }
+
+  app.shutdown();
+  // unlike a windowed run, the game is handed back rather than dropped
+  let App { ctx, app } = app;
+  (app.expect("the game is only dropped on exit"), ctx)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Default)]
+  struct Counter {
+    frames: u32,
+    updates: u32,
+    presses: u32,
+    held_frames: u32,
+    cleaned_up: bool,
+  }
+
+  impl KApp for Counter {
+    fn new() -> Self {
+      Default::default()
+    }
+
+    fn init(&mut self, ctx: &mut Ctx) {}
+
+    fn frame(&mut self, ctx: &mut Ctx) {
+      self.frames += 1;
+      let keyboard = &ctx.input.keyboard;
+      if keyboard.pressed_this_frame(Keycode::KeyA) {
+        self.presses += 1;
+      }
+      if keyboard.is_down(Keycode::KeyA) {
+        self.held_frames += 1;
+      }
+      if keyboard.pressed_this_frame(Keycode::KeyEscape) {
+        request_quit(ctx);
+      }
+    }
+
+    fn update(&mut self, ctx: &mut Ctx, dt: f32) {
+      self.updates += 1;
+    }
+
+    fn cleanup(&mut self, ctx: &mut Ctx) {
+      self.cleaned_up = true;
+    }
+  }
+
+  #[test]
+  fn runs_scripted_frames() {
+    let script = [
+      (2, ScriptEvent::KeyDown(Keycode::KeyA)),
+      (5, ScriptEvent::KeyUp(Keycode::KeyA)),
+    ];
+    let (game, ctx) = run_headless::<Counter>(10, &script);
+
+    assert_eq!(game.frames, 10);
+    // one fixed step per frame
+    assert_eq!(game.updates, 10);
+    assert_eq!(game.presses, 1);
+    assert_eq!(game.held_frames, 3);
+    assert!(game.cleaned_up);
+    assert_eq!(ctx.frame_count, 10);
+    assert!((ctx.time.elapsed - 10.0 * ctx.step.dt as f64).abs() < 1e-6);
+  }
+
+  #[test]
+  fn sorts_the_script_by_frame() {
+    let script = [
+      (5, ScriptEvent::KeyUp(Keycode::KeyA)),
+      (2, ScriptEvent::KeyDown(Keycode::KeyA)),
+      // a tap within one frame, which only works in this order
+      (8, ScriptEvent::KeyDown(Keycode::KeyA)),
+      (8, ScriptEvent::KeyUp(Keycode::KeyA)),
+    ];
+    let (game, _) = run_headless::<Counter>(10, &script);
+
+    assert_eq!(game.presses, 2);
+    assert_eq!(game.held_frames, 3);
+  }
+
+  #[test]
+  fn stops_when_the_game_quits() {
+    let script = [(3, ScriptEvent::KeyDown(Keycode::KeyEscape))];
+    let (game, ctx) = run_headless::<Counter>(10, &script);
+
+    assert_eq!(game.frames, 4);
+    assert_eq!(game.updates, 4);
+    assert!(game.cleaned_up);
+    assert!((ctx.time.real_elapsed - 4.0 * ctx.step.dt as f64).abs() < 1e-6);
+  }
+}
@@ -1,9 +1,38 @@
+//! Input handling built on top of the raw state in `InputCtx`.
+mod actions;
+mod cursor;
+mod gamepad;
+mod keys;
+mod text;
+mod touch;
+
 use crate::math::Vec2;
 use crate::*;
 
-pub fn preferred() -> InputType {
-  // TODO https://www.gamasutra.com/blogs/ZachBurke/20151030/257920/The_5_Golden_Rules_of_Input.php
-  InputType::MouseKeyboard
-  // TODO detect gamepad
-  // TODO detect touch
+pub use actions::*;
+pub use cursor::*;
+pub use gamepad::*;
+pub use keys::*;
+pub use text::*;
+pub use touch::*;
+
+/// The kind of input the player used most recently, for deciding which
+/// prompts and controls to show. Starts out as mouse and keyboard.
+/// See https://www.gamasutra.com/blogs/ZachBurke/20151030/257920/The_5_Golden_Rules_of_Input.php
+pub fn preferred(ctx: &Ctx) -> InputType {
+  ctx.input.preferred
+}
+
+/// whether `preferred` changed since the previous frame. Also see
+/// `KApp::preferred_input_changed`.
+pub fn preferred_changed(ctx: &Ctx) -> bool {
+  ctx.input.preferred_changed
+}
+
+/// records that the player just used the given kind of input
+pub(crate) fn use_input(input: &mut InputCtx, input_type: InputType) {
+  if input.preferred != input_type {
+    input.preferred = input_type;
+    input.preferred_changed = true;
+  }
 }
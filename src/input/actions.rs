@@ -0,0 +1,504 @@
+//! Maps named actions and axes onto physical inputs. Games declare what
+//! "jump" or "move_x" mean once, then ask about the action instead of
+//! checking specific keys, which lets players rebind controls.
+//!
+//! Bindings can be saved to and loaded from a simple text file with one
+//! binding per line, for example:
+//!
+//! ```text
+//! action jump key KeySpace
+//! action jump gamepad South
+//! axis move_x buttons key KeyA key KeyD
+//! axis move_x gamepad LeftX
+//! action crouch
+//! ```
+//!
+//! An action or axis with nothing after its name was cleared by the player,
+//! and stays unbound after loading rather than getting its defaults back.
+
+use crate::input::gamepad::*;
+use crate::input::keys::*;
+use crate::*;
+use std::fs;
+use std::path::Path;
+
+/// A physical input that can trigger a digital action
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Binding {
+  Key(Keycode),
+  Mouse(MouseButton),
+  GamepadButton(GamepadButton),
+}
+
+/// A physical input that can drive an analog axis, which ranges from -1 to 1
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AxisBinding {
+  /// a pair of digital inputs. Holding the first pushes the axis toward -1
+  /// and holding the second pushes it toward 1.
+  Buttons(Binding, Binding),
+  GamepadAxis(GamepadAxis),
+}
+
+// ----------------------------------------------------------------------------
+// declaring actions
+
+/// Binds an input to the named action, in addition to any existing bindings.
+/// Names can't contain whitespace.
+pub fn bind_action(ctx: &mut Ctx, name: &str, binding: Binding) {
+  let bindings = ctx
+    .input
+    .actions
+    .actions
+    .entry(name.to_string())
+    .or_default();
+  if !bindings.contains(&binding) {
+    bindings.push(binding);
+  }
+}
+
+/// Binds an input to the named axis, in addition to any existing bindings.
+/// Names can't contain whitespace.
+pub fn bind_axis(ctx: &mut Ctx, name: &str, binding: AxisBinding) {
+  let bindings = ctx.input.actions.axes.entry(name.to_string()).or_default();
+  if !bindings.contains(&binding) {
+    bindings.push(binding);
+  }
+}
+
+/// Removes all bindings from the named action, e.g. before rebinding it.
+/// The action is saved as unbound, so its defaults don't come back on load.
+pub fn clear_action(ctx: &mut Ctx, name: &str) {
+  let actions = &mut ctx.input.actions.actions;
+  actions.entry(name.to_string()).or_default().clear();
+}
+
+/// Removes all bindings from the named axis, e.g. before rebinding it. The
+/// axis is saved as unbound, so its defaults don't come back on load.
+pub fn clear_axis(ctx: &mut Ctx, name: &str) {
+  let axes = &mut ctx.input.actions.axes;
+  axes.entry(name.to_string()).or_default().clear();
+}
+
+/// the inputs currently bound to the named action, for display in menus
+pub fn action_bindings<'a>(ctx: &'a Ctx, name: &str) -> &'a [Binding] {
+  match ctx.input.actions.actions.get(name) {
+    Some(bindings) => bindings,
+    None => &[],
+  }
+}
+
+/// the inputs currently bound to the named axis, for display in menus
+pub fn axis_bindings<'a>(ctx: &'a Ctx, name: &str) -> &'a [AxisBinding] {
+  match ctx.input.actions.axes.get(name) {
+    Some(bindings) => bindings,
+    None => &[],
+  }
+}
+
+// ----------------------------------------------------------------------------
+// querying actions
+
+/// whether any input bound to the named action is held down
+pub fn action_down(ctx: &Ctx, name: &str) -> bool {
+  action_bindings(ctx, name)
+    .iter()
+    .any(|binding| binding_down(ctx, *binding))
+}
+
+/// whether any input bound to the named action was pressed this frame
+pub fn action_pressed(ctx: &Ctx, name: &str) -> bool {
+  action_bindings(ctx, name)
+    .iter()
+    .any(|binding| binding_pressed(ctx, *binding))
+}
+
+/// whether any input bound to the named action was released this frame
+pub fn action_released(ctx: &Ctx, name: &str) -> bool {
+  action_bindings(ctx, name)
+    .iter()
+    .any(|binding| binding_released(ctx, *binding))
+}
+
+/// the value of the named axis, from -1 to 1. When several bound inputs are
+/// active at once their values are added together.
+pub fn axis(ctx: &Ctx, name: &str) -> f32 {
+  let value = axis_bindings(ctx, name)
+    .iter()
+    .map(|binding| axis_binding_value(ctx, *binding))
+    .sum();
+  clampf(value, -1.0, 1.0)
+}
+
+fn binding_down(ctx: &Ctx, binding: Binding) -> bool {
+  match binding {
+    Binding::Key(key) => ctx.input.keyboard.is_down(key),
+    Binding::Mouse(button) => ctx.input.mouse.button(button).is_some_and(|b| b.held),
+    Binding::GamepadButton(button) => any_gamepad(ctx, |pad| pad.button(button).held),
+  }
+}
+
+fn binding_pressed(ctx: &Ctx, binding: Binding) -> bool {
+  match binding {
+    Binding::Key(key) => ctx.input.keyboard.pressed_this_frame(key),
+    Binding::Mouse(button) => ctx.input.mouse.button(button).is_some_and(|b| b.down > 0),
+    Binding::GamepadButton(button) => any_gamepad(ctx, |pad| pad.button(button).down > 0),
+  }
+}
+
+fn binding_released(ctx: &Ctx, binding: Binding) -> bool {
+  match binding {
+    Binding::Key(key) => ctx.input.keyboard.released_this_frame(key),
+    Binding::Mouse(button) => ctx.input.mouse.button(button).is_some_and(|b| b.up > 0),
+    Binding::GamepadButton(button) => any_gamepad(ctx, |pad| pad.button(button).up > 0),
+  }
+}
+
+/// whether the condition holds for any connected gamepad, so that any
+/// controller can drive actions in single player games
+fn any_gamepad(ctx: &Ctx, f: impl Fn(&GamepadCtx) -> bool) -> bool {
+  ctx.input.gamepads.iter().any(f)
+}
+
+fn axis_binding_value(ctx: &Ctx, binding: AxisBinding) -> f32 {
+  match binding {
+    AxisBinding::Buttons(negative, positive) => {
+      let mut value = 0.0;
+      if binding_down(ctx, negative) {
+        value -= 1.0;
+      }
+      if binding_down(ctx, positive) {
+        value += 1.0;
+      }
+      value
+    }
+    // when several gamepads are connected, the one pushed furthest wins
+    AxisBinding::GamepadAxis(axis) => ctx
+      .input
+      .gamepads
+      .iter()
+      .map(|pad| pad.axis(axis))
+      .fold(0.0, |a, b| if b.abs() > a.abs() { b } else { a }),
+  }
+}
+
+// ----------------------------------------------------------------------------
+// prompts
+
+/// The name of an input bound to the named action that suits the player's
+/// `preferred` input, e.g. "Space" or "Cross", for prompts like "press Space
+/// to jump". `None` if there's no suitable binding, or the player is using
+/// touch, which has no buttons to name.
+pub fn action_prompt(ctx: &Ctx, name: &str) -> Option<String> {
+  let preferred = preferred(ctx);
+  action_bindings(ctx, name)
+    .iter()
+    .find(|binding| {
+      matches!(
+        (preferred, binding),
+        (InputType::MouseKeyboard, Binding::Key(_))
+          | (InputType::MouseKeyboard, Binding::Mouse(_))
+          | (InputType::Gamepad(_), Binding::GamepadButton(_))
+      )
+    })
+    .map(|binding| binding_glyph_name(*binding, preferred))
+}
+
+/// the name of a bound input as shown to players. Gamepad buttons are named
+/// for the family in `input_type`, or like an Xbox controller otherwise.
+pub fn binding_glyph_name(binding: Binding, input_type: InputType) -> String {
+  match binding {
+    Binding::Key(key) => {
+      let name = keycode_name(key);
+      name.trim_start_matches("Key").to_string()
+    }
+    Binding::Mouse(button) => format!("{:?} Mouse", button),
+    Binding::GamepadButton(button) => {
+      let family = match input_type {
+        InputType::Gamepad(family) => family,
+        _ => GamepadFamily::Generic,
+      };
+      gamepad_glyph_name(family, button).to_string()
+    }
+  }
+}
+
+// ----------------------------------------------------------------------------
+// saving and loading
+
+/// Writes all bindings to a text file, e.g. from an options menu
+pub fn save_bindings(ctx: &Ctx, path: &Path) -> Result<(), Error> {
+  fs::write(path, bindings_to_string(ctx)).map_err(|source| Error::Io {
+    path: path.to_path_buf(),
+    source,
+  })
+}
+
+/// Reads bindings from a text file written by `save_bindings`. Each action
+/// or axis in the file has its bindings replaced; any others are left alone,
+/// so defaults declared in code survive for actions added since the save.
+pub fn load_bindings(ctx: &mut Ctx, path: &Path) -> Result<(), Error> {
+  let text = fs::read_to_string(path).map_err(|source| Error::Io {
+    path: path.to_path_buf(),
+    source,
+  })?;
+  parse_bindings(ctx, &text)
+}
+
+pub(crate) fn bindings_to_string(ctx: &Ctx) -> String {
+  let mut text = String::new();
+  for (name, bindings) in &ctx.input.actions.actions {
+    if bindings.is_empty() {
+      text += &format!("action {}\n", name);
+    }
+    for binding in bindings {
+      text += &format!("action {} {}\n", name, binding_to_string(*binding));
+    }
+  }
+  for (name, bindings) in &ctx.input.actions.axes {
+    if bindings.is_empty() {
+      text += &format!("axis {}\n", name);
+    }
+    for binding in bindings {
+      let binding = match binding {
+        AxisBinding::Buttons(negative, positive) => format!(
+          "buttons {} {}",
+          binding_to_string(*negative),
+          binding_to_string(*positive)
+        ),
+        AxisBinding::GamepadAxis(axis) => format!("gamepad {:?}", axis),
+      };
+      text += &format!("axis {} {}\n", name, binding);
+    }
+  }
+  text
+}
+
+/// parses bindings text, only applying it if every line is valid
+pub(crate) fn parse_bindings(ctx: &mut Ctx, text: &str) -> Result<(), Error> {
+  // `None` marks an action or axis that was saved with no bindings
+  let mut actions: Vec<(&str, Option<Binding>)> = vec![];
+  let mut axes: Vec<(&str, Option<AxisBinding>)> = vec![];
+
+  for (i, line) in text.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    let error = |message: &str| Error::Bindings {
+      line: i + 1,
+      message: message.to_string(),
+    };
+
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+      ["action", name] => actions.push((name, None)),
+      ["action", name, rest @ ..] => match parse_binding(rest) {
+        Some((binding, [])) => actions.push((name, Some(binding))),
+        _ => return Err(error("expected a key, mouse or gamepad binding")),
+      },
+      ["axis", name] => axes.push((name, None)),
+      ["axis", name, "buttons", rest @ ..] => match parse_binding(rest) {
+        Some((negative, rest)) => match parse_binding(rest) {
+          Some((positive, [])) => axes.push((name, Some(AxisBinding::Buttons(negative, positive)))),
+          _ => {
+            return Err(error(
+              "expected a second binding for the positive direction",
+            ))
+          }
+        },
+        None => return Err(error("expected a binding for the negative direction")),
+      },
+      ["axis", name, "gamepad", axis] => match gamepad_axis_from_name(axis) {
+        Some(axis) => axes.push((name, Some(AxisBinding::GamepadAxis(axis)))),
+        None => return Err(error("unknown gamepad axis")),
+      },
+      _ => return Err(error("expected an action or axis binding")),
+    }
+  }
+
+  for (name, _) in &actions {
+    clear_action(ctx, name);
+  }
+  for (name, _) in &axes {
+    clear_axis(ctx, name);
+  }
+  for (name, binding) in actions {
+    if let Some(binding) = binding {
+      bind_action(ctx, name, binding);
+    }
+  }
+  for (name, binding) in axes {
+    if let Some(binding) = binding {
+      bind_axis(ctx, name, binding);
+    }
+  }
+  Ok(())
+}
+
+fn binding_to_string(binding: Binding) -> String {
+  match binding {
+    Binding::Key(key) => format!("key {}", keycode_name(key)),
+    Binding::Mouse(button) => format!("mouse {:?}", button),
+    Binding::GamepadButton(button) => format!("gamepad {:?}", button),
+  }
+}
+
+/// parses a single binding from the start of `words`, returning it along
+/// with the remaining words
+fn parse_binding<'a, 'b>(words: &'a [&'b str]) -> Option<(Binding, &'a [&'b str])> {
+  match words {
+    ["key", name, rest @ ..] => Some((Binding::Key(keycode_from_name(name)?), rest)),
+    ["mouse", name, rest @ ..] => {
+      let button = match *name {
+        "Left" => MouseButton::Left,
+        "Right" => MouseButton::Right,
+        "Middle" => MouseButton::Middle,
+        _ => return None,
+      };
+      Some((Binding::Mouse(button), rest))
+    }
+    ["gamepad", name, rest @ ..] => {
+      let button = gamepad_button_from_name(name)?;
+      Some((Binding::GamepadButton(button), rest))
+    }
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn defaults() -> Ctx {
+    let mut ctx: Ctx = Default::default();
+    bind_action(&mut ctx, "jump", Binding::Key(Keycode::KeySpace));
+    bind_action(
+      &mut ctx,
+      "jump",
+      Binding::GamepadButton(GamepadButton::South),
+    );
+    bind_action(&mut ctx, "fire", Binding::Mouse(MouseButton::Left));
+    let keys = AxisBinding::Buttons(Binding::Key(Keycode::KeyA), Binding::Key(Keycode::KeyD));
+    bind_axis(&mut ctx, "move_x", keys);
+    bind_axis(
+      &mut ctx,
+      "move_x",
+      AxisBinding::GamepadAxis(GamepadAxis::LeftX),
+    );
+    ctx
+  }
+
+  #[test]
+  fn round_trips_through_text() {
+    let ctx = defaults();
+    let text = bindings_to_string(&ctx);
+
+    let mut loaded: Ctx = Default::default();
+    parse_bindings(&mut loaded, &text).unwrap();
+    assert_eq!(bindings_to_string(&loaded), text);
+    assert_eq!(
+      action_bindings(&loaded, "jump"),
+      action_bindings(&ctx, "jump")
+    );
+    assert_eq!(
+      axis_bindings(&loaded, "move_x"),
+      axis_bindings(&ctx, "move_x")
+    );
+  }
+
+  #[test]
+  fn loading_replaces_only_the_actions_in_the_file() {
+    let mut ctx = defaults();
+    let text = "# rebound\naction jump key KeyW\n\naxis move_x gamepad RightX\n";
+    parse_bindings(&mut ctx, text).unwrap();
+
+    assert_eq!(action_bindings(&ctx, "jump"), [Binding::Key(Keycode::KeyW)]);
+    let right_x = AxisBinding::GamepadAxis(GamepadAxis::RightX);
+    assert_eq!(axis_bindings(&ctx, "move_x"), [right_x]);
+    assert_eq!(
+      action_bindings(&ctx, "fire"),
+      [Binding::Mouse(MouseButton::Left)]
+    );
+  }
+
+  #[test]
+  fn reports_the_line_of_a_bad_binding() {
+    let mut ctx = defaults();
+    let text = "action jump key KeyW\n\naction jump key NotAKey\n";
+    match parse_bindings(&mut ctx, text) {
+      Err(Error::Bindings { line, .. }) => assert_eq!(line, 3),
+      other => panic!("expected a bindings error, got {:?}", other.err()),
+    }
+    // nothing is applied from a file with errors
+    assert_eq!(action_bindings(&ctx, "jump").len(), 2);
+
+    let errors = [
+      "axis move_x buttons key KeyA",
+      "axis move_x gamepad Sideways",
+      "move_x gamepad LeftX",
+    ];
+    for text in errors.iter() {
+      let text = format!("action jump key KeyW\n{}", text);
+      assert!(matches!(
+        parse_bindings(&mut ctx, &text),
+        Err(Error::Bindings { line: 2, .. })
+      ));
+    }
+  }
+
+  #[test]
+  fn cleared_actions_stay_cleared_after_loading() {
+    let mut ctx = defaults();
+    clear_action(&mut ctx, "jump");
+    clear_axis(&mut ctx, "move_x");
+    clear_action(&mut ctx, "crouch");
+    let text = bindings_to_string(&ctx);
+    assert!(text.contains("action jump\n"));
+    assert!(text.contains("action crouch\n"));
+    assert!(text.contains("axis move_x\n"));
+
+    // a fresh run declares its defaults again before loading the save
+    let mut loaded = defaults();
+    parse_bindings(&mut loaded, &text).unwrap();
+    assert!(action_bindings(&loaded, "jump").is_empty());
+    assert!(axis_bindings(&loaded, "move_x").is_empty());
+    assert_eq!(
+      action_bindings(&loaded, "fire"),
+      [Binding::Mouse(MouseButton::Left)]
+    );
+  }
+
+  #[derive(Default)]
+  struct AxisLog {
+    values: Vec<f32>,
+  }
+
+  impl KApp for AxisLog {
+    fn new() -> Self {
+      Default::default()
+    }
+
+    fn init(&mut self, ctx: &mut Ctx) {
+      let keys = AxisBinding::Buttons(Binding::Key(Keycode::KeyA), Binding::Key(Keycode::KeyD));
+      bind_axis(ctx, "move_x", keys);
+    }
+
+    fn frame(&mut self, ctx: &mut Ctx) {
+      self.values.push(axis(ctx, "move_x"));
+    }
+  }
+
+  #[test]
+  fn composes_an_axis_from_two_keys() {
+    let script = [
+      (1, ScriptEvent::KeyDown(Keycode::KeyA)),
+      (2, ScriptEvent::KeyDown(Keycode::KeyD)),
+      (3, ScriptEvent::KeyUp(Keycode::KeyA)),
+      (4, ScriptEvent::KeyUp(Keycode::KeyD)),
+    ];
+    let (log, _) = run_headless::<AxisLog>(5, &script);
+    // both held cancel out
+    assert_eq!(log.values, [0.0, -1.0, 0.0, 1.0, 0.0]);
+  }
+}
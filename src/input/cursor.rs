@@ -0,0 +1,48 @@
+//! The software cursor: a sprite drawn at the mouse position, and freezing
+//! `MouseCtx::pos` in place for mouse look.
+//!
+//! All of this is kit-side only. sokol_app 0.3 has no way to hide or
+//! capture the os cursor, so the os cursor always stays visible and stops
+//! at the window edge. The names say as much: hiding means not drawing the
+//! sprite, and locking means `MouseCtx::pos` stops following the mouse while
+//! `MouseCtx::delta` keeps reporting movement.
+
+use crate::*;
+
+/// Shows or hides the cursor sprite set with `set_cursor_sprite`. The os
+/// cursor is unaffected.
+pub fn show_cursor_sprite(ctx: &mut Ctx, visible: bool) {
+  ctx.input.mouse.cursor_sprite_visible = visible;
+}
+
+/// whether the cursor sprite is drawn, see `show_cursor_sprite`. Never while
+/// the mouse position is frozen
+pub fn cursor_sprite_shown(ctx: &Ctx) -> bool {
+  ctx.input.mouse.cursor_sprite_visible && !ctx.input.mouse.pos_frozen
+}
+
+/// Freezes `MouseCtx::pos` in place, e.g. for first person cameras. Read
+/// `MouseCtx::delta` for movement while frozen. The os cursor keeps moving
+/// and stops at the window edge, which ends `delta` too.
+pub fn freeze_mouse_pos(ctx: &mut Ctx, frozen: bool) {
+  let mouse = &mut ctx.input.mouse;
+  mouse.pos_frozen = frozen;
+  // pick up from wherever the mouse ended up while frozen
+  if !frozen {
+    if let Some(raw_pos) = mouse.raw_pos {
+      mouse.pos = raw_pos;
+    }
+  }
+}
+
+pub fn mouse_pos_frozen(ctx: &Ctx) -> bool {
+  ctx.input.mouse.pos_frozen
+}
+
+/// Draws a sprite at the mouse position on top of everything else, in window
+/// pixels. The sprite's pivot is the cursor's hotspot, so a `Pivot::Px`
+/// pointing at the arrow's tip lines clicks up with it. Pass `None` to go
+/// back to the plain os cursor.
+pub fn set_cursor_sprite(ctx: &mut Ctx, sprite: Option<Sprite>) {
+  ctx.input.mouse.cursor_sprite = sprite;
+}
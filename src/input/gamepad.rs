@@ -0,0 +1,726 @@
+//! Gamepad buttons and axes, named after their position on the controller
+//! rather than their label so that bindings work across controller families.
+//!
+//! sokol_app doesn't report gamepads, so they come from a `GamepadBackend`
+//! that's polled at the start of each frame. With the optional `gilrs`
+//! feature, real controllers are read through gilrs. Tests and headless runs
+//! can use `MockGamepads` to drive fake ones instead.
+
+use crate::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A button on a gamepad. The face buttons are named by compass direction,
+/// so `South` is A on an Xbox controller and Cross on a PlayStation one.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GamepadButton {
+  South,
+  East,
+  West,
+  North,
+  LeftShoulder,
+  RightShoulder,
+  Back,
+  Start,
+  Guide,
+  LeftStick,
+  RightStick,
+  DPadUp,
+  DPadDown,
+  DPadLeft,
+  DPadRight,
+}
+
+/// An analog input on a gamepad. Stick axes range from -1 to 1 with y
+/// pointing up, and triggers range from 0 to 1.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GamepadAxis {
+  LeftX,
+  LeftY,
+  RightX,
+  RightY,
+  LeftTrigger,
+  RightTrigger,
+}
+
+/// The kind of controller a gamepad is, guessed from its name. Mostly useful
+/// for showing the right button prompts.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum GamepadFamily {
+  Xbox,
+  PlayStation,
+  Nintendo,
+  /// anything else. Prompts use Xbox labels, since most pc gamepads copy it.
+  #[default]
+  Generic,
+}
+
+// number of variants in each enum, for indexing arrays with them
+pub(crate) const GAMEPAD_BUTTONS: usize = 15;
+pub(crate) const GAMEPAD_AXES: usize = 6;
+
+pub(crate) const ALL_GAMEPAD_BUTTONS: [GamepadButton; GAMEPAD_BUTTONS] = [
+  GamepadButton::South,
+  GamepadButton::East,
+  GamepadButton::West,
+  GamepadButton::North,
+  GamepadButton::LeftShoulder,
+  GamepadButton::RightShoulder,
+  GamepadButton::Back,
+  GamepadButton::Start,
+  GamepadButton::Guide,
+  GamepadButton::LeftStick,
+  GamepadButton::RightStick,
+  GamepadButton::DPadUp,
+  GamepadButton::DPadDown,
+  GamepadButton::DPadLeft,
+  GamepadButton::DPadRight,
+];
+
+pub(crate) const ALL_GAMEPAD_AXES: [GamepadAxis; GAMEPAD_AXES] = [
+  GamepadAxis::LeftX,
+  GamepadAxis::LeftY,
+  GamepadAxis::RightX,
+  GamepadAxis::RightY,
+  GamepadAxis::LeftTrigger,
+  GamepadAxis::RightTrigger,
+];
+
+/// the button with the given name, e.g. "South"
+pub(crate) fn gamepad_button_from_name(name: &str) -> Option<GamepadButton> {
+  ALL_GAMEPAD_BUTTONS
+    .iter()
+    .copied()
+    .find(|button| format!("{:?}", button) == name)
+}
+
+/// the axis with the given name, e.g. "LeftX"
+pub(crate) fn gamepad_axis_from_name(name: &str) -> Option<GamepadAxis> {
+  ALL_GAMEPAD_AXES
+    .iter()
+    .copied()
+    .find(|axis| format!("{:?}", axis) == name)
+}
+
+/// guesses a gamepad's family from the name its driver reports
+pub fn gamepad_family_from_name(name: &str) -> GamepadFamily {
+  let name = name.to_lowercase();
+  let matches = |words: &[&str]| words.iter().any(|word| name.contains(word));
+  if matches(&["xbox", "xinput", "microsoft"]) {
+    GamepadFamily::Xbox
+  } else if matches(&[
+    "playstation",
+    "sony",
+    "dualshock",
+    "dualsense",
+    "ps3",
+    "ps4",
+    "ps5",
+  ]) {
+    GamepadFamily::PlayStation
+  } else if matches(&["nintendo", "switch", "joy-con", "joycon", "pro controller"]) {
+    GamepadFamily::Nintendo
+  } else {
+    GamepadFamily::Generic
+  }
+}
+
+/// The label printed on a button for the given controller family, e.g. "A"
+/// or "Cross" for `South`, for showing prompts like "press A to jump"
+pub fn gamepad_glyph_name(family: GamepadFamily, button: GamepadButton) -> &'static str {
+  use GamepadButton::*;
+  match family {
+    GamepadFamily::Xbox | GamepadFamily::Generic => match button {
+      South => "A",
+      East => "B",
+      West => "X",
+      North => "Y",
+      LeftShoulder => "LB",
+      RightShoulder => "RB",
+      Back => "View",
+      Start => "Menu",
+      Guide => "Xbox",
+      LeftStick => "LS",
+      RightStick => "RS",
+      _ => dpad_glyph_name(button),
+    },
+    GamepadFamily::PlayStation => match button {
+      South => "Cross",
+      East => "Circle",
+      West => "Square",
+      North => "Triangle",
+      LeftShoulder => "L1",
+      RightShoulder => "R1",
+      Back => "Share",
+      Start => "Options",
+      Guide => "PS",
+      LeftStick => "L3",
+      RightStick => "R3",
+      _ => dpad_glyph_name(button),
+    },
+    // Nintendo swaps the face button letters around
+    GamepadFamily::Nintendo => match button {
+      South => "B",
+      East => "A",
+      West => "Y",
+      North => "X",
+      LeftShoulder => "L",
+      RightShoulder => "R",
+      Back => "-",
+      Start => "+",
+      Guide => "Home",
+      LeftStick => "LS",
+      RightStick => "RS",
+      _ => dpad_glyph_name(button),
+    },
+  }
+}
+
+/// like `gamepad_glyph_name` for axes. Sticks are named as a whole.
+pub fn gamepad_axis_glyph_name(family: GamepadFamily, axis: GamepadAxis) -> &'static str {
+  use GamepadAxis::*;
+  match (family, axis) {
+    (GamepadFamily::PlayStation, LeftTrigger) => "L2",
+    (GamepadFamily::PlayStation, RightTrigger) => "R2",
+    (GamepadFamily::Nintendo, LeftTrigger) => "ZL",
+    (GamepadFamily::Nintendo, RightTrigger) => "ZR",
+    (_, LeftTrigger) => "LT",
+    (_, RightTrigger) => "RT",
+    (_, LeftX) | (_, LeftY) => "Left Stick",
+    (_, RightX) | (_, RightY) => "Right Stick",
+  }
+}
+
+fn dpad_glyph_name(button: GamepadButton) -> &'static str {
+  match button {
+    GamepadButton::DPadUp => "D-Pad Up",
+    GamepadButton::DPadDown => "D-Pad Down",
+    GamepadButton::DPadLeft => "D-Pad Left",
+    _ => "D-Pad Right",
+  }
+}
+
+// ----------------------------------------------------------------------------
+// backends
+
+/// A change reported by a gamepad backend
+#[derive(Clone, PartialEq, Debug)]
+pub enum GamepadEvent {
+  Connected {
+    id: usize,
+    name: String,
+  },
+  Disconnected {
+    id: usize,
+  },
+  ButtonDown {
+    id: usize,
+    button: GamepadButton,
+  },
+  ButtonUp {
+    id: usize,
+    button: GamepadButton,
+  },
+  /// the raw value of an axis changed. Sticks range from -1 to 1 with y up,
+  /// and triggers from 0 to 1.
+  Axis {
+    id: usize,
+    axis: GamepadAxis,
+    value: f32,
+  },
+}
+
+/// A source of gamepad events, e.g. an os api or a fake device for tests
+pub trait GamepadBackend {
+  /// adds any events that happened since the previous poll to `events`
+  fn poll(&mut self, events: &mut Vec<GamepadEvent>);
+}
+
+/// Replaces the source of gamepad events. Call this during `KApp::init`,
+/// e.g. to drive the game with `MockGamepads` in tests.
+pub fn set_gamepad_backend(ctx: &mut Ctx, backend: Box<dyn GamepadBackend>) {
+  ctx.input.gamepads.backend = Some(backend);
+}
+
+/// A fake gamepad backend. Clones share the same event queue, so keep one
+/// clone to send events through after installing the other with
+/// `set_gamepad_backend`. Events are delivered at the start of the next frame.
+#[derive(Clone, Default)]
+pub struct MockGamepads {
+  queue: Rc<RefCell<Vec<GamepadEvent>>>,
+}
+
+impl MockGamepads {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  pub fn send(&self, event: GamepadEvent) {
+    self.queue.borrow_mut().push(event);
+  }
+
+  pub fn connect(&self, id: usize, name: &str) {
+    let name = name.to_string();
+    self.send(GamepadEvent::Connected { id, name });
+  }
+
+  pub fn disconnect(&self, id: usize) {
+    self.send(GamepadEvent::Disconnected { id });
+  }
+
+  pub fn press(&self, id: usize, button: GamepadButton) {
+    self.send(GamepadEvent::ButtonDown { id, button });
+  }
+
+  pub fn release(&self, id: usize, button: GamepadButton) {
+    self.send(GamepadEvent::ButtonUp { id, button });
+  }
+
+  pub fn set_axis(&self, id: usize, axis: GamepadAxis, value: f32) {
+    self.send(GamepadEvent::Axis { id, axis, value });
+  }
+}
+
+impl GamepadBackend for MockGamepads {
+  fn poll(&mut self, events: &mut Vec<GamepadEvent>) {
+    events.append(&mut self.queue.borrow_mut());
+  }
+}
+
+/// the backend used for real controllers, if one is available
+pub(crate) fn default_gamepad_backend() -> Option<Box<dyn GamepadBackend>> {
+  #[cfg(feature = "gilrs")]
+  {
+    match gilrs_backend::GilrsBackend::new() {
+      Ok(backend) => return Some(Box::new(backend)),
+      // games should still run without controller support
+      Err(e) => println!("Error setting up gamepads: {}", e),
+    }
+  }
+  None
+}
+
+#[cfg(feature = "gilrs")]
+mod gilrs_backend {
+  use super::*;
+  use gilrs::{Axis, Button, EventType, Gilrs, GilrsBuilder};
+
+  pub(crate) struct GilrsBackend {
+    gilrs: Gilrs,
+  }
+
+  impl GilrsBackend {
+    pub(crate) fn new() -> Result<Self, gilrs::Error> {
+      // gilrs's default filters add a dead zone of their own, which would
+      // shrink sticks before kit's configurable one is applied
+      let gilrs = GilrsBuilder::new().with_default_filters(false).build()?;
+      Ok(Self { gilrs })
+    }
+  }
+
+  impl GamepadBackend for GilrsBackend {
+    fn poll(&mut self, events: &mut Vec<GamepadEvent>) {
+      while let Some(gilrs::Event {
+        id: gilrs_id,
+        event,
+        ..
+      }) = self.gilrs.next_event()
+      {
+        let id: usize = gilrs_id.into();
+        match event {
+          EventType::Connected => {
+            let name = self.gilrs.gamepad(gilrs_id).name().to_string();
+            events.push(GamepadEvent::Connected { id, name });
+          }
+          EventType::Disconnected => events.push(GamepadEvent::Disconnected { id }),
+          EventType::ButtonPressed(button, _) => {
+            if let Some(button) = to_button(button) {
+              events.push(GamepadEvent::ButtonDown { id, button });
+            }
+          }
+          EventType::ButtonReleased(button, _) => {
+            if let Some(button) = to_button(button) {
+              events.push(GamepadEvent::ButtonUp { id, button });
+            }
+          }
+          // analog triggers are reported as buttons with a value
+          EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+            let axis = GamepadAxis::LeftTrigger;
+            events.push(GamepadEvent::Axis { id, axis, value });
+          }
+          EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+            let axis = GamepadAxis::RightTrigger;
+            events.push(GamepadEvent::Axis { id, axis, value });
+          }
+          EventType::AxisChanged(axis, value, _) => {
+            if let Some(axis) = to_axis(axis) {
+              events.push(GamepadEvent::Axis { id, axis, value });
+            }
+          }
+          _ => {}
+        }
+      }
+    }
+  }
+
+  fn to_button(button: Button) -> Option<GamepadButton> {
+    Some(match button {
+      Button::South => GamepadButton::South,
+      Button::East => GamepadButton::East,
+      Button::West => GamepadButton::West,
+      Button::North => GamepadButton::North,
+      Button::LeftTrigger => GamepadButton::LeftShoulder,
+      Button::RightTrigger => GamepadButton::RightShoulder,
+      Button::Select => GamepadButton::Back,
+      Button::Start => GamepadButton::Start,
+      Button::Mode => GamepadButton::Guide,
+      Button::LeftThumb => GamepadButton::LeftStick,
+      Button::RightThumb => GamepadButton::RightStick,
+      Button::DPadUp => GamepadButton::DPadUp,
+      Button::DPadDown => GamepadButton::DPadDown,
+      Button::DPadLeft => GamepadButton::DPadLeft,
+      Button::DPadRight => GamepadButton::DPadRight,
+      _ => return None,
+    })
+  }
+
+  fn to_axis(axis: Axis) -> Option<GamepadAxis> {
+    Some(match axis {
+      Axis::LeftStickX => GamepadAxis::LeftX,
+      Axis::LeftStickY => GamepadAxis::LeftY,
+      Axis::RightStickX => GamepadAxis::RightX,
+      Axis::RightStickY => GamepadAxis::RightY,
+      _ => return None,
+    })
+  }
+}
+
+// ----------------------------------------------------------------------------
+// processing
+
+/// reads new events from the backend into `GamepadsCtx`
+pub(crate) fn poll_gamepads(ctx: &mut Ctx) {
+  let time = ctx.time.real_elapsed;
+  let mut events = vec![];
+  if let Some(backend) = &mut ctx.input.gamepads.backend {
+    backend.poll(&mut events);
+  }
+
+  // during a replay the real pads are drained but ignored in favour of the
+  // recorded events
+  if is_replaying(ctx) {
+    events = std::mem::take(&mut ctx.replay.gamepad_events);
+  } else {
+    for event in &events {
+      replay::record_gamepad_event(ctx, event);
+    }
+  }
+
+  let gamepads = &mut ctx.input.gamepads;
+
+  // pads the player deliberately used this frame, for `preferred`
+  let mut used = None;
+  for event in events {
+    match event {
+      GamepadEvent::ButtonDown { id, .. } => used = Some(id),
+      GamepadEvent::Axis { id, value, .. } if value.abs() > ACTIVE_AXIS => used = Some(id),
+      _ => {}
+    }
+    apply_gamepad_event(gamepads, event, time);
+  }
+
+  // dead zones are applied every frame so changes to them take effect at once
+  let stick_dead_zone = gamepads.stick_dead_zone;
+  let trigger_dead_zone = gamepads.trigger_dead_zone;
+  for pad in gamepads.pads.iter_mut() {
+    for button in pad.buttons.iter_mut() {
+      button.tick(time);
+    }
+    let raw = |axis: GamepadAxis| pad.raw[axis as usize];
+    let left = vec2(raw(GamepadAxis::LeftX), raw(GamepadAxis::LeftY));
+    let right = vec2(raw(GamepadAxis::RightX), raw(GamepadAxis::RightY));
+    let left_trigger = raw(GamepadAxis::LeftTrigger);
+    let right_trigger = raw(GamepadAxis::RightTrigger);
+    pad.left_stick = radial_dead_zone(left, stick_dead_zone);
+    pad.right_stick = radial_dead_zone(right, stick_dead_zone);
+    pad.left_trigger = dead_zone(left_trigger, trigger_dead_zone);
+    pad.right_trigger = dead_zone(right_trigger, trigger_dead_zone);
+  }
+
+  if let Some(pad) = used.and_then(|id| gamepads.get(id)) {
+    let family = pad.family;
+    input::use_input(&mut ctx.input, InputType::Gamepad(family));
+  }
+}
+
+// how far an axis has to move to count as the player picking up the gamepad,
+// well past any dead zone so that stick drift doesn't count
+const ACTIVE_AXIS: f32 = 0.5;
+
+fn apply_gamepad_event(gamepads: &mut GamepadsCtx, event: GamepadEvent, time: f64) {
+  match event {
+    GamepadEvent::Connected { id, name } => {
+      while gamepads.pads.len() <= id {
+        let id = gamepads.pads.len();
+        gamepads.pads.push(GamepadCtx {
+          id,
+          ..Default::default()
+        });
+      }
+      let pad = &mut gamepads.pads[id];
+      pad.family = gamepad_family_from_name(&name);
+      pad.name = name;
+      pad.connected = true;
+      gamepads.connected.push(id);
+    }
+    GamepadEvent::Disconnected { id } => {
+      if let Some(pad) = gamepads.pads.get_mut(id) {
+        // release everything so nothing stays stuck down after reconnecting
+        for button in pad.buttons.iter_mut() {
+          if button.held {
+            button.release();
+          }
+        }
+        pad.raw = [0.0; GAMEPAD_AXES];
+        pad.connected = false;
+        gamepads.disconnected.push(id);
+      }
+    }
+    GamepadEvent::ButtonDown { id, button } => {
+      if let Some(pad) = gamepads.pads.get_mut(id) {
+        pad.buttons[button as usize].press(time, Vec2::zero());
+      }
+    }
+    GamepadEvent::ButtonUp { id, button } => {
+      if let Some(pad) = gamepads.pads.get_mut(id) {
+        pad.buttons[button as usize].release();
+      }
+    }
+    GamepadEvent::Axis { id, axis, value } => {
+      if let Some(pad) = gamepads.pads.get_mut(id) {
+        pad.raw[axis as usize] = value;
+      }
+    }
+  }
+}
+
+/// Zeroes a stick position within `dead_zone` of the center and rescales the
+/// rest of the range so that it starts from 0 at the edge of the dead zone
+pub fn radial_dead_zone(v: Vec2, dead_zone: f32) -> Vec2 {
+  let length = v.length();
+  if length <= dead_zone || length == 0.0 {
+    return Vec2::zero();
+  }
+  let scaled = ((length - dead_zone) / (1.0 - dead_zone)).min(1.0);
+  v * (scaled / length)
+}
+
+/// like `radial_dead_zone` for a single axis
+pub fn dead_zone(value: f32, dead_zone: f32) -> f32 {
+  let magnitude = value.abs();
+  if magnitude <= dead_zone {
+    return 0.0;
+  }
+  let scaled = ((magnitude - dead_zone) / (1.0 - dead_zone)).min(1.0);
+  scaled * value.signum()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// what the game saw of pad 0 on one frame
+  struct PadFrame {
+    connected: Vec<usize>,
+    disconnected: Vec<usize>,
+    is_connected: bool,
+    family: GamepadFamily,
+    south: (bool, u32, u32),
+    left_stick: Vec2,
+    left_trigger: f32,
+    preferred: InputType,
+  }
+
+  #[derive(Default)]
+  struct PadLog {
+    frames: Vec<PadFrame>,
+    /// the frame each `preferred_input_changed` call came on, and its input
+    preferred_changes: Vec<(usize, InputType)>,
+  }
+
+  impl KApp for PadLog {
+    fn new() -> Self {
+      Default::default()
+    }
+
+    fn init(&mut self, ctx: &mut Ctx) {}
+
+    fn frame(&mut self, ctx: &mut Ctx) {
+      let gamepads = &ctx.input.gamepads;
+      let pad = gamepads.pads.first();
+      let south = pad.map_or((false, 0, 0), |pad| {
+        let button = pad.button(GamepadButton::South);
+        (button.held, button.down, button.up)
+      });
+      self.frames.push(PadFrame {
+        connected: gamepads.connected.clone(),
+        disconnected: gamepads.disconnected.clone(),
+        is_connected: gamepads.get(0).is_some(),
+        family: pad.map_or(GamepadFamily::Generic, |pad| pad.family),
+        south,
+        left_stick: pad.map_or(Vec2::zero(), |pad| pad.left_stick),
+        left_trigger: pad.map_or(0.0, |pad| pad.left_trigger),
+        preferred: preferred(ctx),
+      });
+    }
+
+    fn preferred_input_changed(&mut self, ctx: &mut Ctx, input: InputType) {
+      self.preferred_changes.push((self.frames.len(), input));
+    }
+  }
+
+  fn pad(event: GamepadEvent) -> ScriptEvent {
+    ScriptEvent::Gamepad(event)
+  }
+
+  fn connect(name: &str) -> ScriptEvent {
+    let name = name.to_string();
+    pad(GamepadEvent::Connected { id: 0, name })
+  }
+
+  fn button(down: bool) -> ScriptEvent {
+    let button = GamepadButton::South;
+    if down {
+      pad(GamepadEvent::ButtonDown { id: 0, button })
+    } else {
+      pad(GamepadEvent::ButtonUp { id: 0, button })
+    }
+  }
+
+  fn axis(axis: GamepadAxis, value: f32) -> ScriptEvent {
+    pad(GamepadEvent::Axis { id: 0, axis, value })
+  }
+
+  fn assert_near(a: f32, b: f32) {
+    assert!((a - b).abs() < 1e-5, "{} isn't {}", a, b);
+  }
+
+  #[test]
+  fn dead_zones_zero_the_center_and_rescale_the_rest() {
+    assert_eq!(radial_dead_zone(Vec2::zero(), 0.2), Vec2::zero());
+    assert_eq!(radial_dead_zone(vec2(0.2, 0.0), 0.2), Vec2::zero());
+    assert_eq!(radial_dead_zone(vec2(0.0, -0.1), 0.2), Vec2::zero());
+    // rescaled from the edge of the dead zone, keeping the direction
+    let v = radial_dead_zone(vec2(0.36, 0.48), 0.2);
+    assert_near(v.length(), 0.5);
+    assert_near(v.x() / v.y(), 0.75);
+    assert_near(radial_dead_zone(vec2(0.0, 1.0), 0.2).y(), 1.0);
+    // corners of square gates don't go past the edge
+    assert_near(radial_dead_zone(vec2(1.0, 1.0), 0.2).length(), 1.0);
+    assert_near(radial_dead_zone(vec2(0.5, 0.0), 0.0).x(), 0.5);
+
+    assert_eq!(dead_zone(0.0, 0.05), 0.0);
+    assert_eq!(dead_zone(0.05, 0.05), 0.0);
+    assert_eq!(dead_zone(-0.05, 0.05), 0.0);
+    assert_near(dead_zone(-0.525, 0.05), -0.5);
+    assert_eq!(dead_zone(1.0, 0.05), 1.0);
+    assert_eq!(dead_zone(-1.5, 0.05), -1.0);
+  }
+
+  #[test]
+  fn applies_dead_zones_to_polled_axes() {
+    let script = [
+      (0, connect("Xbox Wireless Controller")),
+      (1, axis(GamepadAxis::LeftX, 0.1)),
+      (1, axis(GamepadAxis::LeftTrigger, 0.04)),
+      (2, axis(GamepadAxis::LeftX, 0.6)),
+      (2, axis(GamepadAxis::LeftTrigger, 0.525)),
+    ];
+    let (log, ctx) = run_headless::<PadLog>(3, &script);
+
+    assert_eq!(log.frames[1].left_stick, Vec2::zero());
+    assert_eq!(log.frames[1].left_trigger, 0.0);
+    assert_near(log.frames[2].left_stick.x(), 0.5);
+    assert_near(log.frames[2].left_trigger, 0.5);
+    let raw = ctx.input.gamepads.pads[0].raw_axis(GamepadAxis::LeftX);
+    assert_eq!(raw, 0.6);
+  }
+
+  #[test]
+  fn connects_and_disconnects() {
+    let script = [
+      (1, connect("Sony DualSense Wireless Controller")),
+      (3, pad(GamepadEvent::Disconnected { id: 0 })),
+      (4, connect("Sony DualSense Wireless Controller")),
+    ];
+    let (log, ctx) = run_headless::<PadLog>(5, &script);
+    let f = &log.frames;
+
+    assert!(!f[0].is_connected);
+    assert_eq!(f[1].connected, vec![0]);
+    assert!(f[1].is_connected);
+    assert_eq!(f[1].family, GamepadFamily::PlayStation);
+    // only reported on the frame it happened
+    assert!(f[2].connected.is_empty());
+    assert!(f[2].is_connected);
+    assert_eq!(f[3].disconnected, vec![0]);
+    assert!(!f[3].is_connected);
+    assert_eq!(f[4].connected, vec![0]);
+    assert!(f[4].is_connected);
+    assert_eq!(ctx.input.gamepads.pads.len(), 1);
+  }
+
+  #[test]
+  fn tracks_button_presses_and_releases() {
+    let script = [
+      (0, connect("Pro Controller")),
+      (1, button(true)),
+      (3, button(false)),
+      // pressed and released between two frames still counts as a press
+      (4, button(true)),
+      (4, button(false)),
+      (5, button(true)),
+      (6, pad(GamepadEvent::Disconnected { id: 0 })),
+    ];
+    let (log, _) = run_headless::<PadLog>(7, &script);
+    let south: Vec<(bool, u32, u32)> = log.frames.iter().map(|f| f.south).collect();
+
+    assert_eq!(
+      south,
+      vec![
+        (false, 0, 0),
+        (true, 1, 0),
+        (true, 0, 0),
+        (false, 0, 1),
+        (false, 1, 1),
+        (true, 1, 0),
+        // disconnecting lets go of everything
+        (false, 0, 1),
+      ]
+    );
+  }
+
+  #[test]
+  fn switches_preferred_input_when_the_player_picks_up_a_pad() {
+    let script = [
+      (0, connect("DualShock 4")),
+      // drift and connecting aren't the player using the pad
+      (1, axis(GamepadAxis::LeftX, 0.3)),
+      (2, button(true)),
+      (3, button(false)),
+      (4, ScriptEvent::KeyDown(Keycode::KeyA)),
+      (5, axis(GamepadAxis::LeftY, -0.9)),
+    ];
+    let (log, _) = run_headless::<PadLog>(6, &script);
+    let preferred: Vec<InputType> = log.frames.iter().map(|f| f.preferred).collect();
+
+    let pad = InputType::Gamepad(GamepadFamily::PlayStation);
+    let keyboard = InputType::MouseKeyboard;
+    assert_eq!(preferred, vec![keyboard, keyboard, pad, pad, keyboard, pad]);
+    // the hook is called on the frame the input changed, before `frame`
+    assert_eq!(
+      log.preferred_changes,
+      vec![(2, pad), (4, keyboard), (5, pad)]
+    );
+  }
+}
@@ -0,0 +1,141 @@
+//! Naming for keyboard keys, so that they can be written to and read from
+//! text files like saved input bindings.
+
+use crate::*;
+
+/// every valid keycode, for looking keys up by name
+pub(crate) const ALL_KEYCODES: [Keycode; 120] = [
+  Keycode::KeySpace,
+  Keycode::KeyApostrophe,
+  Keycode::KeyComma,
+  Keycode::KeyMinus,
+  Keycode::KeyPeriod,
+  Keycode::KeySlash,
+  Keycode::Key0,
+  Keycode::Key1,
+  Keycode::Key2,
+  Keycode::Key3,
+  Keycode::Key4,
+  Keycode::Key5,
+  Keycode::Key6,
+  Keycode::Key7,
+  Keycode::Key8,
+  Keycode::Key9,
+  Keycode::KeySemicolon,
+  Keycode::KeyEqual,
+  Keycode::KeyA,
+  Keycode::KeyB,
+  Keycode::KeyC,
+  Keycode::KeyD,
+  Keycode::KeyE,
+  Keycode::KeyF,
+  Keycode::KeyG,
+  Keycode::KeyH,
+  Keycode::KeyI,
+  Keycode::KeyJ,
+  Keycode::KeyK,
+  Keycode::KeyL,
+  Keycode::KeyM,
+  Keycode::KeyN,
+  Keycode::KeyO,
+  Keycode::KeyP,
+  Keycode::KeyQ,
+  Keycode::KeyR,
+  Keycode::KeyS,
+  Keycode::KeyT,
+  Keycode::KeyU,
+  Keycode::KeyV,
+  Keycode::KeyW,
+  Keycode::KeyX,
+  Keycode::KeyY,
+  Keycode::KeyZ,
+  Keycode::KeyLeftBracket,
+  Keycode::KeyBackslash,
+  Keycode::KeyRightBracket,
+  Keycode::KeyGraveAccent,
+  Keycode::KeyWorld1,
+  Keycode::KeyWorld2,
+  Keycode::KeyEscape,
+  Keycode::KeyEnter,
+  Keycode::KeyTab,
+  Keycode::KeyBackspace,
+  Keycode::KeyInsert,
+  Keycode::KeyDelete,
+  Keycode::KeyRight,
+  Keycode::KeyLeft,
+  Keycode::KeyDown,
+  Keycode::KeyUp,
+  Keycode::KeyPageUp,
+  Keycode::KeyPageDown,
+  Keycode::KeyHome,
+  Keycode::KeyEnd,
+  Keycode::KeyCapsLock,
+  Keycode::KeyScrollLock,
+  Keycode::KeyNumLock,
+  Keycode::KeyPrintScreen,
+  Keycode::KeyPause,
+  Keycode::KeyF1,
+  Keycode::KeyF2,
+  Keycode::KeyF3,
+  Keycode::KeyF4,
+  Keycode::KeyF5,
+  Keycode::KeyF6,
+  Keycode::KeyF7,
+  Keycode::KeyF8,
+  Keycode::KeyF9,
+  Keycode::KeyF10,
+  Keycode::KeyF11,
+  Keycode::KeyF12,
+  Keycode::KeyF13,
+  Keycode::KeyF14,
+  Keycode::KeyF15,
+  Keycode::KeyF16,
+  Keycode::KeyF17,
+  Keycode::KeyF18,
+  Keycode::KeyF19,
+  Keycode::KeyF20,
+  Keycode::KeyF21,
+  Keycode::KeyF22,
+  Keycode::KeyF23,
+  Keycode::KeyF24,
+  Keycode::KeyF25,
+  Keycode::KeyKP0,
+  Keycode::KeyKP1,
+  Keycode::KeyKP2,
+  Keycode::KeyKP3,
+  Keycode::KeyKP4,
+  Keycode::KeyKP5,
+  Keycode::KeyKP6,
+  Keycode::KeyKP7,
+  Keycode::KeyKP8,
+  Keycode::KeyKP9,
+  Keycode::KeyKPDecimal,
+  Keycode::KeyKPDivide,
+  Keycode::KeyKPMultiply,
+  Keycode::KeyKPSubtract,
+  Keycode::KeyKPAdd,
+  Keycode::KeyKPEnter,
+  Keycode::KeyKPEqual,
+  Keycode::KeyLeftShift,
+  Keycode::KeyLeftControl,
+  Keycode::KeyLeftAlt,
+  Keycode::KeyLeftSuper,
+  Keycode::KeyRightShift,
+  Keycode::KeyRightControl,
+  Keycode::KeyRightAlt,
+  Keycode::KeyRightSuper,
+  Keycode::KeyMenu,
+];
+
+/// a stable name for the key, e.g. "KeySpace"
+pub fn keycode_name(key: Keycode) -> String {
+  format!("{:?}", key)
+}
+
+/// the key with the given name, as produced by `keycode_name`
+pub fn keycode_from_name(name: &str) -> Option<Keycode> {
+  ALL_KEYCODES
+    .iter()
+    .copied()
+    .find(|key| keycode_name(*key) == name)
+}
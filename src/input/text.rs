@@ -0,0 +1,509 @@
+//! Text entry. Typed characters and editing keys are collected into
+//! `TextInputCtx::events` each frame, and `TextField` applies them to an
+//! editable string for things like name entry and chat.
+
+use crate::*;
+use std::ops::Range;
+
+/// A single piece of text input
+#[derive(Clone, PartialEq, Debug)]
+pub enum TextInput {
+  /// a typed character, already combined by the os keyboard layout
+  Char(char),
+  Backspace,
+  Delete,
+  Enter,
+  Tab,
+  Left,
+  Right,
+  Up,
+  Down,
+  Home,
+  End,
+  SelectAll,
+  /// text being composed by an input method editor that hasn't been
+  /// committed yet. Not currently sent, since sokol_app doesn't report
+  /// composition; committed text still arrives as `Char`s.
+  Composition(String),
+}
+
+/// A piece of text input along with the modifier keys held when it happened,
+/// e.g. shift to extend a selection while moving the cursor
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextEvent {
+  pub input: TextInput,
+  pub modifiers: Modifiers,
+}
+
+/// the editing command for a key press, if it has one
+pub(crate) fn text_input_from_key(key: Keycode, modifiers: Modifiers) -> Option<TextInput> {
+  let shortcut = modifiers.intersects(Modifiers::CONTROL | Modifiers::SUPER);
+  Some(match key {
+    Keycode::KeyBackspace => TextInput::Backspace,
+    Keycode::KeyDelete => TextInput::Delete,
+    Keycode::KeyEnter | Keycode::KeyKPEnter => TextInput::Enter,
+    Keycode::KeyTab => TextInput::Tab,
+    Keycode::KeyLeft => TextInput::Left,
+    Keycode::KeyRight => TextInput::Right,
+    Keycode::KeyUp => TextInput::Up,
+    Keycode::KeyDown => TextInput::Down,
+    Keycode::KeyHome => TextInput::Home,
+    Keycode::KeyEnd => TextInput::End,
+    Keycode::KeyA if shortcut => TextInput::SelectAll,
+    _ => return None,
+  })
+}
+
+/// the typed character for a sokol char event, if it's printable
+pub(crate) fn text_input_from_char(code: u32, modifiers: Modifiers) -> Option<TextInput> {
+  // shortcuts like ctrl+a shouldn't also type a letter. Windows reports
+  // AltGr as ctrl+alt though, and it types characters like @ and € on many
+  // keyboard layouts.
+  let alt_gr = modifiers.contains(Modifiers::CONTROL | Modifiers::ALT);
+  let shortcut =
+    modifiers.contains(Modifiers::SUPER) || (modifiers.contains(Modifiers::CONTROL) && !alt_gr);
+  if shortcut {
+    return None;
+  }
+  let c = std::char::from_u32(code)?;
+  // control characters like backspace also arrive as key presses
+  if c.is_control() {
+    return None;
+  }
+  Some(TextInput::Char(c))
+}
+
+// ----------------------------------------------------------------------------
+// text fields
+
+/// What happened to a `TextField` after applying input to it
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TextFieldResult {
+  /// nothing the game needs to know about
+  Unchanged,
+  /// the text changed
+  Changed,
+  /// enter was pressed
+  Submitted,
+}
+
+/// The editing state of a single line of text: the text itself, the cursor,
+/// and an optional selection. Positions are byte offsets into the text and
+/// always sit on character boundaries.
+#[derive(Clone, Default, Debug)]
+pub struct TextField {
+  text: String,
+  cursor: usize,
+  /// the other end of the selection from the cursor, if there is one
+  anchor: Option<usize>,
+  /// the most characters the field will hold
+  pub max_chars: Option<usize>,
+  /// uncommitted input method text to draw at the cursor
+  pub composition: String,
+}
+
+impl TextField {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  /// a field holding the given text, with the cursor at the end
+  pub fn with_text(text: &str) -> Self {
+    let mut field = Self::new();
+    field.set_text(text);
+    field
+  }
+
+  pub fn text(&self) -> &str {
+    &self.text
+  }
+
+  /// replaces the text, moving the cursor to the end and clearing the selection
+  pub fn set_text(&mut self, text: &str) {
+    self.text = text.to_string();
+    self.cursor = self.text.len();
+    self.anchor = None;
+  }
+
+  /// the cursor position, as a byte offset into the text
+  pub fn cursor(&self) -> usize {
+    self.cursor
+  }
+
+  /// the selected byte range, if anything is selected
+  pub fn selection(&self) -> Option<Range<usize>> {
+    let anchor = self.anchor?;
+    if anchor == self.cursor {
+      return None;
+    }
+    Some(anchor.min(self.cursor)..anchor.max(self.cursor))
+  }
+
+  pub fn selected_text(&self) -> &str {
+    match self.selection() {
+      Some(range) => &self.text[range],
+      None => "",
+    }
+  }
+
+  /// Applies this frame's text input to the field. Call it each frame while
+  /// the field has focus.
+  pub fn update(&mut self, ctx: &Ctx) -> TextFieldResult {
+    let mut result = TextFieldResult::Unchanged;
+    for event in &ctx.input.text.events {
+      match self.apply(event) {
+        TextFieldResult::Unchanged => {}
+        TextFieldResult::Changed if result == TextFieldResult::Submitted => {}
+        r => result = r,
+      }
+    }
+    result
+  }
+
+  /// applies a single text event to the field
+  pub fn apply(&mut self, event: &TextEvent) -> TextFieldResult {
+    let select = event.modifiers.contains(Modifiers::SHIFT);
+    let by_word = event
+      .modifiers
+      .intersects(Modifiers::CONTROL | Modifiers::ALT);
+    let before = self.text.clone();
+
+    match &event.input {
+      TextInput::Char(c) => self.insert(&c.to_string()),
+      TextInput::Backspace => self.backspace(by_word),
+      TextInput::Delete => self.delete(by_word),
+      TextInput::Enter => return TextFieldResult::Submitted,
+      TextInput::Left => self.move_left(select, by_word),
+      TextInput::Right => self.move_right(select, by_word),
+      TextInput::Home | TextInput::Up => self.move_to(0, select),
+      TextInput::End | TextInput::Down => self.move_to(self.text.len(), select),
+      TextInput::SelectAll => self.select_all(),
+      TextInput::Composition(text) => self.composition = text.clone(),
+      TextInput::Tab => {}
+    }
+
+    if self.text != before {
+      TextFieldResult::Changed
+    } else {
+      TextFieldResult::Unchanged
+    }
+  }
+
+  // --------------------------------------------------------------------------
+  // editing commands
+
+  /// inserts text at the cursor, replacing the selection if there is one
+  pub fn insert(&mut self, text: &str) {
+    self.delete_selection();
+    let mut text = text;
+    if let Some(max_chars) = self.max_chars {
+      let room = max_chars.saturating_sub(self.text.chars().count());
+      if let Some((end, _)) = text.char_indices().nth(room) {
+        text = &text[..end];
+      }
+    }
+    self.text.insert_str(self.cursor, text);
+    self.cursor += text.len();
+    self.composition.clear();
+  }
+
+  /// deletes the selection, or else the character or word before the cursor
+  pub fn backspace(&mut self, by_word: bool) {
+    if self.delete_selection() {
+      return;
+    }
+    let start = if by_word {
+      self.prev_word(self.cursor)
+    } else {
+      self.prev_char(self.cursor)
+    };
+    self.text.replace_range(start..self.cursor, "");
+    self.cursor = start;
+  }
+
+  /// deletes the selection, or else the character or word after the cursor
+  pub fn delete(&mut self, by_word: bool) {
+    if self.delete_selection() {
+      return;
+    }
+    let end = if by_word {
+      self.next_word(self.cursor)
+    } else {
+      self.next_char(self.cursor)
+    };
+    self.text.replace_range(self.cursor..end, "");
+  }
+
+  /// moves the cursor back a character or word. Without `select`, a
+  /// selection collapses to its start instead.
+  pub fn move_left(&mut self, select: bool, by_word: bool) {
+    if let (false, Some(range)) = (select, self.selection()) {
+      self.move_to(range.start, false);
+      return;
+    }
+    let to = if by_word {
+      self.prev_word(self.cursor)
+    } else {
+      self.prev_char(self.cursor)
+    };
+    self.move_to(to, select);
+  }
+
+  /// moves the cursor forward a character or word. Without `select`, a
+  /// selection collapses to its end instead.
+  pub fn move_right(&mut self, select: bool, by_word: bool) {
+    if let (false, Some(range)) = (select, self.selection()) {
+      self.move_to(range.end, false);
+      return;
+    }
+    let to = if by_word {
+      self.next_word(self.cursor)
+    } else {
+      self.next_char(self.cursor)
+    };
+    self.move_to(to, select);
+  }
+
+  /// Moves the cursor to a byte offset, which is clamped to the text and
+  /// snapped back to a character boundary. With `select`, the selection
+  /// stretches to the new position.
+  pub fn move_to(&mut self, pos: usize, select: bool) {
+    let mut pos = pos.min(self.text.len());
+    while !self.text.is_char_boundary(pos) {
+      pos -= 1;
+    }
+    if select {
+      self.anchor = self.anchor.or(Some(self.cursor));
+    } else {
+      self.anchor = None;
+    }
+    self.cursor = pos;
+  }
+
+  pub fn select_all(&mut self) {
+    self.anchor = Some(0);
+    self.cursor = self.text.len();
+  }
+
+  pub fn clear(&mut self) {
+    self.set_text("");
+    self.composition.clear();
+  }
+
+  /// removes the selected text. Returns false if nothing was selected.
+  fn delete_selection(&mut self) -> bool {
+    let range = match self.selection() {
+      Some(range) => range,
+      None => {
+        self.anchor = None;
+        return false;
+      }
+    };
+    self.text.replace_range(range.clone(), "");
+    self.cursor = range.start;
+    self.anchor = None;
+    true
+  }
+
+  // --------------------------------------------------------------------------
+  // cursor motion helpers
+
+  fn prev_char(&self, pos: usize) -> usize {
+    self.text[..pos]
+      .char_indices()
+      .next_back()
+      .map_or(0, |(i, _)| i)
+  }
+
+  fn next_char(&self, pos: usize) -> usize {
+    self.text[pos..]
+      .chars()
+      .next()
+      .map_or(pos, |c| pos + c.len_utf8())
+  }
+
+  /// the start of the word before `pos`, skipping any whitespace first
+  fn prev_word(&self, pos: usize) -> usize {
+    let mut pos = pos;
+    let mut in_word = false;
+    while pos > 0 {
+      let prev = self.prev_char(pos);
+      let whitespace = self.text[prev..pos].chars().all(char::is_whitespace);
+      if in_word && whitespace {
+        break;
+      }
+      in_word |= !whitespace;
+      pos = prev;
+    }
+    pos
+  }
+
+  /// the end of the word after `pos`, skipping any whitespace first
+  fn next_word(&self, pos: usize) -> usize {
+    let mut pos = pos;
+    let mut in_word = false;
+    while pos < self.text.len() {
+      let next = self.next_char(pos);
+      let whitespace = self.text[pos..next].chars().all(char::is_whitespace);
+      if in_word && whitespace {
+        break;
+      }
+      in_word |= !whitespace;
+      pos = next;
+    }
+    pos
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn typed(c: char, modifiers: Modifiers) -> Option<char> {
+    match text_input_from_char(c as u32, modifiers) {
+      Some(TextInput::Char(c)) => Some(c),
+      _ => None,
+    }
+  }
+
+  #[test]
+  fn types_characters_except_for_shortcuts() {
+    assert_eq!(typed('a', Modifiers::empty()), Some('a'));
+    assert_eq!(typed('A', Modifiers::SHIFT), Some('A'));
+    assert_eq!(typed('a', Modifiers::CONTROL), None);
+    assert_eq!(typed('a', Modifiers::SUPER), None);
+    assert_eq!(typed('\u{8}', Modifiers::empty()), None);
+  }
+
+  #[test]
+  fn types_alt_gr_characters() {
+    let alt_gr = Modifiers::CONTROL | Modifiers::ALT;
+    assert_eq!(typed('@', alt_gr), Some('@'));
+    assert_eq!(typed('€', alt_gr | Modifiers::SHIFT), Some('€'));
+    assert_eq!(typed('@', alt_gr | Modifiers::SUPER), None);
+  }
+
+  fn press(field: &mut TextField, input: TextInput, modifiers: Modifiers) -> TextFieldResult {
+    field.apply(&TextEvent { input, modifiers })
+  }
+
+  fn type_str(field: &mut TextField, text: &str) {
+    for c in text.chars() {
+      press(field, TextInput::Char(c), Modifiers::empty());
+    }
+  }
+
+  #[test]
+  fn moves_by_word() {
+    let word = Modifiers::CONTROL;
+    let mut field = TextField::with_text("hello  big world");
+    press(&mut field, TextInput::Left, word);
+    assert_eq!(field.cursor(), 11);
+    // skips the whitespace before the previous word
+    press(&mut field, TextInput::Left, word);
+    assert_eq!(field.cursor(), 7);
+    press(&mut field, TextInput::Left, word);
+    assert_eq!(field.cursor(), 0);
+    press(&mut field, TextInput::Left, word);
+    assert_eq!(field.cursor(), 0);
+
+    press(&mut field, TextInput::Right, word);
+    assert_eq!(field.cursor(), 5);
+    press(&mut field, TextInput::Right, word);
+    assert_eq!(field.cursor(), 10);
+    // alt moves by word too, as on macos
+    press(&mut field, TextInput::Right, Modifiers::ALT);
+    assert_eq!(field.cursor(), 16);
+  }
+
+  #[test]
+  fn selects_with_shift() {
+    let mut field = TextField::with_text("héllo world");
+    press(&mut field, TextInput::Left, Modifiers::SHIFT);
+    press(
+      &mut field,
+      TextInput::Left,
+      Modifiers::SHIFT | Modifiers::CONTROL,
+    );
+    assert_eq!(field.selected_text(), "world");
+    // the anchor stays put while the cursor moves back over it
+    press(&mut field, TextInput::End, Modifiers::SHIFT);
+    assert_eq!(field.selection(), None);
+    press(&mut field, TextInput::Home, Modifiers::SHIFT);
+    assert_eq!(field.selected_text(), "héllo world");
+    // moving without shift collapses the selection to its end
+    press(&mut field, TextInput::Right, Modifiers::empty());
+    assert_eq!(field.cursor(), field.text().len());
+    assert_eq!(field.selection(), None);
+
+    press(&mut field, TextInput::Home, Modifiers::empty());
+    press(&mut field, TextInput::Right, Modifiers::SHIFT);
+    press(&mut field, TextInput::Right, Modifiers::SHIFT);
+    assert_eq!(field.selected_text(), "hé");
+    // typing replaces the selection
+    type_str(&mut field, "j");
+    assert_eq!(field.text(), "jllo world");
+    assert_eq!(field.cursor(), 1);
+  }
+
+  #[test]
+  fn deletes_by_word() {
+    let word = Modifiers::CONTROL;
+    let mut field = TextField::with_text("one two  three");
+    let result = press(&mut field, TextInput::Backspace, word);
+    assert_eq!(result, TextFieldResult::Changed);
+    assert_eq!(field.text(), "one two  ");
+    press(&mut field, TextInput::Backspace, word);
+    assert_eq!(field.text(), "one ");
+
+    field.move_to(0, false);
+    press(&mut field, TextInput::Delete, word);
+    assert_eq!(field.text(), " ");
+    press(&mut field, TextInput::Delete, word);
+    assert_eq!(field.text(), "");
+    let result = press(&mut field, TextInput::Delete, word);
+    assert_eq!(result, TextFieldResult::Unchanged);
+
+    // without the modifier, a character at a time
+    let mut field = TextField::with_text("añb");
+    press(&mut field, TextInput::Backspace, Modifiers::empty());
+    press(&mut field, TextInput::Backspace, Modifiers::empty());
+    assert_eq!(field.text(), "a");
+  }
+
+  #[test]
+  fn select_all_then_type_replaces_everything() {
+    let mut field = TextField::with_text("old name");
+    field.move_to(2, false);
+    press(&mut field, TextInput::SelectAll, Modifiers::CONTROL);
+    assert_eq!(field.selected_text(), "old name");
+    type_str(&mut field, "new");
+    assert_eq!(field.text(), "new");
+    assert_eq!(field.selection(), None);
+
+    press(&mut field, TextInput::SelectAll, Modifiers::CONTROL);
+    press(&mut field, TextInput::Backspace, Modifiers::empty());
+    assert_eq!(field.text(), "");
+  }
+
+  #[test]
+  fn max_chars_counts_characters_not_bytes() {
+    let mut field = TextField::new();
+    field.max_chars = Some(4);
+    type_str(&mut field, "日本語テキスト");
+    assert_eq!(field.text(), "日本語テ");
+
+    // pasting cuts at a character boundary
+    field.set_text("ab");
+    field.insert("€😀ü");
+    assert_eq!(field.text(), "ab€😀");
+    field.insert("x");
+    assert_eq!(field.text(), "ab€😀");
+
+    // replacing a selection makes room first
+    field.move_to(2, true);
+    assert_eq!(field.selected_text(), "€😀");
+    field.insert("éé");
+    assert_eq!(field.text(), "abéé");
+  }
+}
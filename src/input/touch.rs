@@ -0,0 +1,415 @@
+//! Touch tracking and gesture recognition. Raw fingers are collected from
+//! sokol's touch events into `TouchCtx::touches`, then once per frame the
+//! recognizers below turn them into `Gesture`s.
+
+use crate::*;
+use sokol::app::*;
+use std::f32::consts::PI;
+
+/// A gesture recognized from the fingers on the screen. Positions are in
+/// window coordinates, like `MouseCtx::pos`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Gesture {
+  /// a finger touched down and lifted quickly without moving
+  Tap { pos: Vec2 },
+  /// a second tap close to the previous one. The second tap is also
+  /// reported as a `Tap`.
+  DoubleTap { pos: Vec2 },
+  /// a finger was held still for `TouchCtx::long_press_duration`. Reported
+  /// once, while the finger is still down.
+  LongPress { pos: Vec2 },
+  /// a lone finger moved further than `TouchCtx::slop`
+  DragStart { id: usize, pos: Vec2 },
+  /// a dragging finger moved by `delta` this frame
+  Drag { id: usize, pos: Vec2, delta: Vec2 },
+  /// a dragging finger lifted
+  DragEnd { id: usize, pos: Vec2 },
+  /// two fingers moved apart or together. `scale` is the ratio of their
+  /// distance this frame to their distance the previous frame.
+  Pinch { center: Vec2, scale: f32 },
+  /// two fingers twisted around each other. `angle` is the change since the
+  /// previous frame in radians, counter-clockwise on screen.
+  Rotate { center: Vec2, angle: f32 },
+}
+
+/// updates touches from a sokol touch event
+pub(crate) fn handle_touch_event(touches: &mut TouchCtx, event: &SAppEvent, time: f64) {
+  let points = &event.touches[..event.num_touches as usize];
+  for point in points.iter().filter(|point| point.changed) {
+    let id = point.identifier;
+    let pos = vec2(point.pos_x, point.pos_y);
+
+    if event.event_type == SAppEventType::TouchesBegan {
+      // os ids get reused, so a finger that lifted earlier this frame can
+      // share its id with this one. The new finger replaces it.
+      touches.touches.retain(|touch| touch.id != id);
+      touches.touches.push(Touch {
+        id,
+        phase: TouchPhase::Began,
+        pos,
+        delta: Vec2::zero(),
+        start_pos: pos,
+        start_time: time,
+        prev_pos: pos,
+        dragging: false,
+        long_pressed: false,
+        multi: false,
+      });
+      continue;
+    }
+
+    let touch = match touches.touches.iter_mut().find(|touch| touch.id == id) {
+      Some(touch) => touch,
+      None => continue,
+    };
+    touch.pos = pos;
+    touch.delta = pos - touch.prev_pos;
+    touch.phase = match event.event_type {
+      // a touch that begins and moves in the same frame is still new
+      SAppEventType::TouchesMoved if touch.phase == TouchPhase::Began => TouchPhase::Began,
+      SAppEventType::TouchesMoved => TouchPhase::Moved,
+      SAppEventType::TouchesEnded => TouchPhase::Ended,
+      _ => TouchPhase::Cancelled,
+    };
+  }
+}
+
+/// runs the gesture recognizers over this frame's touches
+pub(crate) fn recognize_gestures(ctx: &mut Ctx) {
+  let now = ctx.time.real_elapsed;
+  let touches = &mut ctx.input.touches;
+  let mut gestures = vec![];
+
+  // fingers that have ever been part of a two finger gesture don't also
+  // tap or drag, since lifting them one at a time would look like a tap
+  let down = touches
+    .touches
+    .iter()
+    .filter(|t| t.phase != TouchPhase::Cancelled)
+    .count();
+  if down >= 2 {
+    for touch in touches.touches.iter_mut() {
+      touch.multi = true;
+    }
+  }
+
+  if let [a, b] = touches.touches.as_slice() {
+    two_finger_gestures(a, b, &mut gestures);
+  }
+
+  let slop = touches.slop;
+  for touch in touches.touches.iter_mut() {
+    if touch.multi || touch.phase == TouchPhase::Cancelled {
+      continue;
+    }
+
+    let moved_far = (touch.pos - touch.start_pos).length() > slop;
+    if moved_far && !touch.dragging && !touch.long_pressed {
+      touch.dragging = true;
+      gestures.push(Gesture::DragStart {
+        id: touch.id,
+        pos: touch.start_pos,
+      });
+    }
+
+    if touch.dragging {
+      if touch.delta != Vec2::zero() {
+        gestures.push(Gesture::Drag {
+          id: touch.id,
+          pos: touch.pos,
+          delta: touch.delta,
+        });
+      }
+      if touch.phase == TouchPhase::Ended {
+        gestures.push(Gesture::DragEnd {
+          id: touch.id,
+          pos: touch.pos,
+        });
+      }
+      continue;
+    }
+
+    let held_for = (now - touch.start_time) as f32;
+    if touch.is_down() && !touch.long_pressed && held_for >= touches.long_press_duration {
+      touch.long_pressed = true;
+      gestures.push(Gesture::LongPress { pos: touch.pos });
+    }
+
+    if touch.phase == TouchPhase::Ended && !touch.long_pressed && held_for <= touches.tap_duration {
+      let pos = touch.pos;
+      gestures.push(Gesture::Tap { pos });
+      match touches.last_tap {
+        Some((time, last_pos))
+          if (now - time) as f32 <= touches.double_tap_interval
+            && (pos - last_pos).length() <= slop =>
+        {
+          gestures.push(Gesture::DoubleTap { pos });
+          // a third tap starts a new pair rather than double tapping again
+          touches.last_tap = None;
+        }
+        _ => touches.last_tap = Some((now, pos)),
+      }
+    }
+  }
+
+  touches.gestures.append(&mut gestures);
+}
+
+/// recognizes pinches and rotations from the movement of two fingers since
+/// the previous frame
+fn two_finger_gestures(a: &Touch, b: &Touch, gestures: &mut Vec<Gesture>) {
+  if a.phase == TouchPhase::Began || b.phase == TouchPhase::Began {
+    return;
+  }
+  if a.delta == Vec2::zero() && b.delta == Vec2::zero() {
+    return;
+  }
+
+  let prev = b.prev_pos - a.prev_pos;
+  let current = b.pos - a.pos;
+  let center = (a.pos + b.pos) * 0.5;
+  if prev.length() == 0.0 || current.length() == 0.0 {
+    return;
+  }
+
+  let scale = current.length() / prev.length();
+  if scale != 1.0 {
+    gestures.push(Gesture::Pinch { center, scale });
+  }
+
+  // window y points down, so flip it to get counter-clockwise angles
+  let mut angle = current.y().atan2(current.x()) - prev.y().atan2(prev.x());
+  if angle > PI {
+    angle -= 2.0 * PI;
+  } else if angle < -PI {
+    angle += 2.0 * PI;
+  }
+  if angle != 0.0 {
+    gestures.push(Gesture::Rotate {
+      center,
+      angle: -angle,
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// the gestures and fingers the game saw on each frame
+  #[derive(Default)]
+  struct GestureLog {
+    gestures: Vec<Vec<Gesture>>,
+    touches: Vec<Vec<(usize, TouchPhase)>>,
+  }
+
+  impl KApp for GestureLog {
+    fn new() -> Self {
+      Default::default()
+    }
+
+    fn init(&mut self, ctx: &mut Ctx) {}
+
+    fn frame(&mut self, ctx: &mut Ctx) {
+      let touches = &ctx.input.touches;
+      self.gestures.push(touches.gestures.clone());
+      let fingers = touches.iter().map(|t| (t.id, t.phase)).collect();
+      self.touches.push(fingers);
+    }
+  }
+
+  impl GestureLog {
+    /// every gesture along with the frame it came on
+    fn all(&self) -> Vec<(usize, Gesture)> {
+      let frames = self.gestures.iter().enumerate();
+      frames
+        .flat_map(|(i, gestures)| gestures.iter().map(move |g| (i, *g)))
+        .collect()
+    }
+  }
+
+  fn began(id: usize, x: f32, y: f32) -> ScriptEvent {
+    ScriptEvent::TouchBegan(id, x, y)
+  }
+
+  fn moved(id: usize, x: f32, y: f32) -> ScriptEvent {
+    ScriptEvent::TouchMoved(id, x, y)
+  }
+
+  fn ended(id: usize, x: f32, y: f32) -> ScriptEvent {
+    ScriptEvent::TouchEnded(id, x, y)
+  }
+
+  fn assert_near(a: f32, b: f32) {
+    assert!((a - b).abs() < 1e-5, "{} isn't {}", a, b);
+  }
+
+  // headless frames are 1/60s apart, and a finger's time down counts from
+  // the start of the frame it touched down on to the end of the frame it
+  // lifted on
+
+  #[test]
+  fn taps_quickly_lifted_fingers() {
+    let script = [(0, began(0, 10.0, 20.0)), (2, ended(0, 12.0, 20.0))];
+    let (log, _) = run_headless::<GestureLog>(4, &script);
+    let pos = vec2(12.0, 20.0);
+    assert_eq!(log.all(), [(2, Gesture::Tap { pos })]);
+
+    // held past `tap_duration`, 0.3s
+    let script = [(0, began(0, 10.0, 20.0)), (20, ended(0, 10.0, 20.0))];
+    let (log, _) = run_headless::<GestureLog>(22, &script);
+    assert_eq!(log.all(), []);
+  }
+
+  #[test]
+  fn double_taps_close_quick_taps() {
+    let pos = vec2(10.0, 20.0);
+    let tap = |id, at| {
+      vec![
+        (at, began(id, pos.x(), pos.y())),
+        (at + 1, ended(id, pos.x(), pos.y())),
+      ]
+    };
+    let script = [tap(0, 0), tap(1, 5), tap(2, 10)].concat();
+    let (log, _) = run_headless::<GestureLog>(12, &script);
+    assert_eq!(
+      log.all(),
+      [
+        (1, Gesture::Tap { pos }),
+        (6, Gesture::Tap { pos }),
+        (6, Gesture::DoubleTap { pos }),
+        // a third tap starts a new pair
+        (11, Gesture::Tap { pos }),
+      ]
+    );
+
+    // past `double_tap_interval`, 0.3s
+    let script = [tap(0, 0), tap(1, 30)].concat();
+    let (log, _) = run_headless::<GestureLog>(32, &script);
+    let double_taps = log.all().into_iter();
+    let double_taps = double_taps.filter(|(_, g)| matches!(g, Gesture::DoubleTap { .. }));
+    assert_eq!(double_taps.count(), 0);
+
+    // further apart than `slop`
+    let script = [
+      (0, began(0, 10.0, 20.0)),
+      (1, ended(0, 10.0, 20.0)),
+      (3, began(1, 30.0, 20.0)),
+      (4, ended(1, 30.0, 20.0)),
+    ];
+    let (log, _) = run_headless::<GestureLog>(5, &script);
+    let taps = log.all();
+    assert_eq!(taps.len(), 2);
+    assert!(taps.iter().all(|(_, g)| matches!(g, Gesture::Tap { .. })));
+  }
+
+  #[test]
+  fn long_presses_once_after_the_duration() {
+    let script = [
+      (0, began(0, 10.0, 20.0)),
+      (10, moved(0, 15.0, 20.0)),
+      (40, ended(0, 15.0, 20.0)),
+    ];
+    let (log, _) = run_headless::<GestureLog>(42, &script);
+    // `long_press_duration` is 0.5s, reached at the end of frame 29. Moving
+    // within `slop` doesn't cancel it, and lifting doesn't also tap.
+    let pos = vec2(15.0, 20.0);
+    assert_eq!(log.all(), [(29, Gesture::LongPress { pos })]);
+  }
+
+  #[test]
+  fn drags_once_a_finger_leaves_the_slop() {
+    let script = [
+      (0, began(3, 0.0, 0.0)),
+      (1, moved(3, 5.0, 0.0)),
+      (2, moved(3, 20.0, 0.0)),
+      (3, moved(3, 20.0, 4.0)),
+      (4, ended(3, 20.0, 4.0)),
+    ];
+    let (log, _) = run_headless::<GestureLog>(6, &script);
+    assert_eq!(
+      log.all(),
+      [
+        (
+          2,
+          Gesture::DragStart {
+            id: 3,
+            pos: vec2(0.0, 0.0)
+          }
+        ),
+        (
+          2,
+          Gesture::Drag {
+            id: 3,
+            pos: vec2(20.0, 0.0),
+            delta: vec2(15.0, 0.0)
+          }
+        ),
+        (
+          3,
+          Gesture::Drag {
+            id: 3,
+            pos: vec2(20.0, 4.0),
+            delta: vec2(0.0, 4.0)
+          }
+        ),
+        (
+          4,
+          Gesture::DragEnd {
+            id: 3,
+            pos: vec2(20.0, 4.0)
+          }
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn pinches_and_rotates_with_two_fingers() {
+    let script = [
+      (0, began(0, 0.0, 0.0)),
+      (0, began(1, 100.0, 0.0)),
+      // apart along x
+      (1, moved(1, 200.0, 0.0)),
+      // a quarter turn counter-clockwise on screen, where y points down
+      (2, moved(1, 0.0, -200.0)),
+      (3, ended(0, 0.0, 0.0)),
+      (4, ended(1, 0.0, -200.0)),
+    ];
+    let (log, _) = run_headless::<GestureLog>(6, &script);
+    let gestures = log.all();
+    assert_eq!(gestures.len(), 2, "{:?}", gestures);
+
+    assert_eq!(
+      gestures[0],
+      (
+        1,
+        Gesture::Pinch {
+          center: vec2(100.0, 0.0),
+          scale: 2.0
+        }
+      )
+    );
+    match gestures[1] {
+      (2, Gesture::Rotate { center, angle }) => {
+        assert_eq!(center, vec2(0.0, -100.0));
+        assert_near(angle, PI / 2.0);
+      }
+      other => panic!("expected a rotation, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn a_reused_id_replaces_the_finger_that_just_lifted() {
+    let script = [
+      (0, began(0, 10.0, 10.0)),
+      (5, ended(0, 10.0, 10.0)),
+      (5, began(0, 50.0, 50.0)),
+    ];
+    let (log, ctx) = run_headless::<GestureLog>(7, &script);
+    assert_eq!(log.touches[5], [(0, TouchPhase::Began)]);
+    assert_eq!(log.touches[6], [(0, TouchPhase::Stationary)]);
+    let touch = ctx.input.touches.get(0).unwrap();
+    assert_eq!(touch.start_pos, vec2(50.0, 50.0));
+  }
+}
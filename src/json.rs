@@ -0,0 +1,317 @@
+//! A small JSON reader, enough for the data files kit loads, like
+//! spritesheets exported from Aseprite or TexturePacker.
+
+use std::str::Chars;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Json {
+  Null,
+  Bool(bool),
+  Num(f64),
+  Str(String),
+  Arr(Vec<Json>),
+  /// keys in the order they appear in the file
+  Obj(Vec<(String, Json)>),
+}
+
+const NULL: Json = Json::Null;
+
+impl Json {
+  /// the value under `key`, or `Null` if there isn't one or this isn't an
+  /// object, so lookups can be chained
+  pub fn get(&self, key: &str) -> &Json {
+    match self {
+      Json::Obj(fields) => fields
+        .iter()
+        .find(|(k, _)| k == key)
+        .map_or(&NULL, |(_, v)| v),
+      _ => &NULL,
+    }
+  }
+
+  pub fn as_f64(&self) -> Option<f64> {
+    match self {
+      Json::Num(n) => Some(*n),
+      _ => None,
+    }
+  }
+
+  pub fn as_u32(&self) -> Option<u32> {
+    self.as_f64().filter(|n| *n >= 0.0).map(|n| n as u32)
+  }
+
+  pub fn as_bool(&self) -> Option<bool> {
+    match self {
+      Json::Bool(b) => Some(*b),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Json::Str(s) => Some(s),
+      _ => None,
+    }
+  }
+
+  pub fn as_arr(&self) -> Option<&[Json]> {
+    match self {
+      Json::Arr(items) => Some(items),
+      _ => None,
+    }
+  }
+
+  pub fn as_obj(&self) -> Option<&[(String, Json)]> {
+    match self {
+      Json::Obj(fields) => Some(fields),
+      _ => None,
+    }
+  }
+}
+
+/// Parses a JSON document. Errors say what went wrong and on which line.
+pub(crate) fn parse_json(text: &str) -> Result<Json, String> {
+  let mut parser = Parser {
+    chars: text.chars(),
+    peeked: None,
+    line: 1,
+  };
+  let value = parser.value()?;
+  parser.skip_whitespace();
+  match parser.next() {
+    None => Ok(value),
+    Some(c) => Err(parser.error(&format!("unexpected {:?} after the end", c))),
+  }
+}
+
+struct Parser<'a> {
+  chars: Chars<'a>,
+  peeked: Option<char>,
+  line: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn peek(&mut self) -> Option<char> {
+    if self.peeked.is_none() {
+      self.peeked = self.chars.next();
+    }
+    self.peeked
+  }
+
+  fn next(&mut self) -> Option<char> {
+    let c = self.peek();
+    self.peeked = None;
+    if c == Some('\n') {
+      self.line += 1;
+    }
+    c
+  }
+
+  fn error(&self, message: &str) -> String {
+    format!("{} on line {}", message, self.line)
+  }
+
+  fn skip_whitespace(&mut self) {
+    while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = self.peek() {
+      self.next();
+    }
+  }
+
+  fn expect(&mut self, expected: char) -> Result<(), String> {
+    self.skip_whitespace();
+    match self.next() {
+      Some(c) if c == expected => Ok(()),
+      Some(c) => Err(self.error(&format!("expected {:?} but found {:?}", expected, c))),
+      None => Err(self.error(&format!("expected {:?} but the file ended", expected))),
+    }
+  }
+
+  fn value(&mut self) -> Result<Json, String> {
+    self.skip_whitespace();
+    match self.peek() {
+      Some('{') => self.object(),
+      Some('[') => self.array(),
+      Some('"') => self.string().map(Json::Str),
+      Some('t') => self.word("true", Json::Bool(true)),
+      Some('f') => self.word("false", Json::Bool(false)),
+      Some('n') => self.word("null", Json::Null),
+      Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+      Some(c) => Err(self.error(&format!("unexpected {:?}", c))),
+      None => Err(self.error("expected a value but the file ended")),
+    }
+  }
+
+  fn object(&mut self) -> Result<Json, String> {
+    self.expect('{')?;
+    let mut fields = vec![];
+    self.skip_whitespace();
+    if self.peek() == Some('}') {
+      self.next();
+      return Ok(Json::Obj(fields));
+    }
+    loop {
+      self.skip_whitespace();
+      let key = self.string()?;
+      self.expect(':')?;
+      fields.push((key, self.value()?));
+      self.skip_whitespace();
+      match self.next() {
+        Some(',') => continue,
+        Some('}') => return Ok(Json::Obj(fields)),
+        _ => return Err(self.error("expected ',' or '}' in object")),
+      }
+    }
+  }
+
+  fn array(&mut self) -> Result<Json, String> {
+    self.expect('[')?;
+    let mut items = vec![];
+    self.skip_whitespace();
+    if self.peek() == Some(']') {
+      self.next();
+      return Ok(Json::Arr(items));
+    }
+    loop {
+      items.push(self.value()?);
+      self.skip_whitespace();
+      match self.next() {
+        Some(',') => continue,
+        Some(']') => return Ok(Json::Arr(items)),
+        _ => return Err(self.error("expected ',' or ']' in array")),
+      }
+    }
+  }
+
+  fn string(&mut self) -> Result<String, String> {
+    if self.next() != Some('"') {
+      return Err(self.error("expected a string"));
+    }
+    let mut s = String::new();
+    loop {
+      match self.next() {
+        Some('"') => return Ok(s),
+        Some('\\') => {
+          let c = match self.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('u') => self.unicode_escape()?,
+            Some(c) => c,
+            None => return Err(self.error("unfinished string")),
+          };
+          s.push(c);
+        }
+        Some(c) => s.push(c),
+        None => return Err(self.error("unfinished string")),
+      }
+    }
+  }
+
+  /// the rest of a `\u` escape, including a second one for surrogate pairs
+  fn unicode_escape(&mut self) -> Result<char, String> {
+    let high = self.hex4()?;
+    if (0xD800..0xDC00).contains(&high) {
+      if self.next() != Some('\\') || self.next() != Some('u') {
+        return Err(self.error("unpaired surrogate in string"));
+      }
+      let low = self.hex4()?;
+      if !(0xDC00..0xE000).contains(&low) {
+        return Err(self.error("unpaired surrogate in string"));
+      }
+      let c = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+      return std::char::from_u32(c).ok_or_else(|| self.error("bad escape in string"));
+    }
+    std::char::from_u32(high).ok_or_else(|| self.error("bad escape in string"))
+  }
+
+  fn hex4(&mut self) -> Result<u32, String> {
+    let mut n = 0;
+    for _ in 0..4 {
+      let digit = self.next().and_then(|c| c.to_digit(16));
+      n = n * 16 + digit.ok_or_else(|| self.error("bad escape in string"))?;
+    }
+    Ok(n)
+  }
+
+  fn number(&mut self) -> Result<Json, String> {
+    let mut s = String::new();
+    while let Some(c) = self.peek() {
+      if c.is_ascii_digit() || "+-.eE".contains(c) {
+        s.push(c);
+        self.next();
+      } else {
+        break;
+      }
+    }
+    s.parse()
+      .map(Json::Num)
+      .map_err(|_| self.error(&format!("bad number {:?}", s)))
+  }
+
+  fn word(&mut self, word: &str, value: Json) -> Result<Json, String> {
+    for expected in word.chars() {
+      if self.next() != Some(expected) {
+        return Err(self.error(&format!("expected {:?}", word)));
+      }
+    }
+    Ok(value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reads_nested_values() {
+    let json = parse_json(r#"{"a": [1, -2.5e1, true, null], "b": {"c": "d"}}"#).unwrap();
+    let a = json.get("a").as_arr().unwrap();
+    assert_eq!(a[0].as_u32(), Some(1));
+    assert_eq!(a[1].as_f64(), Some(-25.0));
+    assert_eq!(a[2].as_bool(), Some(true));
+    assert_eq!(a[3], Json::Null);
+    assert_eq!(json.get("b").get("c").as_str(), Some("d"));
+    assert_eq!(json.get("missing").get("c"), &Json::Null);
+  }
+
+  #[test]
+  fn reads_escapes_and_surrogate_pairs() {
+    let json = parse_json(r#""tab\t quote\" \u00e9 \ud83d\ude00""#).unwrap();
+    assert_eq!(json.as_str(), Some("tab\t quote\" é 😀"));
+  }
+
+  #[test]
+  fn rejects_unpaired_surrogates() {
+    let lone = parse_json(r#""\ud83d!""#);
+    assert_eq!(
+      lone,
+      Err("unpaired surrogate in string on line 1".to_string())
+    );
+    let not_low = parse_json(r#""\ud83d\u0041""#);
+    assert_eq!(
+      not_low,
+      Err("unpaired surrogate in string on line 1".to_string())
+    );
+  }
+
+  #[test]
+  fn rejects_trailing_garbage() {
+    let result = parse_json("{\"a\": 1}\n\n  x");
+    assert_eq!(
+      result,
+      Err("unexpected 'x' after the end on line 3".to_string())
+    );
+    assert!(parse_json("[1] [2]").is_err());
+    assert!(parse_json("[1]\n").is_ok());
+  }
+
+  #[test]
+  fn says_which_line_errors_are_on() {
+    let result = parse_json("[1,\n2,\n]");
+    assert_eq!(result, Err("unexpected ']' on line 3".to_string()));
+    let result = parse_json("{\n\"a\": tru }");
+    assert_eq!(result, Err("expected \"true\" on line 2".to_string()));
+  }
+}
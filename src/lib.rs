@@ -4,24 +4,43 @@
 #![allow(dead_code)]
 #![allow(unused)]
 
+mod error;
 mod geometry;
 mod graphics;
+mod headless;
 mod input;
+mod json;
 mod math;
+mod random;
+mod replay;
 
 // re-exporting for convenient importing by consumers
+pub use error::Error;
 pub use geometry::*;
 pub use graphics::*;
+pub use headless::*;
+pub use input::*;
 pub use math::*;
+pub use random::*;
+pub use replay::*;
 
 // re-exporting for convenient obfuscation - I may replace sokol_app with winit
 pub use sokol::app::SAppDesc as KAppDesc;
 pub use sokol::app::SAppKeycode as Keycode;
+pub use sokol::app::SAppModifier as Modifiers;
+pub use sokol::app::SAppMouseButton as MouseButton;
 
 use sokol::app::*;
 use sokol::gfx::*;
 
-use std::{mem, path::PathBuf};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::{
+  collections::{BTreeMap, HashMap},
+  mem,
+  path::PathBuf,
+  time::Instant,
+};
 
 // ----------------------------------------------------------------------------
 // colors
@@ -76,11 +95,17 @@ impl Default for Transform2d {
 
 // pub(crate) const BYTES_MODEL_BUFF_V (size_of::<MeshVert>() * MAX_MODEL_VERTS)
 // pub(crate) const BYTES_MODEL_BUFF_I (size_of::<u32>() * MAX_MODEL_VERTS)
-pub(crate) const MAX_QUADS: usize = 4000;
-pub(crate) const MAX_POINTS: usize = 100;
-pub(crate) const MAX_LINES: usize = 100;
-pub(crate) const MAX_IMAGES: usize = 100;
-pub(crate) const MAX_MESHES: usize = 200;
+pub(crate) const DEFAULT_MAX_QUADS: usize = 4000;
+pub(crate) const DEFAULT_MAX_POINTS: usize = 100;
+pub(crate) const DEFAULT_MAX_LINES: usize = 100;
+pub(crate) const DEFAULT_MAX_TRIANGLES: usize = 1000;
+pub(crate) const DEFAULT_MAX_IMAGES: usize = 100;
+pub(crate) const DEFAULT_MAX_MESHES: usize = 200;
+pub(crate) const DEFAULT_GLYPH_CACHE_SIZE: u32 = 1024;
+
+// window dimensions reported while running without a window
+pub(crate) const HEADLESS_WIDTH: f32 = 640.0;
+pub(crate) const HEADLESS_HEIGHT: f32 = 480.0;
 
 #[derive(Default, Copy, Clone)]
 pub struct Texture {
@@ -160,26 +185,132 @@ pub(crate) struct DrawLine {
 }
 
 #[derive(Default, Copy, Clone)]
+#[repr(align(16))]
+pub(crate) struct DrawTriangle {
+  pub point_a: Vec4,
+  pub color_a: Vec4,
+  pub point_b: Vec4,
+  pub color_b: Vec4,
+  pub point_c: Vec4,
+  pub color_c: Vec4,
+}
+
+#[derive(Default, Copy, Clone)]
+#[repr(C)]
 pub(crate) struct QuadVert {
   pub pos: Vec3,
   pub uv: Vec2,
+  /// multiplies the texture color
+  pub color: [f32; 4],
+  /// added to the texture color
+  pub flash: [f32; 3],
 }
 
 impl QuadVert {
   pub fn new(x: f32, y: f32, z: f32, uvx: f32, uvy: f32) -> QuadVert {
     let pos = vec3(x, y, z);
     let uv = vec2(uvx, uvy);
-    QuadVert { pos, uv }
+    QuadVert {
+      pos,
+      uv,
+      color: [1.0; 4],
+      flash: [0.0; 3],
+    }
   }
 }
 
 pub(crate) type QuadCorners = [QuadVert; 4];
 
+/// How a sprite's pixels combine with what's already been drawn
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum BlendMode {
+  /// covers what's behind it according to its alpha
+  #[default]
+  Alpha,
+  /// brightens what's behind it, e.g. for glows and sparks
+  Additive,
+  /// darkens what's behind it, e.g. for shadows and lighting
+  Multiply,
+  /// like `Alpha`, for images whose colors are already multiplied by alpha
+  Premultiplied,
+}
+
+pub(crate) const BLEND_MODES: usize = 4;
+
+/// Changes to a sprite's colors when it's drawn, see `draw_sprite_styled`
+#[derive(Copy, Clone, Debug)]
+pub struct SpriteStyle {
+  /// multiplies the sprite's colors, including alpha to fade it out
+  pub color: Vec4,
+  /// added to the sprite's colors, e.g. white to flash it when hit
+  pub flash: Vec3,
+  pub blend: BlendMode,
+}
+
+impl SpriteStyle {
+  /// the sprite as it is, faded to the given alpha
+  pub fn with_alpha(alpha: f32) -> Self {
+    Self {
+      color: vec4(1.0, 1.0, 1.0, alpha),
+      ..Default::default()
+    }
+  }
+
+  /// the sprite as it is, with a color added
+  pub fn with_flash(flash: Vec3) -> Self {
+    Self {
+      flash,
+      ..Default::default()
+    }
+  }
+}
+
+impl Default for SpriteStyle {
+  fn default() -> Self {
+    Self {
+      color: white(),
+      flash: Vec3::zero(),
+      blend: BlendMode::Alpha,
+    }
+  }
+}
+
+/// Where lines of text go relative to the position they're drawn at
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TextAlign {
+  Left,
+  Center,
+  Right,
+}
+
+/// How text is laid out and colored, see `draw_text_styled`
+#[derive(Copy, Clone, Debug)]
+pub struct TextStyle {
+  /// multiplies the glyphs' colors
+  pub color: Vec4,
+  pub align: TextAlign,
+  /// the width to wrap lines at, in the same units as the text's position
+  pub wrap_width: Option<f32>,
+}
+
+impl Default for TextStyle {
+  fn default() -> Self {
+    Self {
+      color: white(),
+      align: TextAlign::Left,
+      wrap_width: None,
+    }
+  }
+}
+
 #[derive(Default, Copy, Clone)]
 pub(crate) struct DrawQuad {
   pub img_id: usize,
   pub corners: QuadCorners,
   pub transform: Mat4,
+  pub color: Vec4,
+  pub flash: Vec3,
+  pub blend: BlendMode,
 }
 
 #[derive(Default, Clone, Copy)]
@@ -201,111 +332,378 @@ pub(crate) struct Image {
   pub(crate) h: u32,
 }
 
+#[derive(Default)]
 pub(crate) struct ImagesCtx {
-  e: [Image; MAX_IMAGES],
-  count: usize,
+  pub(crate) e: Vec<Image>,
+}
+
+/// a character in a font, with its pivot on the baseline where the pen
+/// sits before drawing it
+#[derive(Copy, Clone)]
+pub(crate) struct Glyph {
+  /// `None` for glyphs with nothing to draw, like spaces
+  pub(crate) sprite: Option<Sprite>,
+  /// how far the pen moves after the glyph, in the font's pixels
+  pub(crate) advance: f32,
+}
+
+/// a loaded font's metrics, in the units its glyphs are measured in: the
+/// pixels a bitmap font was exported at, or a vector font's font units
+#[derive(Default)]
+pub(crate) struct Font {
+  /// the size of the em square
+  pub(crate) size: f32,
+  /// the distance between lines
+  pub(crate) line_height: f32,
+  /// the distance from the top of a line to its baseline
+  pub(crate) base: f32,
+  /// fonts to look in, in order, for characters this one doesn't have
+  pub(crate) fallbacks: Vec<usize>,
+  pub(crate) kind: FontKind,
 }
 
-impl Default for ImagesCtx {
+pub(crate) enum FontKind {
+  /// glyphs prebaked into images, from a BMFont file
+  Bitmap {
+    glyphs: HashMap<char, Glyph>,
+    /// extra space between pairs of characters, usually negative
+    kerning: HashMap<(char, char), f32>,
+  },
+  /// outlines from a TrueType or OpenType file, drawn into the glyph cache
+  /// at whatever size they're shown at
+  Vector(TtfFont),
+}
+
+impl Default for FontKind {
   fn default() -> Self {
-    Self {
-      e: [Default::default(); MAX_IMAGES],
-      count: 0,
+    FontKind::Bitmap {
+      glyphs: HashMap::new(),
+      kerning: HashMap::new(),
     }
   }
 }
 
-pub(crate) struct QuadsCtx {
-  pub(crate) shape: GlShape,
-  pub(crate) e: [DrawQuad; MAX_QUADS],
-  pub(crate) count: usize,
+/// every loaded font, indexed by the id `load_font` returns
+#[derive(Default)]
+pub(crate) struct FontsCtx {
+  pub(crate) e: Vec<Font>,
 }
 
-impl Default for QuadsCtx {
+/// which vector font glyph at which size, in pixels per em
+pub(crate) type GlyphKey = (usize, u16, u16);
+
+/// a glyph drawn into the glyph cache
+#[derive(Copy, Clone)]
+pub(crate) struct CachedGlyph {
+  /// `None` for glyphs with nothing to draw, like spaces
+  pub(crate) sprite: Option<Sprite>,
+  /// the shelf it's on, if it has a sprite
+  pub(crate) shelf: usize,
+}
+
+/// a row of the glyph cache texture, filled left to right
+pub(crate) struct Shelf {
+  pub(crate) y: u32,
+  pub(crate) h: u32,
+  /// where the next glyph goes
+  pub(crate) x: u32,
+  /// the last frame a glyph on it was drawn
+  pub(crate) last_used: u64,
+  pub(crate) glyphs: Vec<GlyphKey>,
+}
+
+/// Vector font glyphs, drawn as they're needed into one texture. When it's
+/// full, the shelf that's gone unused the longest is cleared for reuse.
+#[derive(Default)]
+pub(crate) struct GlyphCacheCtx {
+  /// made the first time a vector font is drawn
+  pub(crate) texture: Option<Texture>,
+  /// RGBA pixels, white with coverage as alpha, sent to the gpu each frame
+  /// they change
+  pub(crate) pixels: Vec<u8>,
+  pub(crate) dirty: bool,
+  pub(crate) shelves: Vec<Shelf>,
+  pub(crate) glyphs: HashMap<GlyphKey, CachedGlyph>,
+  pub(crate) frame: u64,
+}
+
+/// What to do when more draw calls of one kind are made in a frame than
+/// there's room for.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum Overflow {
+  /// double the capacity, including the gpu buffer backing it
+  #[default]
+  Grow,
+  /// ignore the extra draw calls, printing a warning once per frame
+  Drop,
+  /// panic - useful for catching runaway draw calls during development
+  Panic,
+}
+
+/// Sizing for the draw calls of a single primitive
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PoolConfig {
+  /// the number of draw calls per frame to allocate room for up front
+  pub capacity: usize,
+  pub overflow: Overflow,
+}
+
+impl PoolConfig {
+  pub fn new(capacity: usize, overflow: Overflow) -> Self {
+    Self { capacity, overflow }
+  }
+}
+
+/// Configures how much memory is set aside for draw calls and loaded assets
+#[derive(Copy, Clone, Debug)]
+pub struct GraphicsConfig {
+  pub quads: PoolConfig,
+  pub points: PoolConfig,
+  pub lines: PoolConfig,
+  /// filled shapes, which take a triangle per segment of their outline
+  pub triangles: PoolConfig,
+  pub meshes: PoolConfig,
+  /// the number of images to allocate room for up front. Loading more is fine.
+  pub images: usize,
+  /// the width and height of the texture vector font glyphs are cached in
+  pub glyph_cache_size: u32,
+}
+
+impl Default for GraphicsConfig {
   fn default() -> Self {
     Self {
-      shape: Default::default(),
-      e: [Default::default(); MAX_QUADS],
-      count: Default::default(),
+      quads: PoolConfig::new(DEFAULT_MAX_QUADS, Overflow::Grow),
+      points: PoolConfig::new(DEFAULT_MAX_POINTS, Overflow::Grow),
+      lines: PoolConfig::new(DEFAULT_MAX_LINES, Overflow::Grow),
+      triangles: PoolConfig::new(DEFAULT_MAX_TRIANGLES, Overflow::Grow),
+      meshes: PoolConfig::new(DEFAULT_MAX_MESHES, Overflow::Grow),
+      images: DEFAULT_MAX_IMAGES,
+      glyph_cache_size: DEFAULT_GLYPH_CACHE_SIZE,
     }
   }
 }
 
-pub(crate) struct PointsCtx {
-  pub(crate) shape: GlShape,
-  pub(crate) e: [DrawPoint; MAX_POINTS],
-  pub(crate) count: usize,
+/// Engine configuration, passed to `run_with_config`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KConfig {
+  pub gfx: GraphicsConfig,
 }
 
-impl Default for PointsCtx {
-  fn default() -> Self {
+/// Heap-backed storage for a single frame's worth of one kind of draw call
+#[derive(Default)]
+pub(crate) struct DrawBuffer<T> {
+  pub(crate) e: Vec<T>,
+  pub(crate) capacity: usize,
+  pub(crate) overflow: Overflow,
+  warned: bool,
+}
+
+impl<T> DrawBuffer<T> {
+  pub(crate) fn new(config: PoolConfig) -> Self {
     Self {
-      shape: Default::default(),
-      e: [Default::default(); MAX_POINTS],
-      count: Default::default(),
+      e: Vec::with_capacity(config.capacity),
+      capacity: config.capacity,
+      overflow: config.overflow,
+      warned: false,
+    }
+  }
+
+  /// Adds a draw call, applying the overflow policy if there's no room left.
+  /// `name` is used in warning and panic messages. Returns false if the draw
+  /// call was dropped.
+  pub(crate) fn push(&mut self, item: T, name: &str) -> bool {
+    if self.e.len() >= self.capacity {
+      match self.overflow {
+        Overflow::Grow => {
+          self.capacity = max(self.capacity * 2, 1);
+          self.e.reserve(self.capacity - self.e.len());
+        }
+        Overflow::Drop => {
+          if !self.warned {
+            println!(
+              "Too many {} draw calls this frame! Maximum is {}. Dropping the rest.",
+              name, self.capacity
+            );
+            self.warned = true;
+          }
+          return false;
+        }
+        Overflow::Panic => panic!("Too many {} draw calls! Maximum is {}", name, self.capacity),
+      }
     }
+    self.e.push(item);
+    true
+  }
+
+  pub(crate) fn len(&self) -> usize {
+    self.e.len()
   }
+
+  pub(crate) fn clear(&mut self) {
+    self.e.clear();
+    self.warned = false;
+  }
+}
+
+#[derive(Default)]
+pub(crate) struct QuadsCtx {
+  /// the pipeline here is for `BlendMode::Alpha`
+  pub(crate) shape: GlShape,
+  /// a pipeline per blend mode, indexed by `BlendMode`
+  pub(crate) pipelines: [SgPipeline; BLEND_MODES],
+  pub(crate) e: DrawBuffer<DrawQuad>,
+  /// the number of quads the gpu buffers currently have room for
+  pub(crate) gpu_capacity: usize,
+  /// holds the software cursor's corners, which are drawn after everything else
+  pub(crate) cursor_buffer: SgBuffer,
 }
 
+#[derive(Default)]
+pub(crate) struct PointsCtx {
+  pub(crate) shape: GlShape,
+  pub(crate) e: DrawBuffer<DrawPoint>,
+  /// the number of points the gpu buffer currently has room for
+  pub(crate) gpu_capacity: usize,
+}
+
+#[derive(Default)]
 pub(crate) struct LinesCtx {
   pub(crate) shape: GlShape,
-  pub(crate) e: [DrawLine; MAX_LINES],
-  pub(crate) count: usize,
+  pub(crate) e: DrawBuffer<DrawLine>,
+  /// the number of lines the gpu buffer currently has room for
+  pub(crate) gpu_capacity: usize,
 }
 
-impl Default for LinesCtx {
-  fn default() -> Self {
-    Self {
-      shape: Default::default(),
-      e: [Default::default(); MAX_LINES],
-      count: Default::default(),
-    }
-  }
+#[derive(Default)]
+pub(crate) struct TrianglesCtx {
+  pub(crate) shape: GlShape,
+  pub(crate) e: DrawBuffer<DrawTriangle>,
+  /// the number of triangles the gpu buffer currently has room for
+  pub(crate) gpu_capacity: usize,
 }
 
+#[derive(Default)]
 pub(crate) struct MeshCtx {
   pub(crate) shape: GlShape,
-  pub(crate) e: [DrawMesh; MAX_MESHES],
-  pub(crate) count: usize,
+  pub(crate) e: DrawBuffer<DrawMesh>,
 }
 
-impl Default for MeshCtx {
-  fn default() -> Self {
-    Self {
-      shape: Default::default(),
-      e: [Default::default(); MAX_MESHES],
-      count: Default::default(),
-    }
-  }
+/// The kinds of draw call, each with its own pipeline
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) enum PrimitiveKind {
+  Mesh,
+  Quad,
+  Point,
+  Line,
+  Triangle,
+}
+
+/// A single draw call in the order it was made. The primitive's own draw
+/// buffer holds the data to draw at `index`.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct DrawCmd {
+  pub primitive: PrimitiveKind,
+  pub index: usize,
+  /// where the draw call sits in the world, for sorting by depth
+  pub pos: Vec3,
+  /// the index of the `Layer` the draw call is on
+  pub layer: usize,
+}
+
+/// Every draw call made this frame, across all primitives. Sorted by layer,
+/// depth and material before drawing, see `graphics::draw_list`.
+#[derive(Default)]
+pub(crate) struct DrawList {
+  pub(crate) e: Vec<DrawCmd>,
+}
+
+/// A group of draw calls with its own camera, e.g. a hud over the game
+/// world. Each layer is drawn over the ones before it, whatever their depth.
+/// See `set_layer_camera`, `set_layer_clear` and `set_layer_order`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Layer {
+  /// drawn behind the world, e.g. for a sky or parallax scenery
+  Background,
+  /// where draw calls go unless they name a layer
+  World,
+  /// drawn over the world in window pixels
+  Ui,
+  /// more layers for the game to set up. They draw after the others, in
+  /// order of their number, until reordered.
+  Custom(u8),
+}
+
+/// How a layer's draw calls are projected onto the window
+#[derive(Copy, Clone, Debug)]
+pub enum LayerCamera {
+  /// `GraphicsCtx::proj` and `view`, the same camera as the world
+  World,
+  /// 1 unit per window pixel, with the origin at the bottom left and y up
+  Screen,
+  Custom {
+    proj: Mat4,
+    view: Mat4,
+  },
+}
+
+/// What's cleared before a layer is drawn
+#[derive(Copy, Clone, Debug)]
+pub struct LayerClear {
+  /// fills the window with a color, covering earlier layers
+  pub color: Option<Vec4>,
+  /// forgets the depth of earlier layers, so nothing in them can hide this one
+  pub depth: bool,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct LayerCtx {
+  pub(crate) camera: LayerCamera,
+  pub(crate) clear: LayerClear,
+  /// layers are drawn from the lowest order to the highest
+  pub(crate) order: i32,
+}
+
+/// every layer the game has used or set up, indexed by `Layer::index`
+#[derive(Default)]
+pub(crate) struct LayersCtx {
+  pub(crate) e: Vec<LayerCtx>,
 }
 
 // TODO add api for setting bg, proj, and view and then hide the whole GraphicsCtx from the external api
 #[derive(Default)]
 
 pub struct GraphicsCtx {
+  /// the color the window is cleared to at the start of each frame
   pub bg: Vec3,
 
-  // TODO - maybe pass view_proj manually to the draw calls?
-  // This would let users draw shapes in multiple coordinate systems
-  // which would be useful for layering UI over a 3D field.
-  // alternatively, maybe draw calls could/should be defined in various layer groups
+  /// the world camera. Other layers can have their own, see `Layer`.
   pub proj: Mat4,
   pub view: Mat4,
-  pub(crate) view_proj: Mat4,
   //
   pub(crate) quads: QuadsCtx,
   pub(crate) points: PointsCtx,
   pub(crate) lines: LinesCtx,
+  pub(crate) triangles: TrianglesCtx,
   pub(crate) images: ImagesCtx,
+  pub(crate) fonts: FontsCtx,
+  pub(crate) glyph_cache: GlyphCacheCtx,
   pub(crate) mesh: MeshCtx,
+  pub(crate) draw_list: DrawList,
+  pub(crate) layers: LayersCtx,
+  /// the number of draw calls made presenting the last frame
+  pub(crate) draw_calls: usize,
   //
-  pub(crate) pass_action: SgPassAction,
+  pub(crate) config: GraphicsConfig,
+  /// set when running without a window, in which case nothing reaches the gpu
+  pub(crate) headless: bool,
 }
 
-/// describes the most recent mouse button state
+/// describes the most recent state of a mouse or gamepad button
 #[derive(Default)]
 
 pub struct ButtonState {
+  /// whether the button is currently held down
+  pub held: bool,
   /// the number of presses during the previous frame
   pub prev_down: u32,
   /// the number of releases during the previous frame
@@ -314,38 +712,218 @@ pub struct ButtonState {
   pub down: u32,
   /// the number of releases during the current frame
   pub up: u32,
+  /// how long the button has been held, in seconds. Zero while released.
+  pub held_time: f32,
+  /// for mouse buttons, where the mouse was when the button was last pressed
+  pub press_pos: Vec2,
+
+  // mouse gestures. See `MouseCtx::drag_threshold` and
+  // `MouseCtx::double_click_interval` for tuning.
+  /// whether the mouse has moved far enough while the button is held to
+  /// count as a drag. Drags start from `press_pos`.
+  pub dragging: bool,
+  /// whether a drag started during the current frame
+  pub drag_started: bool,
+  /// whether a drag ended during the current frame
+  pub drag_ended: bool,
+  /// how far the mouse moved while dragging during the current frame
+  pub drag_delta: Vec2,
+  /// the number of clicks during the current frame. A click is a press and
+  /// release that didn't turn into a drag.
+  pub clicks: u32,
+  /// the number of clicks during the current frame that closely followed an
+  /// earlier click. The second click is also counted in `clicks`.
+  pub double_clicks: u32,
+  pub(crate) press_time: f64,
+  pub(crate) last_click: Option<(f64, Vec2)>,
 }
 
 impl ButtonState {
+  pub(crate) fn press(&mut self, time: f64, pos: Vec2) {
+    self.held = true;
+    self.down += 1;
+    self.press_time = time;
+    self.press_pos = pos;
+  }
+
+  pub(crate) fn release(&mut self) {
+    self.held = false;
+    self.up += 1;
+  }
+
+  /// updates `held_time` at the start of a frame
+  pub(crate) fn tick(&mut self, time: f64) {
+    self.held_time = if self.held {
+      (time - self.press_time) as f32
+    } else {
+      0.0
+    };
+  }
+
   pub(crate) fn frame_end(&mut self) {
     self.prev_down = self.down;
     self.prev_up = self.up;
     self.down = 0;
     self.up = 0;
+    self.drag_started = false;
+    self.drag_ended = false;
+    self.drag_delta = Vec2::zero();
+    self.clicks = 0;
+    self.double_clicks = 0;
   }
 }
 
 /// read from this struct to access information about mouse input state
-#[derive(Default)]
 
 pub struct MouseCtx {
   pub left: ButtonState,
   pub middle: ButtonState,
   pub right: ButtonState,
 
-  // TODO should there be a way to get mouse position in world coordinates? ie. reverse view & projeection?
+  /// position in window coordinates. Use `screen_to_world` or
+  /// `window_to_world_2d` to find what's under the mouse.
   pub pos: Vec2,
   pub prev_pos: Vec2,
 
   /// contains the amount of mouse wheel movement since the previous frame
   pub scroll_x: f32,
   pub scroll_y: f32,
+  /// how far the mouse moved during the current frame. Keeps reporting
+  /// movement while `pos` is frozen, see `freeze_mouse_pos`.
+  pub delta: Vec2,
+
+  /// how far in pixels the mouse has to move with a button held before it
+  /// counts as a drag instead of a click
+  pub drag_threshold: f32,
+  /// the longest time between two clicks that makes them a double click, in
+  /// seconds
+  pub double_click_interval: f32,
+
+  // software cursor, see `show_cursor_sprite` and friends
+  pub(crate) cursor_sprite_visible: bool,
+  pub(crate) pos_frozen: bool,
+  pub(crate) cursor_sprite: Option<Sprite>,
+  /// the last position reported by the os, which `pos` stops following
+  /// while it is frozen
+  pub(crate) raw_pos: Option<Vec2>,
+}
+
+impl Default for MouseCtx {
+  fn default() -> Self {
+    Self {
+      left: Default::default(),
+      middle: Default::default(),
+      right: Default::default(),
+      pos: Vec2::zero(),
+      prev_pos: Vec2::zero(),
+      scroll_x: 0.0,
+      scroll_y: 0.0,
+      delta: Vec2::zero(),
+      drag_threshold: 4.0,
+      double_click_interval: 0.3,
+      cursor_sprite_visible: true,
+      pos_frozen: false,
+      cursor_sprite: None,
+      raw_pos: None,
+    }
+  }
 }
 
 impl MouseCtx {
+  /// the state of the given button, if it's one kit tracks
+  pub fn button(&self, button: MouseButton) -> Option<&ButtonState> {
+    match button {
+      MouseButton::Left => Some(&self.left),
+      MouseButton::Right => Some(&self.right),
+      MouseButton::Middle => Some(&self.middle),
+      _ => None,
+    }
+  }
+
+  pub(crate) fn button_mut(&mut self, button: MouseButton) -> Option<&mut ButtonState> {
+    match button {
+      MouseButton::Left => Some(&mut self.left),
+      MouseButton::Right => Some(&mut self.right),
+      MouseButton::Middle => Some(&mut self.middle),
+      _ => None,
+    }
+  }
+
+  /// follows the mouse to a new position reported by the os
+  pub(crate) fn moved(&mut self, pos: Vec2) {
+    if let Some(raw_pos) = self.raw_pos {
+      self.delta += pos - raw_pos;
+    }
+    self.raw_pos = Some(pos);
+    if !self.pos_frozen {
+      self.pos = pos;
+    }
+  }
+
+  pub(crate) fn press(&mut self, button: MouseButton, time: f64) {
+    let pos = self.pos;
+    if let Some(button) = self.button_mut(button) {
+      button.press(time, pos);
+    }
+  }
+
+  /// releases a button, deciding whether it finished a drag or a click
+  pub(crate) fn release(&mut self, button: MouseButton, time: f64) {
+    let (pos, threshold, interval) = (self.pos, self.drag_threshold, self.double_click_interval);
+    let button = match self.button_mut(button) {
+      Some(button) => button,
+      None => return,
+    };
+    button.release();
+
+    // a quick drag may not have been noticed by `update` yet
+    if !button.dragging && (pos - button.press_pos).length() > threshold {
+      button.drag_started = true;
+      button.dragging = true;
+    }
+
+    if button.dragging {
+      button.dragging = false;
+      button.drag_ended = true;
+      return;
+    }
+
+    button.clicks += 1;
+    match button.last_click {
+      Some((last_time, last_pos))
+        if (time - last_time) as f32 <= interval && (pos - last_pos).length() <= threshold =>
+      {
+        button.double_clicks += 1;
+        // a third click starts a new pair rather than double clicking again
+        button.last_click = None;
+      }
+      _ => button.last_click = Some((time, pos)),
+    }
+  }
+
+  /// updates hold times and drags at the start of a frame
+  pub(crate) fn update(&mut self, time: f64) {
+    let (pos, prev_pos, threshold) = (self.pos, self.prev_pos, self.drag_threshold);
+    for button in [&mut self.left, &mut self.middle, &mut self.right].iter_mut() {
+      button.tick(time);
+      if !button.held {
+        continue;
+      }
+      if !button.dragging && (pos - button.press_pos).length() > threshold {
+        button.dragging = true;
+        button.drag_started = true;
+        // the drag picks up everything since the press, not just this frame
+        button.drag_delta = pos - button.press_pos;
+      } else if button.dragging {
+        button.drag_delta = pos - prev_pos;
+      }
+    }
+  }
+
   pub(crate) fn frame_end(&mut self) {
     self.scroll_x = 0.0;
     self.scroll_y = 0.0;
+    self.delta = Vec2::zero();
     self.prev_pos = self.pos;
     self.left.frame_end();
     self.middle.frame_end();
@@ -353,37 +931,511 @@ impl MouseCtx {
   }
 }
 
+// large enough to index by any `Keycode`
+pub(crate) const MAX_KEYCODES: usize = 512;
+
+/// describes the most recent state of a single key
+#[derive(Default, Copy, Clone)]
+
+pub struct KeyState {
+  /// whether the key is currently held down
+  pub held: bool,
+  /// the number of presses during the current frame, not counting repeats
+  pub down: u32,
+  /// the number of releases during the current frame
+  pub up: u32,
+  /// the number of os key repeats during the current frame from holding the key
+  pub repeats: u32,
+}
+
+/// read from this struct to access information about keyboard input state
+pub struct KeyboardCtx {
+  pub(crate) keys: [KeyState; MAX_KEYCODES],
+  /// modifier keys held during the most recent key or mouse event
+  pub modifiers: Modifiers,
+}
+
+impl Default for KeyboardCtx {
+  fn default() -> Self {
+    Self {
+      keys: [Default::default(); MAX_KEYCODES],
+      modifiers: Modifiers::empty(),
+    }
+  }
+}
+
+impl KeyboardCtx {
+  /// the full state of the given key
+  pub fn key(&self, key: Keycode) -> KeyState {
+    self.keys[key as usize]
+  }
+
+  /// whether the key is currently held down
+  pub fn is_down(&self, key: Keycode) -> bool {
+    self.keys[key as usize].held
+  }
+
+  /// whether the key was pressed during the current frame
+  pub fn pressed_this_frame(&self, key: Keycode) -> bool {
+    self.keys[key as usize].down > 0
+  }
+
+  /// whether the key was released during the current frame
+  pub fn released_this_frame(&self, key: Keycode) -> bool {
+    self.keys[key as usize].up > 0
+  }
+
+  /// whether the os sent a key repeat for the held key during the current
+  /// frame. Useful for things like holding an arrow key to scroll a menu.
+  pub fn repeated_this_frame(&self, key: Keycode) -> bool {
+    self.keys[key as usize].repeats > 0
+  }
+
+  pub(crate) fn key_down(&mut self, key: Keycode) {
+    let state = &mut self.keys[key as usize];
+    if state.held {
+      state.repeats += 1;
+    } else {
+      state.held = true;
+      state.down += 1;
+    }
+  }
+
+  pub(crate) fn key_up(&mut self, key: Keycode) {
+    let state = &mut self.keys[key as usize];
+    state.held = false;
+    state.up += 1;
+  }
+
+  pub(crate) fn frame_end(&mut self) {
+    for state in self.keys.iter_mut() {
+      state.down = 0;
+      state.up = 0;
+      state.repeats = 0;
+    }
+  }
+}
+
+/// Named actions and axes, and the inputs bound to each. See `bind_action`.
+#[derive(Default)]
+pub struct ActionsCtx {
+  pub(crate) actions: BTreeMap<String, Vec<Binding>>,
+  pub(crate) axes: BTreeMap<String, Vec<AxisBinding>>,
+}
+
+/// the most recent state of a single gamepad
+#[derive(Default)]
+pub struct GamepadCtx {
+  /// stays the same for as long as the gamepad is connected. Backends try to
+  /// give a reconnected gamepad its old id back.
+  pub id: usize,
+  pub name: String,
+  pub family: GamepadFamily,
+  pub connected: bool,
+  pub(crate) buttons: [ButtonState; GAMEPAD_BUTTONS],
+  /// left stick position with the dead zone applied, y up
+  pub left_stick: Vec2,
+  /// right stick position with the dead zone applied, y up
+  pub right_stick: Vec2,
+  /// how far the left trigger is pulled, from 0 to 1
+  pub left_trigger: f32,
+  /// how far the right trigger is pulled, from 0 to 1
+  pub right_trigger: f32,
+  pub(crate) raw: [f32; GAMEPAD_AXES],
+}
+
+impl GamepadCtx {
+  /// the full state of the given button
+  pub fn button(&self, button: GamepadButton) -> &ButtonState {
+    &self.buttons[button as usize]
+  }
+
+  /// the value of the given axis after dead zones are applied
+  pub fn axis(&self, axis: GamepadAxis) -> f32 {
+    match axis {
+      GamepadAxis::LeftX => self.left_stick.x(),
+      GamepadAxis::LeftY => self.left_stick.y(),
+      GamepadAxis::RightX => self.right_stick.x(),
+      GamepadAxis::RightY => self.right_stick.y(),
+      GamepadAxis::LeftTrigger => self.left_trigger,
+      GamepadAxis::RightTrigger => self.right_trigger,
+    }
+  }
+
+  /// the value of the given axis as reported by the device
+  pub fn raw_axis(&self, axis: GamepadAxis) -> f32 {
+    self.raw[axis as usize]
+  }
+
+  pub(crate) fn frame_end(&mut self) {
+    for button in self.buttons.iter_mut() {
+      button.frame_end();
+    }
+  }
+}
+
+/// read from this struct to access gamepads. Gamepads are indexed by id and
+/// stay in the list after disconnecting, so check `GamepadCtx::connected`
+/// or use `iter` to skip them.
+pub struct GamepadsCtx {
+  pub pads: Vec<GamepadCtx>,
+  /// stick positions closer to the center than this are treated as centered.
+  /// The dead zone is radial so diagonals aren't snapped to an axis, and
+  /// the rest of the range is rescaled so movement still starts from 0.
+  pub stick_dead_zone: f32,
+  /// trigger values below this are treated as released
+  pub trigger_dead_zone: f32,
+  /// ids of gamepads connected during the current frame
+  pub connected: Vec<usize>,
+  /// ids of gamepads disconnected during the current frame
+  pub disconnected: Vec<usize>,
+  pub(crate) backend: Option<Box<dyn GamepadBackend>>,
+}
+
+impl Default for GamepadsCtx {
+  fn default() -> Self {
+    Self {
+      pads: vec![],
+      stick_dead_zone: 0.2,
+      trigger_dead_zone: 0.05,
+      connected: vec![],
+      disconnected: vec![],
+      backend: None,
+    }
+  }
+}
+
+impl GamepadsCtx {
+  /// the connected gamepad with the given id
+  pub fn get(&self, id: usize) -> Option<&GamepadCtx> {
+    self.pads.get(id).filter(|pad| pad.connected)
+  }
+
+  /// all connected gamepads
+  pub fn iter(&self) -> impl Iterator<Item = &GamepadCtx> {
+    self.pads.iter().filter(|pad| pad.connected)
+  }
+
+  /// the connected gamepad with the lowest id, for single player games
+  pub fn first(&self) -> Option<&GamepadCtx> {
+    self.iter().next()
+  }
+
+  pub(crate) fn frame_end(&mut self) {
+    self.connected.clear();
+    self.disconnected.clear();
+    for pad in self.pads.iter_mut() {
+      pad.frame_end();
+    }
+  }
+}
+
+/// where a touch is in its lifetime, as of the current frame
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TouchPhase {
+  /// the finger touched down this frame
+  Began,
+  /// the finger moved this frame
+  Moved,
+  /// the finger is down but hasn't moved this frame
+  Stationary,
+  /// the finger lifted this frame. The touch is removed next frame.
+  Ended,
+  /// the os took the touch away, e.g. for a system gesture. The touch is
+  /// removed next frame.
+  Cancelled,
+}
+
+/// a single finger on the screen
+#[derive(Copy, Clone, Debug)]
+pub struct Touch {
+  /// stays the same while the finger is down
+  pub id: usize,
+  pub phase: TouchPhase,
+  /// position in window coordinates, like `MouseCtx::pos`
+  pub pos: Vec2,
+  /// movement since the previous frame
+  pub delta: Vec2,
+  /// where the finger first touched down
+  pub start_pos: Vec2,
+  /// `TimeCtx::real_elapsed` when the finger touched down
+  pub start_time: f64,
+  pub(crate) prev_pos: Vec2,
+  // gesture bookkeeping
+  pub(crate) dragging: bool,
+  pub(crate) long_pressed: bool,
+  pub(crate) multi: bool,
+}
+
+impl Touch {
+  /// whether the finger is still on the screen
+  pub fn is_down(&self) -> bool {
+    match self.phase {
+      TouchPhase::Began | TouchPhase::Moved | TouchPhase::Stationary => true,
+      TouchPhase::Ended | TouchPhase::Cancelled => false,
+    }
+  }
+}
+
+/// read from this struct to access the fingers currently on the screen and
+/// any gestures they made this frame
+pub struct TouchCtx {
+  /// fingers on the screen this frame, in the order they touched down
+  pub touches: Vec<Touch>,
+  /// gestures recognized during the current frame
+  pub gestures: Vec<Gesture>,
+  /// how far in pixels a finger can wander before a tap or long press
+  /// becomes a drag
+  pub slop: f32,
+  /// the longest a finger can be down and still count as a tap, in seconds
+  pub tap_duration: f32,
+  /// the longest time between two taps that makes them a double tap, in seconds
+  pub double_tap_interval: f32,
+  /// how long a finger has to be held still to make a long press, in seconds
+  pub long_press_duration: f32,
+  pub(crate) last_tap: Option<(f64, Vec2)>,
+}
+
+impl Default for TouchCtx {
+  fn default() -> Self {
+    Self {
+      touches: vec![],
+      gestures: vec![],
+      slop: 10.0,
+      tap_duration: 0.3,
+      double_tap_interval: 0.3,
+      long_press_duration: 0.5,
+      last_tap: None,
+    }
+  }
+}
+
+impl TouchCtx {
+  /// the touch with the given id, if that finger is on the screen
+  pub fn get(&self, id: usize) -> Option<&Touch> {
+    self.touches.iter().find(|touch| touch.id == id)
+  }
+
+  /// all touches, including ones that ended this frame
+  pub fn iter(&self) -> impl Iterator<Item = &Touch> {
+    self.touches.iter()
+  }
+
+  /// whether any finger is touching the screen
+  pub fn any_down(&self) -> bool {
+    self.touches.iter().any(|touch| touch.is_down())
+  }
+
+  pub(crate) fn frame_end(&mut self) {
+    self.gestures.clear();
+    self
+      .touches
+      .retain(|touch| touch.phase != TouchPhase::Ended && touch.phase != TouchPhase::Cancelled);
+    for touch in self.touches.iter_mut() {
+      touch.phase = TouchPhase::Stationary;
+      touch.prev_pos = touch.pos;
+      touch.delta = Vec2::zero();
+    }
+  }
+}
+
+/// read from this struct to access text typed during the current frame. See
+/// `TextField` for turning it into an editable string.
+#[derive(Default)]
+pub struct TextInputCtx {
+  /// typed characters and editing keys in the order they arrived, including
+  /// os key repeats
+  pub events: Vec<TextEvent>,
+}
+
+impl TextInputCtx {
+  /// just the characters typed this frame
+  pub fn chars(&self) -> String {
+    self
+      .events
+      .iter()
+      .filter_map(|event| match event.input {
+        TextInput::Char(c) => Some(c),
+        _ => None,
+      })
+      .collect()
+  }
+
+  pub(crate) fn frame_end(&mut self) {
+    self.events.clear();
+  }
+}
+
 /// Holds input state. Read from this during a game update to consume player inputs.
 #[derive(Default)]
 
 pub struct InputCtx {
   pub mouse: MouseCtx,
-
-  // TODO add multiple controllers
-  pub l_stick: Vec2,
-  pub r_stick: Vec2,
-
-  // TODO should this be pub? Maybe hide it as an implementation detail
-  pub quit: bool,
-  // TODO replace these with actual keyboard state - what to do with the keys should be
-  // a detail the game provides.
-  pub dir_u: bool,
-  pub dir_d: bool,
-  pub dir_l: bool,
-  pub dir_r: bool,
-  pub action_pressed: bool,
-  pub action_released: bool,
-  // TODO touch input
+  pub keyboard: KeyboardCtx,
+  pub actions: ActionsCtx,
+  pub gamepads: GamepadsCtx,
+  pub touches: TouchCtx,
+  pub text: TextInputCtx,
+  pub(crate) preferred: InputType,
+  pub(crate) preferred_changed: bool,
 }
 
 /// describes a type of input the player may be using
-
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
 pub enum InputType {
+  #[default]
   MouseKeyboard,
-  Gamepad, // TODO which kind? may be relevant for icons
+  /// a gamepad, along with which kind so prompts can show the right buttons
+  Gamepad(GamepadFamily),
   Touch,
 }
 
+/// Configures and tracks the fixed timestep that drives `KApp::update`.
+/// Change `dt` or `max_steps` during `KApp::init` to tune the simulation rate.
+pub struct StepCtx {
+  /// the length of a single fixed update step, in seconds
+  pub dt: f32,
+  /// the maximum number of fixed updates run in a single frame. Any time left
+  /// over past this is dropped so that a slow frame can't snowball into more
+  /// updates on the next frame.
+  pub max_steps: u32,
+  /// how far the current frame sits between the last fixed update and the
+  /// next one, in the range [0, 1). Use this to interpolate drawn positions.
+  pub alpha: f32,
+  pub(crate) accumulator: f32,
+}
+
+impl Default for StepCtx {
+  fn default() -> Self {
+    Self {
+      dt: 1.0 / 60.0,
+      max_steps: 5,
+      alpha: 0.0,
+      accumulator: 0.0,
+    }
+  }
+}
+
+impl StepCtx {
+  /// adds elapsed time to the accumulator and returns the number of fixed
+  /// updates that should run this frame
+  pub(crate) fn advance(&mut self, elapsed: f32) -> u32 {
+    self.accumulator += elapsed;
+    let mut steps = 0;
+    while self.accumulator >= self.dt && steps < self.max_steps {
+      self.accumulator -= self.dt;
+      steps += 1;
+    }
+    if steps == self.max_steps {
+      // too far behind to catch up; drop the backlog instead of spiraling,
+      // keeping only the partial step so that alpha stays below 1
+      self.accumulator %= self.dt;
+    }
+    self.alpha = clampf(self.accumulator / self.dt, 0.0, 1.0);
+    steps
+  }
+}
+
+/// Game clock, updated once at the start of each frame. Scaled time is what
+/// drives `KApp::update`, so slowing it down or pausing it slows or freezes the
+/// simulation while `KApp::frame` and `KApp::draw` keep running (for menus, etc).
+pub struct TimeCtx {
+  /// real seconds since the previous frame, ignoring `scale` and `paused`
+  pub real_delta: f32,
+  /// seconds since the previous frame after applying `scale`. Zero while paused.
+  pub delta: f32,
+  /// real seconds since the first frame
+  pub real_elapsed: f64,
+  /// scaled seconds since the first frame. Doesn't advance while paused.
+  pub elapsed: f64,
+  /// frames per second, smoothed over recent frames so it's readable on screen
+  pub fps: f32,
+  /// multiplier for scaled time. Below 1 for slow motion, above 1 for fast forward.
+  pub scale: f32,
+  /// freezes scaled time when set
+  pub paused: bool,
+  pub(crate) last_frame: Option<Instant>,
+}
+
+impl Default for TimeCtx {
+  fn default() -> Self {
+    Self {
+      real_delta: 0.0,
+      delta: 0.0,
+      real_elapsed: 0.0,
+      elapsed: 0.0,
+      fps: 0.0,
+      scale: 1.0,
+      paused: false,
+      last_frame: None,
+    }
+  }
+}
+
+// how much of each new frame's fps reading is blended into the smoothed value
+const FPS_SMOOTHING: f32 = 0.1;
+
+impl TimeCtx {
+  /// measures the real time passed since the previous frame
+  pub(crate) fn measure(&mut self) -> f32 {
+    let now = Instant::now();
+    let real_delta = match self.last_frame {
+      Some(last) => (now - last).as_secs_f32(),
+      None => 0.0,
+    };
+    self.last_frame = Some(now);
+    real_delta
+  }
+
+  /// advances the clock by the given real time
+  pub(crate) fn tick(&mut self, real_delta: f32) {
+    self.real_delta = real_delta;
+    self.real_elapsed += real_delta as f64;
+
+    self.delta = if self.paused {
+      0.0
+    } else {
+      real_delta * self.scale
+    };
+    self.elapsed += self.delta as f64;
+
+    if real_delta > 0.0 {
+      let fps = 1.0 / real_delta;
+      self.fps = if self.fps == 0.0 {
+        fps
+      } else {
+        lerpf(self.fps, fps, FPS_SMOOTHING)
+      };
+    }
+  }
+}
+
+/// The engine's random number generator, see `seed_random`
+pub(crate) struct RandomCtx {
+  pub(crate) rng: StdRng,
+  pub(crate) seed: u64,
+}
+
+impl Default for RandomCtx {
+  fn default() -> Self {
+    let seed = rand::random();
+    Self {
+      rng: StdRng::seed_from_u64(seed),
+      seed,
+    }
+  }
+}
+
+/// Input being recorded or replayed, see `start_recording`
+#[derive(Default)]
+pub(crate) struct ReplayCtx {
+  pub(crate) recording: Option<Recording>,
+  /// the replay and how far through its records it's got
+  pub(crate) replay: Option<(Recording, usize)>,
+  /// replayed gamepad events waiting for `poll_gamepads`
+  pub(crate) gamepad_events: Vec<GamepadEvent>,
+}
+
 // TODO should arrays in here be Vec<T> instead? Heap instead of stack?
 
 /// Engine state. Most engine apis operate by taking this state as the first
@@ -393,16 +1445,202 @@ pub enum InputType {
 #[derive(Default)]
 pub struct Ctx {
   pub frame_count: u32,
+  pub time: TimeCtx,
+  pub step: StepCtx,
   pub input: InputCtx,
   pub gfx: GraphicsCtx,
+  pub(crate) random: RandomCtx,
+  pub(crate) replay: ReplayCtx,
+  pub(crate) quit_requested: bool,
+  pub(crate) cleaned_up: bool,
+}
+
+/// Asks the engine to quit at the end of the current frame. The game gets a
+/// chance to veto via `KApp::on_quit_requested`, then `KApp::cleanup` runs.
+pub fn request_quit(ctx: &mut Ctx) {
+  ctx.quit_requested = true;
 }
 
 // ----------------------------------------------------------------------------
 // Lifecycle
 
-struct App<K: KApp> {
-  ctx: Ctx,
-  app: K,
+pub(crate) struct App<K: KApp> {
+  pub(crate) ctx: Ctx,
+  /// `None` once the game has been dropped on the way out
+  pub(crate) app: Option<K>,
+}
+
+impl<K: KApp> App<K> {
+  /// runs a single frame of the main loop, given the real time since the
+  /// previous frame. Returns false once the game has agreed to quit.
+  pub(crate) fn frame(&mut self, real_delta: f32) -> bool {
+    // a replay also decides how much time passes, so frames line up exactly
+    let real_delta = match replay::next_replay_frame(&mut self.ctx) {
+      Some((delta, events)) => {
+        for event in events {
+          self.handle_event(event);
+        }
+        delta
+      }
+      None => real_delta,
+    };
+
+    let (app, ctx) = match (&mut self.app, &mut self.ctx) {
+      (Some(app), ctx) => (app, ctx),
+      (None, _) => return false,
+    };
+    ctx.frame_count += 1;
+    ctx.time.tick(real_delta);
+    ctx.input.mouse.update(ctx.time.real_elapsed);
+    input::poll_gamepads(ctx);
+    input::recognize_gestures(ctx);
+    if ctx.input.preferred_changed {
+      let input = ctx.input.preferred;
+      app.preferred_input_changed(ctx, input);
+    }
+    app.frame(ctx);
+
+    let steps = ctx.step.advance(ctx.time.delta);
+    let dt = ctx.step.dt;
+    for _ in 0..steps {
+      app.update(ctx, dt);
+    }
+    let alpha = ctx.step.alpha;
+    app.draw(ctx, alpha);
+
+    graphics::present(ctx);
+
+    // input cleanup
+    ctx.input.mouse.frame_end();
+    ctx.input.keyboard.frame_end();
+    ctx.input.gamepads.frame_end();
+    ctx.input.touches.frame_end();
+    ctx.input.text.frame_end();
+    ctx.input.preferred_changed = false;
+    replay::record_frame(ctx);
+
+    !self.confirm_quit()
+  }
+
+  /// gives the game a chance to veto a pending quit request. Returns true if
+  /// the engine should shut down.
+  fn confirm_quit(&mut self) -> bool {
+    if !self.ctx.quit_requested {
+      return false;
+    }
+    self.ctx.quit_requested = false;
+    match &mut self.app {
+      Some(app) => app.on_quit_requested(&mut self.ctx),
+      None => true,
+    }
+  }
+
+  /// lets the game clean up and releases engine resources. Safe to call more
+  /// than once; only the first call has any effect.
+  pub(crate) fn shutdown(&mut self) {
+    let ctx = &mut self.ctx;
+    if ctx.cleaned_up {
+      return;
+    }
+    ctx.cleaned_up = true;
+    if let Some(app) = &mut self.app {
+      app.cleanup(ctx);
+    }
+    graphics::shutdown(ctx);
+  }
+
+  /// Shuts down and drops the game, so anything it saves or flushes when
+  /// dropped gets done before the process exits
+  pub(crate) fn shutdown_and_drop(&mut self) {
+    self.shutdown();
+    self.app = None;
+  }
+
+  /// updates input state and calls game hooks for an event from the window
+  /// or a replay
+  pub(crate) fn handle_event(&mut self, event: SAppEvent) {
+    let (app, ctx) = match (&mut self.app, &mut self.ctx) {
+      (Some(app), ctx) => (app, ctx),
+      (None, _) => return,
+    };
+
+    // key and mouse events carry the current modifier key state
+    match event.event_type {
+      SAppEventType::KeyDown
+      | SAppEventType::KeyUp
+      | SAppEventType::MouseDown
+      | SAppEventType::MouseUp => ctx.input.keyboard.modifiers = event.modifiers,
+      _ => {}
+    }
+
+    // whatever the player used last is what they're playing with. Browsers
+    // send fake mouse events after touches, so those are ignored.
+    match event.event_type {
+      SAppEventType::KeyDown
+      | SAppEventType::MouseDown
+      | SAppEventType::MouseMove
+      | SAppEventType::MouseScroll
+        if ctx.input.touches.touches.is_empty() =>
+      {
+        input::use_input(&mut ctx.input, InputType::MouseKeyboard);
+      }
+      SAppEventType::TouchesBegan => input::use_input(&mut ctx.input, InputType::Touch),
+      _ => {}
+    }
+
+    // sokol_app doesn't know about gamepads, so they're polled separately
+    // at the start of each frame. See `poll_gamepads`.
+    match event.event_type {
+      SAppEventType::Resized => {
+        let w = event.framebuffer_width as f32;
+        let h = event.framebuffer_height as f32;
+        app.resized(ctx, w, h);
+      }
+      SAppEventType::Iconified => app.minimized(ctx),
+      SAppEventType::Restored => app.restored(ctx),
+      SAppEventType::Suspended => app.suspended(ctx),
+      SAppEventType::Resumed => app.resumed(ctx),
+      SAppEventType::MouseMove => {
+        ctx.input.mouse.moved(vec2(event.mouse_x, event.mouse_y));
+      }
+      SAppEventType::MouseScroll => {
+        ctx.input.mouse.scroll_x += event.scroll_x;
+
+        ctx.input.mouse.scroll_y += event.scroll_y;
+      }
+      SAppEventType::MouseDown => {
+        let time = ctx.time.real_elapsed;
+        ctx.input.mouse.press(event.mouse_button, time);
+      }
+      SAppEventType::MouseUp => {
+        let time = ctx.time.real_elapsed;
+        ctx.input.mouse.release(event.mouse_button, time);
+      }
+      SAppEventType::KeyDown => {
+        ctx.input.keyboard.key_down(event.key_code);
+        if let Some(input) = input::text_input_from_key(event.key_code, event.modifiers) {
+          let modifiers = event.modifiers;
+          ctx.input.text.events.push(TextEvent { input, modifiers });
+        }
+      }
+      SAppEventType::Char => {
+        if let Some(input) = input::text_input_from_char(event.char_code, event.modifiers) {
+          let modifiers = event.modifiers;
+          ctx.input.text.events.push(TextEvent { input, modifiers });
+        }
+      }
+      SAppEventType::KeyUp => ctx.input.keyboard.key_up(event.key_code),
+      SAppEventType::TouchesBegan
+      | SAppEventType::TouchesMoved
+      | SAppEventType::TouchesEnded
+      | SAppEventType::TouchesCancelled => {
+        let time = ctx.time.real_elapsed;
+        input::handle_touch_event(&mut ctx.input.touches, &event, time);
+      }
+
+      _ => {}
+    }
+  }
 }
 
 /// Your game should implement this trait.
@@ -413,90 +1651,134 @@ pub trait KApp: 'static + Sized {
   /// called once after the window is initialized
   fn init(&mut self, ctx: &mut Ctx);
 
-  /// called each frame of the main loop - loop behavior and
-  /// frequency can be configured via `KAppDesc`
-  fn frame(&mut self, ctx: &mut Ctx);
+  /// called instead of `init` if the engine can't start, e.g. when the
+  /// graphics backend isn't supported. The game has already been dropped,
+  /// and the process exits once this returns. Prints the error by default.
+  fn init_failed(error: &Error) {
+    println!("{}", error);
+  }
+
+  /// called each frame of the main loop, before any fixed updates - loop
+  /// behavior and frequency can be configured via `KAppDesc`. Input counts
+  /// are per frame, so read clicks and key presses here rather than in `update`.
+  fn frame(&mut self, ctx: &mut Ctx) {}
+
+  /// called zero or more times per frame with a constant `dt` (see `StepCtx`)
+  /// so that simulation runs at the same speed regardless of refresh rate
+  fn update(&mut self, ctx: &mut Ctx, dt: f32) {}
+
+  /// called once per frame after all fixed updates. `alpha` is how far the
+  /// frame sits between the previous and next update, for interpolation.
+  fn draw(&mut self, ctx: &mut Ctx, alpha: f32) {}
+
+  /// called once before the engine shuts down - save game state here. The
+  /// game is dropped right after this returns, and then the process exits,
+  /// since sokol_app can't return from its loop yet. Anything left in a
+  /// `Drop` impl still runs, but `run` never returns.
+  fn cleanup(&mut self, ctx: &mut Ctx) {}
+
+  /// called when a quit is requested via `request_quit` or the system quit
+  /// shortcut. Return false to keep running, e.g. to show a confirmation.
+  /// Closing the window can't be intercepted yet, but `cleanup` still runs.
+  fn on_quit_requested(&mut self, ctx: &mut Ctx) -> bool {
+    true
+  }
+
+  /// called when the window's size changes, with the new size in device pixels
+  fn resized(&mut self, ctx: &mut Ctx, w: f32, h: f32) {}
+
+  /// called when the window is minimized. sokol_app doesn't report keyboard
+  /// focus, so there's no hook for the window merely losing it.
+  fn minimized(&mut self, ctx: &mut Ctx) {}
+
+  /// called when the window is restored after being minimized
+  fn restored(&mut self, ctx: &mut Ctx) {}
+
+  /// called when the app is sent to the background on mobile platforms
+  fn suspended(&mut self, ctx: &mut Ctx) {}
+
+  /// called when the app returns to the foreground on mobile platforms
+  fn resumed(&mut self, ctx: &mut Ctx) {}
+
+  /// called at the start of a frame when the player has switched to a
+  /// different kind of input, e.g. to swap the button prompts on screen
+  fn preferred_input_changed(&mut self, ctx: &mut Ctx, input: InputType) {}
 }
 
 impl<K: KApp> SApp for App<K> {
   fn sapp_init(&mut self) {
     let ctx = &mut self.ctx;
-    graphics::init(ctx);
-    self.app.init(ctx);
+    // nothing can be drawn without graphics, so there's no way to carry on
+    if let Err(e) = graphics::init(ctx) {
+      self.app = None;
+      K::init_failed(&e);
+      std::process::exit(1);
+    }
+    // installed before `init` so the game can swap in its own backend
+    if ctx.input.gamepads.backend.is_none() {
+      ctx.input.gamepads.backend = default_gamepad_backend();
+    }
+    if let Some(app) = &mut self.app {
+      app.init(ctx);
+    }
   }
 
   fn sapp_frame(&mut self) {
-    let ctx = &mut self.ctx;
-    ctx.frame_count += 1;
-    ctx.gfx.view_proj = ctx.gfx.proj * ctx.gfx.view;
-    self.app.frame(ctx);
-    graphics::present(ctx);
-
-    // input cleanup
-    ctx.input.mouse.frame_end();
+    let real_delta = self.ctx.time.measure();
+    if !self.frame(real_delta) {
+      // sokol_app 0.3 can't leave its loop programmatically: there's no
+      // sapp_request_quit, only a TODO for one in sokol_app.h. So the game
+      // is dropped here rather than when `run` returns, then the process
+      // exits
+      self.shutdown_and_drop();
+      std::process::exit(0);
+    }
   }
 
   fn sapp_cleanup(&mut self) {
-    std::process::exit(0);
+    // the window was closed
+    self.shutdown_and_drop();
   }
 
   fn sapp_event(&mut self, event: SAppEvent) {
-    let ctx = &mut self.ctx;
-
     // check for system exit shortcut
     if event.event_type == SAppEventType::KeyDown
       && event.modifiers.contains(SAppModifier::SUPER)
       && (event.key_code == SAppKeycode::KeyW || event.key_code == SAppKeycode::KeyQ)
     {
-      std::process::exit(0)
+      request_quit(&mut self.ctx);
     }
 
-    // TODO... sapp for events vs sdl? how do I handle gamepad input?
-    match event.event_type {
-      SAppEventType::MouseMove => {
-        ctx.input.mouse.pos = vec2(event.mouse_x, event.mouse_y);
-      }
-      SAppEventType::MouseScroll => {
-        ctx.input.mouse.scroll_x += event.scroll_x;
-
-        ctx.input.mouse.scroll_y += event.scroll_y;
-      }
-      SAppEventType::MouseDown => match event.mouse_button {
-        SAppMouseButton::Left => ctx.input.mouse.left.down += 1,
-        SAppMouseButton::Right => ctx.input.mouse.right.down += 1,
-        SAppMouseButton::Middle => ctx.input.mouse.middle.down += 1,
-        _ => {}
-      },
-      SAppEventType::MouseUp => match event.mouse_button {
-        SAppMouseButton::Left => ctx.input.mouse.left.up += 1,
-        SAppMouseButton::Right => ctx.input.mouse.right.up += 1,
-        SAppMouseButton::Middle => ctx.input.mouse.middle.up += 1,
-        _ => {}
-      },
-      SAppEventType::KeyDown => match event.key_code {
-        SAppKeycode::KeyW => ctx.input.dir_u = true,
-        SAppKeycode::KeyA => ctx.input.dir_l = true,
-        SAppKeycode::KeyD => ctx.input.dir_r = true,
-        SAppKeycode::KeyS => ctx.input.dir_d = true,
-        _ => {}
-      },
-      SAppEventType::KeyUp => match event.key_code {
-        SAppKeycode::KeyW => ctx.input.dir_u = false,
-        SAppKeycode::KeyA => ctx.input.dir_l = false,
-        SAppKeycode::KeyD => ctx.input.dir_r = false,
-        SAppKeycode::KeyS => ctx.input.dir_d = false,
-        _ => {}
-      },
-
-      _ => {}
+    // recorded input stands in for the real devices during a replay
+    if is_replaying(&self.ctx) && replay::is_input_event(&event) {
+      return;
     }
+    replay::record_event(&mut self.ctx, &event);
+    self.handle_event(event);
   }
 }
 
+/// Opens the window and runs the game until it quits. When the game quits
+/// itself this never returns, since sokol_app has no way to leave its loop:
+/// the process exits once `KApp::cleanup` has run. Closing the window
+/// returns on some platforms and not at all on others.
 pub fn run<K: KApp>(desc: KAppDesc) {
-  let ctx: Ctx = Default::default();
+  run_with_config::<K>(desc, Default::default());
+}
+
+/// Like `run`, but with control over engine settings like how many draw
+/// calls to allocate room for.
+pub fn run_with_config<K: KApp>(desc: KAppDesc, config: KConfig) {
+  let mut ctx: Ctx = Default::default();
+  ctx.gfx.config = config.gfx;
   let app: K = K::new();
-  sapp_run(App { ctx, app }, desc);
+  sapp_run(
+    App {
+      ctx,
+      app: Some(app),
+    },
+    desc,
+  );
 }
 
 pub fn application_root_dir() -> PathBuf {
@@ -521,23 +1803,104 @@ pub fn application_root_dir() -> PathBuf {
   }
 }
 
-// ----------------------------------------------------------------------------
-// Gamepad input
-
-// TODO test these
-// const AXIS_MIN: i16 = 4;
-// const AXIS_MAX: i32 = 1000;
-
-// fn get_normalized_gamepad_axis_value(controller: GameController, axis: Axis) -> f32 {
-//   let value = controller.axis(axis);
-//   let magnitude = value.abs();
-//   if magnitude < AXIS_MIN {
-//     return 0.0;
-//   }
-//   let normalized_magnitude = ((magnitude - AXIS_MIN) as f32) / (AXIS_MAX as f32);
-//   if value > 0 {
-//     normalized_magnitude
-//   } else {
-//     -normalized_magnitude
-//   }
-// }
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn assert_near(a: f32, b: f32) {
+    assert!((a - b).abs() < 1e-4, "{} isn't {}", a, b);
+  }
+
+  #[test]
+  fn steps_once_per_dt_and_carries_the_rest() {
+    let mut step = StepCtx {
+      dt: 0.1,
+      ..Default::default()
+    };
+    assert_eq!(step.advance(0.05), 0);
+    assert_near(step.alpha, 0.5);
+    assert_eq!(step.advance(0.075), 1);
+    assert_near(step.alpha, 0.25);
+    assert_eq!(step.advance(0.2), 2);
+    assert_near(step.alpha, 0.25);
+  }
+
+  #[test]
+  fn drops_the_backlog_past_max_steps() {
+    let mut step = StepCtx {
+      dt: 0.1,
+      max_steps: 3,
+      ..Default::default()
+    };
+    // a 1.05s hitch runs 3 steps rather than 10, then everything is back
+    // to normal on the next frame
+    assert_eq!(step.advance(1.05), 3);
+    assert_near(step.alpha, 0.5);
+    assert_eq!(step.advance(0.1), 1);
+    assert_near(step.alpha, 0.5);
+  }
+
+  #[test]
+  fn alpha_stays_below_one() {
+    let mut step = StepCtx {
+      dt: 0.1,
+      max_steps: 2,
+      ..Default::default()
+    };
+    // frames that leave a full step or more past max_steps
+    for elapsed in [0.3, 0.2999, 0.0999, 1.0].iter() {
+      step.advance(*elapsed);
+      assert!(
+        step.alpha >= 0.0 && step.alpha < 1.0,
+        "alpha {}",
+        step.alpha
+      );
+    }
+  }
+
+  fn full_buffer(overflow: Overflow) -> DrawBuffer<u32> {
+    let mut buffer = DrawBuffer::new(PoolConfig::new(2, overflow));
+    assert!(buffer.push(0, "test"));
+    assert!(buffer.push(1, "test"));
+    buffer
+  }
+
+  #[test]
+  fn grows_when_full() {
+    let mut buffer = full_buffer(Overflow::Grow);
+    assert!(buffer.push(2, "test"));
+    assert_eq!(buffer.capacity, 4);
+    assert!(buffer.push(3, "test"));
+    assert!(buffer.push(4, "test"));
+    assert_eq!(buffer.capacity, 8);
+    assert_eq!(buffer.e, [0, 1, 2, 3, 4]);
+
+    // an empty pool still grows
+    let mut buffer = DrawBuffer::new(PoolConfig::new(0, Overflow::Grow));
+    assert!(buffer.push(0, "test"));
+    assert_eq!(buffer.capacity, 1);
+  }
+
+  #[test]
+  fn drops_when_full_and_warns_once_a_frame() {
+    let mut buffer = full_buffer(Overflow::Drop);
+    assert!(!buffer.warned);
+    assert!(!buffer.push(2, "test"));
+    assert!(buffer.warned);
+    assert!(!buffer.push(3, "test"));
+    assert_eq!(buffer.e, [0, 1]);
+    assert_eq!(buffer.capacity, 2);
+
+    // a new frame can warn again
+    buffer.clear();
+    assert!(!buffer.warned);
+    assert!(buffer.push(0, "test"));
+  }
+
+  #[test]
+  #[should_panic(expected = "Too many test draw calls! Maximum is 2")]
+  fn panics_when_full() {
+    let mut buffer = full_buffer(Overflow::Panic);
+    buffer.push(2, "test");
+  }
+}
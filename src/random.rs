@@ -0,0 +1,39 @@
+//! Seedable random numbers. Drawing from the engine's generator instead of
+//! `rand::random` makes a game reproducible: the same seed and the same input
+//! give the same results, which is what makes replays work.
+
+use crate::*;
+use rand::distributions::uniform::SampleUniform;
+use rand::distributions::{Distribution, Standard};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Restarts the random number generator from the given seed
+pub fn seed_random(ctx: &mut Ctx, seed: u64) {
+  ctx.random.seed = seed;
+  ctx.random.rng = StdRng::seed_from_u64(seed);
+}
+
+/// the seed the random number generator was last started from
+pub fn random_seed(ctx: &Ctx) -> u64 {
+  ctx.random.seed
+}
+
+/// a random value of any type `rand` knows how to generate, e.g. a float
+/// from 0 to 1
+pub fn random<T>(ctx: &mut Ctx) -> T
+where
+  Standard: Distribution<T>,
+{
+  ctx.random.rng.gen()
+}
+
+/// a random value from `low` up to but not including `high`
+pub fn random_range<T: SampleUniform>(ctx: &mut Ctx, low: T, high: T) -> T {
+  ctx.random.rng.gen_range(low, high)
+}
+
+/// the generator itself, for anything else `rand` can do like shuffling
+pub fn rng(ctx: &mut Ctx) -> &mut StdRng {
+  &mut ctx.random.rng
+}
@@ -0,0 +1,566 @@
+//! Records player input so it can be played back later, e.g. to attach a
+//! replay to a bug report or to reproduce a desync.
+//!
+//! A recording holds every input event that reached the game, which frame it
+//! arrived on and how much time each frame took, along with the random seed
+//! in use when recording started. Replaying it feeds the same events in on
+//! the same frames while ignoring the real devices, so a game that draws its
+//! random numbers from `random` plays out the same way frame for frame.
+//!
+//! Start recording or replaying during `KApp::init`, before the game draws
+//! any random numbers.
+
+use crate::input::*;
+use crate::*;
+use sokol::app::*;
+use std::fs;
+use std::path::Path;
+
+// file layout: MAGIC, VERSION, the seed, then records until the end
+const MAGIC: &[u8; 4] = b"KREC";
+const VERSION: u8 = 1;
+
+// record tags
+const FRAME: u8 = 0;
+const INPUT: u8 = 1;
+const GAMEPAD: u8 = 2;
+
+/// Recorded input, see the module docs. Save it with `save_recording`.
+#[derive(Clone, Default, Debug)]
+pub struct Recording {
+  /// the random seed when recording started
+  pub seed: u64,
+  records: Vec<Record>,
+}
+
+impl Recording {
+  /// the number of frames recorded
+  pub fn frames(&self) -> usize {
+    self
+      .records
+      .iter()
+      .filter(|record| matches!(record, Record::Frame(_)))
+      .count()
+  }
+}
+
+/// A single recorded happening. The events for a frame come before the
+/// `Frame` record that ends it.
+#[derive(Clone, Debug)]
+enum Record {
+  /// the end of a frame, and the real time it took
+  Frame(f32),
+  Input(InputRecord),
+  Gamepad(GamepadEvent),
+}
+
+/// the parts of an `SAppEvent` that input handling reads
+#[derive(Clone, Debug)]
+struct InputRecord {
+  event_type: SAppEventType,
+  key_code: Keycode,
+  char_code: u32,
+  modifiers: Modifiers,
+  mouse_button: MouseButton,
+  mouse_x: f32,
+  mouse_y: f32,
+  scroll_x: f32,
+  scroll_y: f32,
+  touches: Vec<SAppTouchPoint>,
+}
+
+// ----------------------------------------------------------------------------
+// recording and replaying
+
+/// Starts recording input, dropping any recording in progress. Reseeds the
+/// random number generator so the replay can start from the same seed.
+pub fn start_recording(ctx: &mut Ctx) {
+  let seed = rand::random();
+  seed_random(ctx, seed);
+  ctx.replay.replay = None;
+  ctx.replay.recording = Some(Recording {
+    seed,
+    records: vec![],
+  });
+}
+
+/// Stops recording and returns what was recorded, if anything was
+pub fn stop_recording(ctx: &mut Ctx) -> Option<Recording> {
+  ctx.replay.recording.take()
+}
+
+pub fn is_recording(ctx: &Ctx) -> bool {
+  ctx.replay.recording.is_some()
+}
+
+/// Plays back a recording from the start of the next frame, ignoring input
+/// from real devices until it runs out. Stops any recording in progress.
+pub fn start_replay(ctx: &mut Ctx, recording: Recording) {
+  seed_random(ctx, recording.seed);
+  ctx.replay.recording = None;
+  ctx.replay.gamepad_events.clear();
+  ctx.replay.replay = Some((recording, 0));
+}
+
+/// Stops a replay early and goes back to the real devices
+pub fn stop_replay(ctx: &mut Ctx) {
+  ctx.replay.replay = None;
+}
+
+pub fn is_replaying(ctx: &Ctx) -> bool {
+  ctx.replay.replay.is_some()
+}
+
+/// whether the event comes from an input device rather than the window
+pub(crate) fn is_input_event(event: &SAppEvent) -> bool {
+  matches!(
+    event.event_type,
+    SAppEventType::KeyDown
+      | SAppEventType::KeyUp
+      | SAppEventType::Char
+      | SAppEventType::MouseDown
+      | SAppEventType::MouseUp
+      | SAppEventType::MouseScroll
+      | SAppEventType::MouseMove
+      | SAppEventType::MouseEnter
+      | SAppEventType::MouseLeave
+      | SAppEventType::TouchesBegan
+      | SAppEventType::TouchesMoved
+      | SAppEventType::TouchesEnded
+      | SAppEventType::TouchesCancelled
+  )
+}
+
+pub(crate) fn record_event(ctx: &mut Ctx, event: &SAppEvent) {
+  let recording = match &mut ctx.replay.recording {
+    Some(recording) if is_input_event(event) => recording,
+    _ => return,
+  };
+  let touches = &event.touches[..event.num_touches as usize];
+  recording.records.push(Record::Input(InputRecord {
+    event_type: event.event_type,
+    key_code: event.key_code,
+    char_code: event.char_code,
+    modifiers: event.modifiers,
+    mouse_button: event.mouse_button,
+    mouse_x: event.mouse_x,
+    mouse_y: event.mouse_y,
+    scroll_x: event.scroll_x,
+    scroll_y: event.scroll_y,
+    touches: touches.to_vec(),
+  }));
+}
+
+pub(crate) fn record_gamepad_event(ctx: &mut Ctx, event: &GamepadEvent) {
+  if let Some(recording) = &mut ctx.replay.recording {
+    recording.records.push(Record::Gamepad(event.clone()));
+  }
+}
+
+/// marks the end of a frame in the recording
+pub(crate) fn record_frame(ctx: &mut Ctx) {
+  let real_delta = ctx.time.real_delta;
+  if let Some(recording) = &mut ctx.replay.recording {
+    recording.records.push(Record::Frame(real_delta));
+  }
+}
+
+/// Reads the next frame of a replay, if one is running. Returns the real
+/// time the frame took and the window events to handle before it. Gamepad
+/// events are queued for `poll_gamepads`.
+pub(crate) fn next_replay_frame(ctx: &mut Ctx) -> Option<(f32, Vec<SAppEvent>)> {
+  let (recording, pos) = ctx.replay.replay.as_mut()?;
+  let mut events = vec![];
+  while let Some(record) = recording.records.get(*pos) {
+    *pos += 1;
+    match record {
+      Record::Frame(real_delta) => return Some((*real_delta, events)),
+      Record::Input(input) => events.push(input.to_sapp_event(ctx.frame_count)),
+      Record::Gamepad(event) => ctx.replay.gamepad_events.push(event.clone()),
+    }
+  }
+
+  // out of frames, so hand control back to the real devices
+  ctx.replay.replay = None;
+  None
+}
+
+impl InputRecord {
+  fn to_sapp_event(&self, frame_count: u32) -> SAppEvent {
+    let mut event = blank_sapp_event(frame_count);
+    event.event_type = self.event_type;
+    event.key_code = self.key_code;
+    event.char_code = self.char_code;
+    event.modifiers = self.modifiers;
+    event.mouse_button = self.mouse_button;
+    event.mouse_x = self.mouse_x;
+    event.mouse_y = self.mouse_y;
+    event.scroll_x = self.scroll_x;
+    event.scroll_y = self.scroll_y;
+    event.num_touches = self.touches.len() as i32;
+    event.touches[..self.touches.len()].copy_from_slice(&self.touches);
+    event
+  }
+}
+
+// ----------------------------------------------------------------------------
+// saving and loading
+
+/// Writes a recording to a file in kit's compact binary format
+pub fn save_recording(recording: &Recording, path: &Path) -> Result<(), Error> {
+  fs::write(path, encode(recording)).map_err(|source| Error::Io {
+    path: path.to_path_buf(),
+    source,
+  })
+}
+
+/// Reads a recording written by `save_recording`
+pub fn load_recording(path: &Path) -> Result<Recording, Error> {
+  let bytes = fs::read(path).map_err(|source| Error::Io {
+    path: path.to_path_buf(),
+    source,
+  })?;
+  decode(&bytes).ok_or_else(|| Error::Recording {
+    path: path.to_path_buf(),
+  })
+}
+
+// all numbers are little endian. Positions are stored as f32 and enums as
+// their discriminant.
+
+fn encode(recording: &Recording) -> Vec<u8> {
+  let mut out = vec![];
+  out.extend_from_slice(MAGIC);
+  out.push(VERSION);
+  out.extend_from_slice(&recording.seed.to_le_bytes());
+
+  for record in &recording.records {
+    match record {
+      Record::Frame(real_delta) => {
+        out.push(FRAME);
+        out.extend_from_slice(&real_delta.to_le_bytes());
+      }
+      Record::Input(input) => {
+        out.push(INPUT);
+        encode_input(&mut out, input);
+      }
+      Record::Gamepad(event) => {
+        out.push(GAMEPAD);
+        encode_gamepad(&mut out, event);
+      }
+    }
+  }
+  out
+}
+
+/// only writes the fields each kind of event uses
+fn encode_input(out: &mut Vec<u8>, input: &InputRecord) {
+  out.push(input.event_type as u8);
+  out.push(input.modifiers.bits() as u8);
+  let f32s = |out: &mut Vec<u8>, values: &[f32]| {
+    for value in values {
+      out.extend_from_slice(&value.to_le_bytes());
+    }
+  };
+
+  match input.event_type {
+    SAppEventType::KeyDown | SAppEventType::KeyUp => {
+      out.extend_from_slice(&(input.key_code as u16).to_le_bytes());
+    }
+    SAppEventType::Char => out.extend_from_slice(&input.char_code.to_le_bytes()),
+    SAppEventType::MouseDown | SAppEventType::MouseUp => {
+      out.push((input.mouse_button as i32 + 1) as u8);
+      f32s(out, &[input.mouse_x, input.mouse_y]);
+    }
+    SAppEventType::MouseScroll => f32s(out, &[input.scroll_x, input.scroll_y]),
+    SAppEventType::MouseMove | SAppEventType::MouseEnter | SAppEventType::MouseLeave => {
+      f32s(out, &[input.mouse_x, input.mouse_y]);
+    }
+    _ => {
+      out.push(input.touches.len() as u8);
+      for touch in &input.touches {
+        out.extend_from_slice(&(touch.identifier as u64).to_le_bytes());
+        f32s(out, &[touch.pos_x, touch.pos_y]);
+        out.push(touch.changed as u8);
+      }
+    }
+  }
+}
+
+fn encode_gamepad(out: &mut Vec<u8>, event: &GamepadEvent) {
+  let id = |out: &mut Vec<u8>, id: usize| out.extend_from_slice(&(id as u32).to_le_bytes());
+  match event {
+    GamepadEvent::Connected { id: pad, name } => {
+      out.push(0);
+      id(out, *pad);
+      out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+      out.extend_from_slice(name.as_bytes());
+    }
+    GamepadEvent::Disconnected { id: pad } => {
+      out.push(1);
+      id(out, *pad);
+    }
+    GamepadEvent::ButtonDown { id: pad, button } => {
+      out.push(2);
+      id(out, *pad);
+      out.push(*button as u8);
+    }
+    GamepadEvent::ButtonUp { id: pad, button } => {
+      out.push(3);
+      id(out, *pad);
+      out.push(*button as u8);
+    }
+    GamepadEvent::Axis {
+      id: pad,
+      axis,
+      value,
+    } => {
+      out.push(4);
+      id(out, *pad);
+      out.push(*axis as u8);
+      out.extend_from_slice(&value.to_le_bytes());
+    }
+  }
+}
+
+/// reads through a byte slice, failing once it runs out
+struct Reader<'a> {
+  bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+    if self.bytes.len() < n {
+      return None;
+    }
+    let (taken, rest) = self.bytes.split_at(n);
+    self.bytes = rest;
+    Some(taken)
+  }
+
+  fn u8(&mut self) -> Option<u8> {
+    Some(self.take(1)?[0])
+  }
+
+  fn u16(&mut self) -> Option<u16> {
+    let mut bytes = [0; 2];
+    bytes.copy_from_slice(self.take(2)?);
+    Some(u16::from_le_bytes(bytes))
+  }
+
+  fn u32(&mut self) -> Option<u32> {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(self.take(4)?);
+    Some(u32::from_le_bytes(bytes))
+  }
+
+  fn u64(&mut self) -> Option<u64> {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(self.take(8)?);
+    Some(u64::from_le_bytes(bytes))
+  }
+
+  fn f32(&mut self) -> Option<f32> {
+    Some(f32::from_bits(self.u32()?))
+  }
+}
+
+fn decode(bytes: &[u8]) -> Option<Recording> {
+  let mut r = Reader { bytes };
+  if r.take(4)? != MAGIC || r.u8()? != VERSION {
+    return None;
+  }
+  let seed = r.u64()?;
+
+  let mut records = vec![];
+  while !r.bytes.is_empty() {
+    let record = match r.u8()? {
+      FRAME => Record::Frame(r.f32()?),
+      INPUT => Record::Input(decode_input(&mut r)?),
+      GAMEPAD => Record::Gamepad(decode_gamepad(&mut r)?),
+      _ => return None,
+    };
+    records.push(record);
+  }
+  Some(Recording { seed, records })
+}
+
+fn decode_input(r: &mut Reader) -> Option<InputRecord> {
+  let event_type = *EVENT_TYPES.get(r.u8()? as usize)?;
+  let modifiers = Modifiers::from_bits_truncate(r.u8()? as u32);
+  let mut input = InputRecord {
+    event_type,
+    key_code: Keycode::KeyInvalid,
+    char_code: 0,
+    modifiers,
+    mouse_button: MouseButton::Invalid,
+    mouse_x: 0.0,
+    mouse_y: 0.0,
+    scroll_x: 0.0,
+    scroll_y: 0.0,
+    touches: vec![],
+  };
+
+  match event_type {
+    SAppEventType::KeyDown | SAppEventType::KeyUp => {
+      // unmapped keys, like media keys on some platforms, come through as
+      // `KeyInvalid`, which isn't in the table of real keys
+      let code = r.u16()?;
+      input.key_code = ALL_KEYCODES
+        .iter()
+        .copied()
+        .find(|key| *key as u16 == code)
+        .unwrap_or(Keycode::KeyInvalid);
+    }
+    SAppEventType::Char => input.char_code = r.u32()?,
+    SAppEventType::MouseDown | SAppEventType::MouseUp => {
+      input.mouse_button = match r.u8()? {
+        1 => MouseButton::Left,
+        2 => MouseButton::Right,
+        3 => MouseButton::Middle,
+        _ => MouseButton::Invalid,
+      };
+      input.mouse_x = r.f32()?;
+      input.mouse_y = r.f32()?;
+    }
+    SAppEventType::MouseScroll => {
+      input.scroll_x = r.f32()?;
+      input.scroll_y = r.f32()?;
+    }
+    SAppEventType::MouseMove | SAppEventType::MouseEnter | SAppEventType::MouseLeave => {
+      input.mouse_x = r.f32()?;
+      input.mouse_y = r.f32()?;
+    }
+    SAppEventType::TouchesBegan
+    | SAppEventType::TouchesMoved
+    | SAppEventType::TouchesEnded
+    | SAppEventType::TouchesCancelled => {
+      let count = r.u8()? as usize;
+      if count > sokol::app::ffi::SAPP_MAX_TOUCHPOINTS {
+        return None;
+      }
+      for _ in 0..count {
+        input.touches.push(SAppTouchPoint {
+          identifier: r.u64()? as usize,
+          pos_x: r.f32()?,
+          pos_y: r.f32()?,
+          changed: r.u8()? != 0,
+        });
+      }
+    }
+    _ => return None,
+  }
+  Some(input)
+}
+
+fn decode_gamepad(r: &mut Reader) -> Option<GamepadEvent> {
+  let kind = r.u8()?;
+  let id = r.u32()? as usize;
+  Some(match kind {
+    0 => {
+      let len = r.u16()? as usize;
+      let name = String::from_utf8(r.take(len)?.to_vec()).ok()?;
+      GamepadEvent::Connected { id, name }
+    }
+    1 => GamepadEvent::Disconnected { id },
+    2 => GamepadEvent::ButtonDown {
+      id,
+      button: *ALL_GAMEPAD_BUTTONS.get(r.u8()? as usize)?,
+    },
+    3 => GamepadEvent::ButtonUp {
+      id,
+      button: *ALL_GAMEPAD_BUTTONS.get(r.u8()? as usize)?,
+    },
+    4 => GamepadEvent::Axis {
+      id,
+      axis: *ALL_GAMEPAD_AXES.get(r.u8()? as usize)?,
+      value: r.f32()?,
+    },
+    _ => return None,
+  })
+}
+
+/// every event type, indexed by discriminant
+const EVENT_TYPES: [SAppEventType; 20] = [
+  SAppEventType::Invalid,
+  SAppEventType::KeyDown,
+  SAppEventType::KeyUp,
+  SAppEventType::Char,
+  SAppEventType::MouseDown,
+  SAppEventType::MouseUp,
+  SAppEventType::MouseScroll,
+  SAppEventType::MouseMove,
+  SAppEventType::MouseEnter,
+  SAppEventType::MouseLeave,
+  SAppEventType::TouchesBegan,
+  SAppEventType::TouchesMoved,
+  SAppEventType::TouchesEnded,
+  SAppEventType::TouchesCancelled,
+  SAppEventType::Resized,
+  SAppEventType::Iconified,
+  SAppEventType::Restored,
+  SAppEventType::Suspended,
+  SAppEventType::Resumed,
+  SAppEventType::UpdateCursor,
+];
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn key(event_type: SAppEventType, key_code: Keycode) -> Record {
+    Record::Input(InputRecord {
+      event_type,
+      key_code,
+      char_code: 0,
+      modifiers: Modifiers::empty(),
+      mouse_button: MouseButton::Invalid,
+      mouse_x: 0.0,
+      mouse_y: 0.0,
+      scroll_x: 0.0,
+      scroll_y: 0.0,
+      touches: vec![],
+    })
+  }
+
+  #[test]
+  fn round_trips_through_the_file_format() {
+    let mut click = match key(SAppEventType::MouseDown, Keycode::KeyInvalid) {
+      Record::Input(input) => input,
+      _ => unreachable!(),
+    };
+    click.mouse_button = MouseButton::Right;
+    click.mouse_x = 12.5;
+    click.mouse_y = -3.0;
+    click.modifiers = Modifiers::SHIFT;
+
+    let recording = Recording {
+      seed: 0xDEAD_BEEF,
+      records: vec![
+        key(SAppEventType::KeyDown, Keycode::KeyA),
+        Record::Frame(1.0 / 60.0),
+        // sent for keys sokol has no code for, like media keys
+        key(SAppEventType::KeyDown, Keycode::KeyInvalid),
+        key(SAppEventType::KeyUp, Keycode::KeyInvalid),
+        Record::Input(click),
+        Record::Frame(1.0 / 30.0),
+      ],
+    };
+
+    let decoded = decode(&encode(&recording)).expect("recording should decode");
+    assert_eq!(decoded.seed, recording.seed);
+    assert_eq!(decoded.frames(), 2);
+    assert_eq!(
+      format!("{:?}", decoded.records),
+      format!("{:?}", recording.records)
+    );
+  }
+
+  #[test]
+  fn rejects_other_files() {
+    assert!(decode(b"not a recording").is_none());
+    let mut truncated = encode(&Recording::default());
+    truncated.pop();
+    assert!(decode(&truncated).is_none());
+  }
+}